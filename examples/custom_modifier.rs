@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{Context, DragValue, Ui};
+use serde::{Serialize, Deserialize};
+
+use mtb_terrain_gen::core::modifier::{RegisterModifier, TerrainModifier};
+use mtb_terrain_gen::core::planes::PlaneData;
+use mtb_terrain_gen::core::vertex::Vertex;
+use mtb_terrain_gen::editor::MTBEditorPlugin;
+use mtb_terrain_gen::editor::colors::Colors;
+
+// Editor with an extra modifier registered from outside of the crate
+// cargo run --example custom_modifier
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Flatten {
+    height: f32
+}
+
+impl Default for Flatten {
+    fn default() -> Self {
+        Flatten{height: 0.0}
+    }
+}
+
+impl TerrainModifier for Flatten {
+    fn name(&self) -> &'static str {
+        "Flatten"
+    }
+    fn apply(&self, _pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.loc[1] = self.height;
+        }
+    }
+    fn is_height(&self) -> bool {
+        true
+    }
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        ui.label("Flatten");
+        ui.add(DragValue::new(&mut self.height).speed(1.0));
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(MTBEditorPlugin)
+        .register_modifier::<Flatten>()
+        .run();
+}
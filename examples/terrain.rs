@@ -0,0 +1,33 @@
+use bevy::asset::ChangeWatcher;
+use bevy::prelude::*;
+use std::time::Duration;
+use mtb_terrain_gen::core::planes::PlanesAsset;
+use mtb_terrain_gen::core::terrain::MTBTerrainPlugin;
+
+// Loads terrain saved by the editor into a plain bevy app, saving again in the editor hot-reloads it
+// cargo run --example terrain --no-default-features
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins
+            .set(AssetPlugin {watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),..default()})
+        )
+        .add_plugins(MTBTerrainPlugin)
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, ass: Res<AssetServer>) {
+    commands.insert_resource(PlanesAsset(ass.load("saves/test.mtb.json")));
+
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(0.0, 500.0, 400.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {illuminance: 25000.0, shadows_enabled: true, ..default()},
+        transform: Transform::from_xyz(0.0, 2500.0, 1000.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
@@ -0,0 +1,48 @@
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::path::Path;
+use std::process::ExitCode;
+
+use mtb_terrain_gen::core::binary::BinaryOptions;
+use mtb_terrain_gen::core::files::write_file;
+use mtb_terrain_gen::core::modifier::ModifierRegistry;
+use mtb_terrain_gen::core::recipe::Recipe;
+use mtb_terrain_gen::core::save::SaveFormat;
+
+// Headless generator: reads recipe (.toml or .json) and writes save file readable by the editor
+// usage: mtb_recipe <recipe> <output.mtb.json|output.mtb>
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <recipe.toml|recipe.json> <output.mtb.json|output.mtb>", args[0]);
+        return ExitCode::FAILURE;
+    }
+
+    let recipe = match Recipe::load(&args[1]) {
+        Ok(recipe) => {recipe}
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let v_planes = match recipe.generate(&ModifierRegistry::new()) {
+        Ok(v_planes) => {v_planes}
+        Err(e) => {
+            eprintln!("Failed to generate {}: {}", args[1], e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let path = Path::new(&args[2]);
+    let format = SaveFormat::from_path(path, BinaryOptions::default());
+    if let Err(e) = write_file(path, 0, |writer| format.write(writer, &v_planes)) {
+        eprintln!("Failed to write {}: {}", args[2], e);
+        return ExitCode::FAILURE;
+    }
+
+    println!("Generated {} planes into {}", v_planes.len(), args[2]);
+    return ExitCode::SUCCESS;
+}
@@ -0,0 +1,436 @@
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Serialize, Deserialize};
+use std::io::{Read, Write};
+
+use super::flow::FlowData;
+use super::planes::{PlaneData, plane_mesh};
+use super::save::{PLANES_VERSION, SavePlaneData, load_plane};
+use super::version::{SaveError, as_object};
+use super::vertex::Vertex;
+
+// Binary container for saves (.mtb), much smaller and faster to read than json for dense planes.
+//
+// file:   magic "MTB1", u32 format version, u8 flags (1 = body is deflate compressed),
+//         u32 version of the plane json (see save::PLANES_MIGRATIONS), u32 body length before compression, body
+// body:   u32 plane count, planes
+// plane:  u32 header length, header json ({"plane": PlaneData, "flow": FlowData}),
+//         u8 positions (0 = heights only, 1 = x,y,z), u8 colors (0 = white, 1 = u8 rgba, 2 = f32 rgba),
+//         u32 vertex count, f32 heights or positions, colors
+// All numbers are little endian. Heights only is used when no vertex was moved off the grid,
+// x and z are then rebuilt from dims and subdivisions.
+// Version 1 files have no plane json version (it is 1) and no body length, their compressed
+// body is only limited by the largest possible deflate ratio.
+
+pub const BINARY_MAGIC: &[u8; 4] = b"MTB1";
+pub const BINARY_VERSION: u32 = 2;
+
+const MAX_DEFLATE_RATIO: usize = 1032;
+
+const FLAG_COMPRESSED: u8 = 1;
+
+const POSITIONS_HEIGHTS: u8 = 0;
+const POSITIONS_FULL: u8 = 1;
+
+const COLORS_NONE: u8 = 0;
+const COLORS_U8: u8 = 1;
+const COLORS_F32: u8 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct BinaryOptions {
+    pub compress:    bool,    // deflate the body
+    pub u8_colors:   bool     // quantize vertex colors to u8
+}
+
+impl Default for BinaryOptions {
+    fn default() -> Self {
+        BinaryOptions{compress: true, u8_colors: true}
+    }
+}
+
+#[derive(Serialize)]
+struct PlaneHeader {
+    plane:  PlaneData,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flow:   Option<FlowData>
+}
+
+pub fn write_binary<W: Write>(mut writer: W, planes: &[SavePlaneData], options: BinaryOptions) -> Result<(), SaveError> {
+    let mut body: Vec<u8> = Vec::new();
+    body.extend((planes.len() as u32).to_le_bytes());
+    for spd in planes.iter(){
+        write_plane(&mut body, spd, options)?;
+    }
+
+    let body_len = u32::try_from(body.len()).map_err(|_| SaveError::Io(format!("{} bytes of planes don't fit binary save", body.len())))?;
+    let flags = if options.compress {FLAG_COMPRESSED} else {0};
+    writer.write_all(BINARY_MAGIC).map_err(io_error)?;
+    writer.write_all(&BINARY_VERSION.to_le_bytes()).map_err(io_error)?;
+    writer.write_all(&[flags]).map_err(io_error)?;
+    writer.write_all(&PLANES_VERSION.to_le_bytes()).map_err(io_error)?;
+    writer.write_all(&body_len.to_le_bytes()).map_err(io_error)?;
+    if options.compress {
+        let mut encoder = DeflateEncoder::new(writer, Compression::default());
+        encoder.write_all(&body).map_err(io_error)?;
+        encoder.finish().map_err(io_error)?;
+    } else {
+        writer.write_all(&body).map_err(io_error)?;
+    }
+    return Ok(());
+}
+
+fn io_error(e: std::io::Error) -> SaveError {
+    SaveError::Io(e.to_string())
+}
+
+fn write_plane(body: &mut Vec<u8>, spd: &SavePlaneData, options: BinaryOptions) -> Result<(), SaveError> {
+    let header = serde_json::to_vec(&PlaneHeader{plane: spd.plane.clone(), flow: spd.flow.clone()})
+                 .map_err(|e| SaveError::Io(e.to_string()))?;
+    body.extend((header.len() as u32).to_le_bytes());
+    body.extend(header);
+
+    let mesh = spd.mesh();
+    let v_pos = mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap();
+    let v_clr: Vec<[f32; 4]> = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+        Some(VertexAttributeValues::Float32x4(v_clr)) => {v_clr.to_vec()}
+        _ => {vec![[1.0, 1.0, 1.0, 1.0]; v_pos.len()]}
+    };
+
+    let grid = plane_mesh(&spd.plane.subdivisions, &spd.plane.dims);
+    let grid_pos = grid.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap();
+    let moved = v_pos.iter().zip(grid_pos.iter()).any(|(p, g)| p[0] != g[0] || p[2] != g[2]);
+    let white = v_clr.iter().all(|c| *c == [1.0, 1.0, 1.0, 1.0]);
+
+    let positions = if moved {POSITIONS_FULL} else {POSITIONS_HEIGHTS};
+    let colors = match (white, options.u8_colors) {
+        (true, _)      => {COLORS_NONE}
+        (false, true)  => {COLORS_U8}
+        (false, false) => {COLORS_F32}
+    };
+    body.extend([positions, colors]);
+    body.extend((v_pos.len() as u32).to_le_bytes());
+
+    for p in v_pos.iter(){
+        if moved {
+            body.extend(p[0].to_le_bytes());
+            body.extend(p[1].to_le_bytes());
+            body.extend(p[2].to_le_bytes());
+        } else {
+            body.extend(p[1].to_le_bytes());
+        }
+    }
+    for c in v_clr.iter(){
+        match colors {
+            COLORS_U8  => {body.extend(c.map(|v| (v.clamp(0.0, 1.0)*255.0).round() as u8));}
+            COLORS_F32 => {c.iter().for_each(|v| body.extend(v.to_le_bytes()));}
+            _ => {}
+        }
+    }
+    return Ok(());
+}
+
+
+pub fn load_binary(bytes: &[u8]) -> Result<Vec<SavePlaneData>, SaveError> {
+    let mut file = BinaryReader::new(bytes);
+    if file.take(4, "magic")? != BINARY_MAGIC {
+        return Err(SaveError::Field{path: "magic".to_string(), message: "not a binary save file".to_string()});
+    }
+    let version = file.u32("version")?;
+    if version > BINARY_VERSION {
+        return Err(SaveError::Version{found: version, supported: BINARY_VERSION});
+    }
+    let flags = file.u8("flags")?;
+    let (planes_version, body_len) = match version {
+        1 => {(1, None)}
+        _ => {(file.u32("planes version")?, Some(file.u32("body length")? as usize))}
+    };
+
+    let rest = &bytes[file.pos..];
+    let mut decompressed: Vec<u8> = Vec::new();
+    let body = if flags & FLAG_COMPRESSED != 0 {
+        // output is never read past the declared length, or what the input could hold
+        let max_len = rest.len().saturating_mul(MAX_DEFLATE_RATIO);
+        let limit = body_len.unwrap_or(max_len);
+        if limit > max_len {
+            return Err(SaveError::Field{path: "body".to_string(), message: format!("{} compressed bytes can't hold {} bytes", rest.len(), limit)});
+        }
+        DeflateDecoder::new(rest).take(limit as u64 + 1).read_to_end(&mut decompressed)
+                                 .map_err(|e| SaveError::Field{path: "body".to_string(), message: e.to_string()})?;
+        if decompressed.len() > limit {
+            return Err(SaveError::Field{path: "body".to_string(), message: format!("longer than {} bytes", limit)});
+        }
+        &decompressed[..]
+    } else {
+        rest
+    };
+    if let Some(len) = body_len {
+        if body.len() != len {
+            return Err(SaveError::Field{path: "body".to_string(), message: format!("{} bytes, expected {}", body.len(), len)});
+        }
+    }
+
+    let mut reader = BinaryReader::new(body);
+    let count = reader.u32("planes")?;
+    let mut planes: Vec<SavePlaneData> = Vec::new();
+    for i in 0..count {
+        planes.push(read_plane(&mut reader, planes_version, &format!("planes[{}]", i))?);
+    }
+    return Ok(planes);
+}
+
+// planes_version is the version of the header json
+fn read_plane(reader: &mut BinaryReader, planes_version: u32, path: &str) -> Result<SavePlaneData, SaveError> {
+    let header_len = reader.u32(&format!("{}.header", path))? as usize;
+    let header_bytes = reader.take(header_len, &format!("{}.header", path))?;
+    let value: serde_json::Value = serde_json::from_slice(header_bytes).map_err(|e| SaveError::Json(e.to_string()))?;
+    // header is a save plane without vertices, they follow it
+    let mut value = as_object(value, &format!("{}.header", path))?;
+    value.insert("vertex".to_string(), serde_json::Value::Array(Vec::new()));
+    let header = load_plane(serde_json::Value::Object(value), planes_version, path)?;
+
+    let positions = reader.u8(&format!("{}.positions", path))?;
+    let colors = reader.u8(&format!("{}.colors", path))?;
+    let count = reader.u32(&format!("{}.vertex", path))? as usize;
+
+    // checked before the grid is built, subdivisions come from the file
+    let [s0, s1] = header.plane.subdivisions;
+    let expected = (s0 as usize + 2).checked_mul(s1 as usize + 2);
+    if expected != Some(count) {
+        return Err(SaveError::Field{path: format!("{}.vertex", path),
+                                    message: format!("subdivisions {:?} don't match {} vertices", header.plane.subdivisions, count)});
+    }
+    let floats = match positions {
+        POSITIONS_HEIGHTS => {1}
+        POSITIONS_FULL    => {3}
+        other => {return Err(SaveError::Field{path: format!("{}.positions", path), message: format!("unknown layout {}", other)});}
+    };
+    let color_bytes = match colors {
+        COLORS_NONE => {0}
+        COLORS_U8   => {4}
+        COLORS_F32  => {16}
+        other => {return Err(SaveError::Field{path: format!("{}.colors", path), message: format!("unknown layout {}", other)});}
+    };
+    let size = count.checked_mul(floats*4 + color_bytes).filter(|size| *size <= reader.remaining());
+    if size.is_none() {
+        return Err(SaveError::Field{path: format!("{}.vertex", path), message: "unexpected end of file".to_string()});
+    }
+
+    let grid = plane_mesh(&header.plane.subdivisions, &header.plane.dims);
+    let grid_pos = grid.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap();
+
+    let mut vertex: Vec<Vertex> = Vec::with_capacity(count);
+    for (index, g) in grid_pos.iter().enumerate(){
+        let loc = match positions {
+            POSITIONS_HEIGHTS => {[g[0], reader.f32(&format!("{}.heights", path))?, g[2]]}
+            POSITIONS_FULL    => {
+                let p = format!("{}.positions", path);
+                [reader.f32(&p)?, reader.f32(&p)?, reader.f32(&p)?]
+            }
+            other => {return Err(SaveError::Field{path: format!("{}.positions", path), message: format!("unknown layout {}", other)});}
+        };
+        vertex.push(Vertex::new(index, &loc, &[1.0, 1.0, 1.0, 1.0]));
+    }
+
+    let p = format!("{}.colors", path);
+    for v in vertex.iter_mut(){
+        match colors {
+            COLORS_NONE => {break;}
+            COLORS_U8   => {
+                let c = reader.take(4, &p)?;
+                v.clr = [c[0] as f32/255.0, c[1] as f32/255.0, c[2] as f32/255.0, c[3] as f32/255.0];
+            }
+            COLORS_F32  => {v.clr = [reader.f32(&p)?, reader.f32(&p)?, reader.f32(&p)?, reader.f32(&p)?];}
+            other => {return Err(SaveError::Field{path: p, message: format!("unknown layout {}", other)});}
+        }
+    }
+
+    return Ok(SavePlaneData{plane: header.plane, vertex, flow: header.flow});
+}
+
+
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    pos:   usize
+}
+
+impl<'a> BinaryReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BinaryReader{bytes, pos: 0}
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize, path: &str) -> Result<&'a [u8], SaveError> {
+        if n > self.remaining() {
+            return Err(SaveError::Field{path: path.to_string(), message: "unexpected end of file".to_string()});
+        }
+        let slice = &self.bytes[self.pos..self.pos+n];
+        self.pos += n;
+        return Ok(slice);
+    }
+
+    fn u8(&mut self, path: &str) -> Result<u8, SaveError> {
+        Ok(self.take(1, path)?[0])
+    }
+
+    fn u32(&mut self, path: &str) -> Result<u32, SaveError> {
+        Ok(u32::from_le_bytes(self.take(4, path)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self, path: &str) -> Result<f32, SaveError> {
+        Ok(f32::from_le_bytes(self.take(4, path)?.try_into().unwrap()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::save::{load_planes, write_planes};
+
+    // magic, version, flags, plane json version
+    const BODY_LEN_AT: usize = 13;
+
+    // plane with raised vertices, moved ones are shifted off the grid in x
+    fn test_plane(moved: bool, colored: bool) -> SavePlaneData {
+        let mut spd = SavePlaneData::from_pd(&PlaneData::new());
+        spd.plane.subdivisions = [3, 5];
+        spd.vertex = spd.plane.base_vertices();
+        for v in spd.vertex.iter_mut(){
+            v.loc[1] = v.index as f32*0.5;
+            if moved && v.index % 3 == 0 {
+                v.loc[0] += 1.5;
+            }
+            if colored {
+                v.clr = [0.2, 0.4, 1.0, 1.0];
+            }
+        }
+        return spd;
+    }
+
+    fn round_trip(planes: &[SavePlaneData], options: BinaryOptions) -> Vec<SavePlaneData> {
+        let mut bytes: Vec<u8> = Vec::new();
+        write_binary(&mut bytes, planes, options).unwrap();
+        return load_binary(&bytes).unwrap();
+    }
+
+    fn assert_same(a: &[SavePlaneData], b: &[SavePlaneData], clr_eps: f32) {
+        assert_eq!(a.len(), b.len());
+        for (pa, pb) in a.iter().zip(b.iter()){
+            assert_eq!(pa.plane, pb.plane);
+            assert_eq!(pa.vertex.len(), pb.vertex.len());
+            for (va, vb) in pa.vertex.iter().zip(pb.vertex.iter()){
+                assert_eq!(va.index, vb.index);
+                assert_eq!(va.loc, vb.loc);
+                assert!(va.clr.iter().zip(vb.clr.iter()).all(|(ca, cb)| (ca - cb).abs() <= clr_eps));
+            }
+        }
+    }
+
+    #[test]
+    fn json_and_binary_hold_the_same_planes() {
+        let planes = vec![test_plane(false, false), test_plane(true, true)];
+        let exact = BinaryOptions{compress: false, u8_colors: false};
+        for options in [BinaryOptions::default(), exact]{
+            let mut json: Vec<u8> = Vec::new();
+            write_planes(&mut json, &planes).unwrap();
+            let from_json = load_planes(&json).unwrap();
+            let from_binary = round_trip(&from_json, options);
+            let clr_eps = if options.u8_colors {0.5/255.0} else {0.0};
+            assert_same(&planes, &from_binary, clr_eps);
+
+            // and back to json
+            let mut json_again: Vec<u8> = Vec::new();
+            write_planes(&mut json_again, &from_binary).unwrap();
+            assert_same(&planes, &load_planes(&json_again).unwrap(), clr_eps);
+        }
+    }
+
+    #[test]
+    fn heights_only_layout_is_used_for_grid_planes() {
+        let options = BinaryOptions{compress: false, u8_colors: true};
+        let mut heights: Vec<u8> = Vec::new();
+        write_binary(&mut heights, &[test_plane(false, false)], options).unwrap();
+        let mut full: Vec<u8> = Vec::new();
+        write_binary(&mut full, &[test_plane(true, false)], options).unwrap();
+        assert_eq!(full.len() - heights.len(), 5*7*8); // x and z of each vertex
+    }
+
+    #[test]
+    fn truncated_or_corrupt_file_is_rejected() {
+        let planes = vec![test_plane(true, true)];
+        for options in [BinaryOptions::default(), BinaryOptions{compress: false, u8_colors: false}]{
+            let mut bytes: Vec<u8> = Vec::new();
+            write_binary(&mut bytes, &planes, options).unwrap();
+            for len in [0, 3, 8, 9, 20, bytes.len()/2, bytes.len() - 1]{
+                assert!(load_binary(&bytes[..len]).is_err(), "truncated to {} of {}", len, bytes.len());
+            }
+        }
+        assert!(load_binary(b"MTB2\x01\x00\x00\x00\x00").is_err());
+        assert!(matches!(load_binary(b"MTB1\x09\x00\x00\x00\x00"), Err(SaveError::Version{found: 9, ..})));
+    }
+
+    #[test]
+    fn body_must_match_its_length() {
+        let planes = vec![test_plane(true, true)];
+        for options in [BinaryOptions::default(), BinaryOptions{compress: false, u8_colors: false}]{
+            let mut bytes: Vec<u8> = Vec::new();
+            write_binary(&mut bytes, &planes, options).unwrap();
+            let len = u32::from_le_bytes(bytes[BODY_LEN_AT..BODY_LEN_AT+4].try_into().unwrap());
+            for wrong in [len - 1, len + 1, u32::MAX]{
+                let mut broken = bytes.clone();
+                broken[BODY_LEN_AT..BODY_LEN_AT+4].copy_from_slice(&wrong.to_le_bytes());
+                assert!(matches!(load_binary(&broken), Err(SaveError::Field{..})), "length {} of {}", wrong, len);
+            }
+        }
+    }
+
+    #[test]
+    fn version_1_file_is_read() {
+        let planes = vec![test_plane(false, false), test_plane(true, true)];
+        for options in [BinaryOptions::default(), BinaryOptions{compress: false, u8_colors: false}]{
+            let mut bytes: Vec<u8> = Vec::new();
+            write_binary(&mut bytes, &planes, options).unwrap();
+            // version 1 has no plane json version and body length
+            bytes.drain(9..BODY_LEN_AT+4);
+            bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+            let clr_eps = if options.u8_colors {0.5/255.0} else {0.0};
+            assert_same(&planes, &load_binary(&bytes).unwrap(), clr_eps);
+        }
+    }
+
+    #[test]
+    fn newer_plane_json_is_rejected() {
+        let mut bytes: Vec<u8> = Vec::new();
+        write_binary(&mut bytes, &[test_plane(false, false)], BinaryOptions::default()).unwrap();
+        bytes[9..13].copy_from_slice(&(PLANES_VERSION + 1).to_le_bytes());
+        assert!(matches!(load_binary(&bytes), Err(SaveError::Version{..})));
+    }
+
+    #[test]
+    fn subdivisions_must_match_vertex_count() {
+        let mut bytes: Vec<u8> = Vec::new();
+        write_binary(&mut bytes, &[test_plane(false, false)], BinaryOptions{compress: false, u8_colors: true}).unwrap();
+        let body = String::from_utf8_lossy(&bytes).to_string();
+        let header = body.find("[3,5]").unwrap();
+
+        // huge subdivisions are rejected before any grid is built
+        let mut huge = bytes.clone();
+        huge.splice(header..header+5, b"[4000000000,4000000000]".iter().copied());
+        for len_at in [BODY_LEN_AT, BODY_LEN_AT + 8]{   // body length, header length
+            let len = u32::from_le_bytes(bytes[len_at..len_at+4].try_into().unwrap()) + 18;
+            huge[len_at..len_at+4].copy_from_slice(&len.to_le_bytes());
+        }
+        assert!(matches!(load_binary(&huge), Err(SaveError::Field{..})));
+
+        let mut other = bytes.clone();
+        other[header+1] = b'4';
+        assert!(matches!(load_binary(&other), Err(SaveError::Field{..})));
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Serialize, Deserialize};
+
+use super::easings::Easings;
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
+
+// Shapes terrain around sea level: heights just above it are flattened into beaches,
+// heights just below into a shallow shelf. Island mask pushes plane borders under water first.
+// Sea level is in world space, in the editor it follows the project sea level.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Coastline {
+    pub sea_level:     f32,
+    pub beach_height:  f32,      // height band above sea level shaped into beaches
+    pub beach:         Easings,  // remaps the beach band, SmoothStart keeps it flat near the water
+    pub shelf_depth:   f32,      // depth band below sea level shaped into the shelf
+    pub shelf:         Easings,
+    pub strength:      f32,
+    pub island:        bool,
+    pub island_margin: f32,      // distance from plane border where terrain sinks
+    pub island_depth:  f32       // depth below sea level at the plane border
+}
+
+impl Default for Coastline {
+    fn default() -> Self {
+        Coastline::new()
+    }
+}
+
+impl Coastline {
+    pub fn new() -> Self {
+        Coastline{sea_level:     0.0,
+                  beach_height:  5.0,
+                  beach:         Easings::SmoothStart,
+                  shelf_depth:   10.0,
+                  shelf:         Easings::SmoothStart,
+                  strength:      1.0,
+                  island:        false,
+                  island_margin: 100.0,
+                  island_depth:  20.0}
+    }
+
+    // world height after island mask and coast shaping
+    pub fn height(&self, pd: &PlaneData, loc: &[f32; 3]) -> f32 {
+        let mut h = loc[1] + pd.loc[1];
+
+        if self.island && self.island_margin > 0.0 {
+            let edge = (pd.dims[0]/2.0 - loc[0].abs()).min(pd.dims[1]/2.0 - loc[2].abs()).max(0.0);
+            if edge < self.island_margin {
+                let w = Easings::SmoothStep.apply(1.0 - edge/self.island_margin);
+                h += (self.sea_level - self.island_depth - h).min(0.0)*w;
+            }
+        }
+
+        let shaped = match h >= self.sea_level {
+            true if h <= self.sea_level + self.beach_height && self.beach_height > 0.0 => {
+                self.sea_level + self.beach_height*self.beach.apply((h - self.sea_level)/self.beach_height)
+            }
+            false if h >= self.sea_level - self.shelf_depth && self.shelf_depth > 0.0 => {
+                self.sea_level - self.shelf_depth*self.shelf.apply((self.sea_level - h)/self.shelf_depth)
+            }
+            _ => {h}
+        };
+        return h + (shaped - h)*self.strength.clamp(0.0, 1.0);
+    }
+}
+
+impl TerrainModifier for Coastline {
+    fn name(&self) -> &'static str {
+        "Coastline"
+    }
+
+    fn apply(&self, pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.loc[1] = self.height(pd, &v.loc) - pd.loc[1];
+        }
+    }
+
+    fn is_height(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
+    }
+}
@@ -1,14 +1,25 @@
-use bevy_egui::egui::{DragValue, Ui, Context, RichText};
-use bevy::prelude::ResMut;
-use crate::editor::{mtb_ui::ModResources, colors::Colors, colors::f32_to_clr32};
 use serde::{Serialize, Deserialize};
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Color {
     pub color: [f32; 4],
     pub open:   bool,
 }
 
+impl Default for Color {
+    fn default() -> Self {
+        Color::new()
+    }
+}
+
 impl Color {
     pub fn apply(&self) -> [f32; 4] {
         self.color
@@ -17,24 +28,13 @@ impl Color {
         Color{color: [1.0, 1.0, 1.0, 1.0], open: false}
     }
 
-    pub fn ui(ctx: &Context, ui: &mut Ui, mod_res: &mut ResMut<ModResources>, colors: &mut ResMut<Colors>){
-        let color = f32_to_clr32(&mod_res.color.color);
-        ui.label("Color:");
-        ui.toggle_value(&mut mod_res.color.open, 
-                        RichText::new("_____________")
-                        .background_color(color)
-                        .color(color));
-        colors.show(ctx, &mut mod_res.color.open, "Color");
-        if mod_res.color.open {
-            mod_res.color.color = colors.input;
-        }
-    }
 }
 
 
 
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ColorGradient {
     pub min_height: f32,
     pub max_height: f32,
@@ -44,6 +44,12 @@ pub struct ColorGradient {
     pub max_open:   bool
 }
 
+impl Default for ColorGradient {
+    fn default() -> Self {
+        ColorGradient::new()
+    }
+}
+
 
 impl ColorGradient {
     pub fn apply(&self, height: f32) -> [f32; 4] {
@@ -63,54 +69,43 @@ impl ColorGradient {
                       max_open: false}
     }
 
-    pub fn ui(ctx: &Context, ui: &mut Ui, mod_res: &mut ResMut<ModResources>, colors: &mut ResMut<Colors>) {
-        ui.vertical(|ui| {
-            ui.label("Color Gradient");
-            ui.separator();
-
-            ui.columns(2, |columns| {
-                columns[0].label("Min Height:");
-                columns[0].add(DragValue::new(&mut mod_res.color_gradient.min_height).speed(1.0));
-                columns[1].label("Max Height:");
-                columns[1].add(DragValue::new(&mut mod_res.color_gradient.max_height).speed(1.0));
-            });
-            
-            ui.separator();
-
-            ui.columns(2, |columns| {
-                columns[0].label("Min Color:");
-                columns[1].label("Max Color:");
-
-                let min_color = f32_to_clr32(&mut mod_res.color_gradient.min_color);
-                columns[0].toggle_value(&mut mod_res.color_gradient.min_open,  
-                                        RichText::new("_____________")
-                                                 .background_color(min_color)
-                                                 .color(min_color));
-
-                colors.show(ctx, &mut mod_res.color_gradient.min_open, "Gradient Min Color");
-                if mod_res.color_gradient.min_open {
-                    mod_res.color_gradient.max_open = false;
-                    if colors.clicked {
-                        mod_res.color_gradient.min_color = colors.input;
-                        colors.clicked = false;
-                    }
-                }
-
-                let max_color = f32_to_clr32(&mut mod_res.color_gradient.max_color);
-                columns[1].toggle_value(&mut mod_res.color_gradient.max_open,  
-                                        RichText::new("_____________")
-                                                 .background_color(max_color)
-                                                 .color(max_color));         
-                colors.show(ctx, &mut mod_res.color_gradient.max_open, "Gradient Max Color");
-                if mod_res.color_gradient.max_open {
-                    mod_res.color_gradient.min_open = false;
-                    if colors.clicked {
-                        mod_res.color_gradient.max_color = colors.input;
-                        colors.clicked = false;
-                    }
-                }
-            });
-          });
+}
+
+
+impl TerrainModifier for Color {
+    fn name(&self) -> &'static str {
+        "Color"
+    }
+    fn apply(&self, _pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.clr = Color::apply(self);
+        }
+    }
+    fn used_colors(&self) -> Vec<[f32; 4]> {
+        vec![self.color]
+    }
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, ctx: &Context, ui: &mut Ui, colors: &mut Colors) {
+        self.settings_ui(ctx, ui, colors);
     }
 }
 
+impl TerrainModifier for ColorGradient {
+    fn name(&self) -> &'static str {
+        "ColorGradient"
+    }
+    fn apply(&self, _pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.clr = ColorGradient::apply(self, v.loc[1]);
+        }
+    }
+    fn used_colors(&self) -> Vec<[f32; 4]> {
+        vec![self.min_color, self.max_color]
+    }
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, ctx: &Context, ui: &mut Ui, colors: &mut Colors) {
+        self.settings_ui(ctx, ui, colors);
+    }
+}
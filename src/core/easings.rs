@@ -13,7 +13,7 @@ pub enum Easings {
     AbsoluteValue
 }
 
-impl<'a> Easings {
+impl Easings {
     pub fn iterator() -> Iter<'static, Easings> {
         static EASING_OPTIONS: [Easings; 6] = [
                 Easings::None,
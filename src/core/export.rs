@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use serde_json::{Value, json};
+use std::io::Write;
+use std::path::Path;
+use std::slice::Iter;
+
+use super::planes::PlaneData;
+use super::utils::{compute_seamless_normals, mesh_positions_indices};
+use super::version::SaveError;
+
+// Export of plane meshes for other tools. In gltf each plane keeps its label and location and
+// positions stay local to the plane. Obj and ply have no transforms, positions are in world space there.
+// Normals are computed seamless across planes. Obj and ply keep y of the plane location in a
+// `plane_y` comment, so core::import can split it from the heights.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Gltf,   // json with embedded buffer
+    Glb,    // binary gltf
+    Obj,    // objects per plane, vertex colors as `v x y z r g b`
+    Ply     // binary, all planes in one mesh, vertex count, label and y of planes in comments
+}
+
+impl ExportFormat {
+    pub fn iterator() -> Iter<'static, ExportFormat> {
+        static EXPORT_OPTIONS: [ExportFormat; 4] = [ExportFormat::Gltf, ExportFormat::Glb, ExportFormat::Obj, ExportFormat::Ply];
+        EXPORT_OPTIONS.iter()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Gltf => {"gltf"}
+            ExportFormat::Glb  => {"glb"}
+            ExportFormat::Obj  => {"obj"}
+            ExportFormat::Ply  => {"ply"}
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<ExportFormat> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        return ExportFormat::iterator().find(|format| format.extension() == ext).copied();
+    }
+
+    pub fn write<W: Write>(&self, writer: W, meshes: &[ExportMesh]) -> Result<(), SaveError> {
+        match self {
+            ExportFormat::Gltf => {write_gltf(writer, meshes)}
+            ExportFormat::Glb  => {write_glb(writer, meshes)}
+            ExportFormat::Obj  => {write_obj(writer, meshes)}
+            ExportFormat::Ply  => {write_ply(writer, meshes)}
+        }
+    }
+}
+
+// plane mesh data in export order
+pub struct ExportMesh {
+    pub label:      String,
+    pub loc:        [f32; 3],
+    pub positions:  Vec<[f32; 3]>,
+    pub normals:    Vec<[f32; 3]>,
+    pub uvs:        Vec<[f32; 2]>,
+    pub colors:     Vec<[f32; 4]>,
+    pub indices:    Vec<u32>
+}
+
+impl ExportMesh {
+    pub fn from_planes(planes: &Vec<(&PlaneData, &Mesh)>) -> Vec<ExportMesh> {
+        let offsets: Vec<(Vec3, &Mesh)> = planes.iter().map(|(pd, mesh)| (Vec3::from(pd.loc), *mesh)).collect();
+        let normals = compute_seamless_normals(&offsets);
+
+        let mut meshes: Vec<ExportMesh> = Vec::new();
+        for ((pd, mesh), normals) in planes.iter().zip(normals){
+            let Some((positions, indices)) = mesh_positions_indices(mesh) else {continue;};
+            if positions.is_empty() || indices.is_empty() {
+                continue; // gltf accessors need at least one element for min and max
+            }
+            let uvs: Vec<[f32; 2]> = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+                Some(VertexAttributeValues::Float32x2(uvs)) => {uvs.to_vec()}
+                _ => {vec![[0.0, 0.0]; positions.len()]}
+            };
+            let colors: Vec<[f32; 4]> = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+                Some(VertexAttributeValues::Float32x4(colors)) => {colors.to_vec()}
+                _ => {vec![[1.0, 1.0, 1.0, 1.0]; positions.len()]}
+            };
+            meshes.push(ExportMesh{label: pd.label.clone(), loc: pd.loc, positions, normals, uvs, colors, indices});
+        }
+        return meshes;
+    }
+}
+
+
+const GLTF_FLOAT: u32 = 5126;
+const GLTF_UINT: u32 = 5125;
+const GLTF_ARRAY_BUFFER: u32 = 34962;
+const GLTF_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const GLTF_TRIANGLES: u32 = 4;
+
+// builds gltf json and the single buffer it points to
+fn gltf_document(meshes: &[ExportMesh]) -> (Value, Vec<u8>) {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut views: Vec<Value> = Vec::new();
+    let mut accessors: Vec<Value> = Vec::new();
+    let mut gltf_meshes: Vec<Value> = Vec::new();
+    let mut nodes: Vec<Value> = Vec::new();
+
+    // adds buffer view with its accessor, returns accessor index
+    let mut add = |data: Vec<f32>, indices: Option<&Vec<u32>>, kind: &str, count: usize, min_max: Option<(Vec3, Vec3)>| -> usize {
+        let offset = buffer.len();
+        let (component, target) = match indices {
+            Some(indices) => {
+                indices.iter().for_each(|i| buffer.extend(i.to_le_bytes()));
+                (GLTF_UINT, GLTF_ELEMENT_ARRAY_BUFFER)
+            }
+            None => {
+                data.iter().for_each(|v| buffer.extend(v.to_le_bytes()));
+                (GLTF_FLOAT, GLTF_ARRAY_BUFFER)
+            }
+        };
+        views.push(json!({"buffer": 0, "byteOffset": offset, "byteLength": buffer.len() - offset, "target": target}));
+        let mut accessor = json!({"bufferView": views.len() - 1, "componentType": component, "count": count, "type": kind});
+        if let Some((min, max)) = min_max {
+            accessor["min"] = json!(min.to_array());
+            accessor["max"] = json!(max.to_array());
+        }
+        accessors.push(accessor);
+        return accessors.len() - 1;
+    };
+
+    for (index, mesh) in meshes.iter().enumerate(){
+        let min = mesh.positions.iter().fold(Vec3::splat(f32::MAX), |m, p| m.min(Vec3::from(*p)));
+        let max = mesh.positions.iter().fold(Vec3::splat(f32::MIN), |m, p| m.max(Vec3::from(*p)));
+        let n = mesh.positions.len();
+        let position = add(mesh.positions.concat(), None, "VEC3", n, Some((min, max)));
+        let normal = add(mesh.normals.concat(), None, "VEC3", n, None);
+        let uv = add(mesh.uvs.concat(), None, "VEC2", n, None);
+        let color = add(mesh.colors.concat(), None, "VEC4", n, None);
+        let indices = add(Vec::new(), Some(&mesh.indices), "SCALAR", mesh.indices.len(), None);
+
+        gltf_meshes.push(json!({
+            "name": mesh.label,
+            "primitives": [{
+                "attributes": {"POSITION": position, "NORMAL": normal, "TEXCOORD_0": uv, "COLOR_0": color},
+                "indices": indices,
+                "mode": GLTF_TRIANGLES
+            }]
+        }));
+        nodes.push(json!({"name": mesh.label, "mesh": index, "translation": mesh.loc}));
+    }
+
+    let doc = json!({
+        "asset": {"version": "2.0", "generator": "mtb_terrain_gen"},
+        "scene": 0,
+        "scenes": [{"nodes": (0..nodes.len()).collect::<Vec<usize>>()}],
+        "nodes": nodes,
+        "meshes": gltf_meshes,
+        "accessors": accessors,
+        "bufferViews": views,
+        "buffers": [{"byteLength": buffer.len()}]
+    });
+    return (doc, buffer);
+}
+
+fn write_gltf<W: Write>(mut writer: W, meshes: &[ExportMesh]) -> Result<(), SaveError> {
+    let (mut doc, buffer) = gltf_document(meshes);
+    doc["buffers"][0]["uri"] = json!(format!("data:application/octet-stream;base64,{}", base64(&buffer)));
+    serde_json::to_writer(&mut writer, &doc).map_err(|e| SaveError::Io(e.to_string()))?;
+    return Ok(());
+}
+
+// header, json chunk and binary chunk, chunks padded to 4 bytes
+fn write_glb<W: Write>(mut writer: W, meshes: &[ExportMesh]) -> Result<(), SaveError> {
+    let (doc, mut buffer) = gltf_document(meshes);
+    let mut json = serde_json::to_vec(&doc).map_err(|e| SaveError::Io(e.to_string()))?;
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    while buffer.len() % 4 != 0 {
+        buffer.push(0);
+    }
+    let length = 12 + 8 + json.len() + 8 + buffer.len();
+
+    let mut out: Vec<u8> = Vec::with_capacity(length);
+    out.extend(b"glTF");
+    out.extend(2u32.to_le_bytes());
+    out.extend((length as u32).to_le_bytes());
+    out.extend((json.len() as u32).to_le_bytes());
+    out.extend(b"JSON");
+    out.extend(json);
+    out.extend((buffer.len() as u32).to_le_bytes());
+    out.extend(b"BIN\0");
+    out.extend(buffer);
+    writer.write_all(&out).map_err(|e| SaveError::Io(e.to_string()))?;
+    return Ok(());
+}
+
+fn world(mesh: &ExportMesh, p: &[f32; 3]) -> [f32; 3] {
+    [p[0] + mesh.loc[0], p[1] + mesh.loc[1], p[2] + mesh.loc[2]]
+}
+
+fn write_obj<W: Write>(mut writer: W, meshes: &[ExportMesh]) -> Result<(), SaveError> {
+    let io = |e: std::io::Error| SaveError::Io(e.to_string());
+    writeln!(writer, "# mtb_terrain_gen").map_err(io)?;
+    let mut first: usize = 1; // obj indices start at 1 and are global
+    for mesh in meshes.iter(){
+        writeln!(writer, "o {}", mesh.label).map_err(io)?;
+        writeln!(writer, "# plane_y {}", mesh.loc[1]).map_err(io)?;
+        for (p, c) in mesh.positions.iter().zip(mesh.colors.iter()){
+            let p = world(mesh, p);
+            writeln!(writer, "v {} {} {} {} {} {}", p[0], p[1], p[2], c[0], c[1], c[2]).map_err(io)?;
+        }
+        for uv in mesh.uvs.iter(){
+            writeln!(writer, "vt {} {}", uv[0], uv[1]).map_err(io)?;
+        }
+        for n in mesh.normals.iter(){
+            writeln!(writer, "vn {} {} {}", n[0], n[1], n[2]).map_err(io)?;
+        }
+        for tri in mesh.indices.chunks_exact(3){
+            let [a, b, c] = [tri[0] as usize + first, tri[1] as usize + first, tri[2] as usize + first];
+            writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}").map_err(io)?;
+        }
+        first += mesh.positions.len();
+    }
+    return Ok(());
+}
+
+fn write_ply<W: Write>(mut writer: W, meshes: &[ExportMesh]) -> Result<(), SaveError> {
+    let io = |e: std::io::Error| SaveError::Io(e.to_string());
+    let vertex_count: usize = meshes.iter().map(|mesh| mesh.positions.len()).sum();
+    let face_count: usize = meshes.iter().map(|mesh| mesh.indices.len()/3).sum();
+
+    let mut header = String::from("ply\nformat binary_little_endian 1.0\ncomment mtb_terrain_gen\n");
+    for mesh in meshes.iter(){
+        header += &format!("comment plane {} {}\n", mesh.positions.len(), mesh.label);
+        header += &format!("comment plane_y {}\n", mesh.loc[1]);
+    }
+    header += &format!("element vertex {}\n", vertex_count);
+    for prop in ["x", "y", "z", "nx", "ny", "nz", "s", "t"].iter(){
+        header += &format!("property float {}\n", prop);
+    }
+    for prop in ["red", "green", "blue", "alpha"].iter(){
+        header += &format!("property uchar {}\n", prop);
+    }
+    header += &format!("element face {}\nproperty list uchar uint vertex_indices\nend_header\n", face_count);
+    writer.write_all(header.as_bytes()).map_err(io)?;
+
+    for mesh in meshes.iter(){
+        for i in 0..mesh.positions.len(){
+            let values = [world(mesh, &mesh.positions[i]).as_slice(), mesh.normals[i].as_slice(), mesh.uvs[i].as_slice()].concat();
+            for v in values.iter(){
+                writer.write_all(&v.to_le_bytes()).map_err(io)?;
+            }
+            writer.write_all(&mesh.colors[i].map(|c| (c.clamp(0.0, 1.0)*255.0).round() as u8)).map_err(io)?;
+        }
+    }
+    let mut first: u32 = 0;
+    for mesh in meshes.iter(){
+        for tri in mesh.indices.chunks_exact(3){
+            writer.write_all(&[3u8]).map_err(io)?;
+            for index in tri.iter(){
+                writer.write_all(&(index + first).to_le_bytes()).map_err(io)?;
+            }
+        }
+        first += mesh.positions.len() as u32;
+    }
+    return Ok(());
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3)*4);
+    for chunk in bytes.chunks(3){
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6*i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    return out;
+}
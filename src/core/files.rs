@@ -0,0 +1,149 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::version::SaveError;
+
+// Crash safe writing: every file is written to a temp file next to it and renamed over the target
+// only once all files were written and synced, so a crash or error never leaves half written saves.
+// If replacing one of several files fails, the files replaced before it are restored from copies
+// of their previous contents (name.old), which are removed once all files are in place.
+// Previous contents are kept as rotating backups: name.1.bak is the newest, name.N.bak the oldest.
+
+pub type WriteFn<'a> = Box<dyn FnOnce(&mut BufWriter<File>) -> Result<(), SaveError> + 'a>;
+
+fn io_error(path: &Path, e: std::io::Error) -> SaveError {
+    SaveError::Io(format!("{}: {}", path.display(), e))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", path.display(), suffix))
+}
+
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    with_suffix(path, &format!(".{}.bak", n))
+}
+
+pub fn write_file<'a, F>(path: &Path, backups: usize, write: F) -> Result<(), SaveError>
+where F: FnOnce(&mut BufWriter<File>) -> Result<(), SaveError> + 'a {
+    write_files(vec![(path.to_path_buf(), Box::new(write))], backups)
+}
+
+// writes all files or none of them, except for a crash in the middle of replacing them
+pub fn write_files(files: Vec<(PathBuf, WriteFn)>, backups: usize) -> Result<(), SaveError> {
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();  // target, temp
+    for (path, write) in files.into_iter(){
+        let tmp = with_suffix(&path, ".tmp");
+        let res = stage(&path, &tmp, write);
+        staged.push((path, tmp));
+        if let Err(e) = res {
+            for (_path, tmp) in staged.iter(){
+                let _res = fs::remove_file(tmp);
+            }
+            return Err(e);
+        }
+    }
+
+    // previous contents to restore, None if the file did not exist
+    let mut previous: Vec<Option<PathBuf>> = Vec::new();
+    for (path, _tmp) in staged.iter(){
+        let old = with_suffix(path, ".old");
+        let res = match path.exists() {
+            true  => {fs::copy(path, &old).map(|_| Some(old)).map_err(|e| io_error(path, e))}
+            false => {Ok(None)}
+        };
+        match res {
+            Ok(old) => {previous.push(old);}
+            Err(e)  => {
+                discard(&staged, &previous);
+                return Err(e);
+            }
+        }
+    }
+
+    for (i, (path, tmp)) in staged.iter().enumerate(){
+        let res = match backups > 0 && path.exists() {
+            true  => {rotate(path, backups)}
+            false => {Ok(())}
+        };
+        if let Err(e) = res.and_then(|_| fs::rename(tmp, path).map_err(|e| io_error(path, e))) {
+            restore(&staged[..i], &previous[..i]);
+            discard(&staged, &previous);
+            return Err(e);
+        }
+    }
+    discard(&staged, &previous);
+    return Ok(());
+}
+
+// puts previous contents of already replaced files back
+fn restore(replaced: &[(PathBuf, PathBuf)], previous: &[Option<PathBuf>]) {
+    for ((path, _tmp), old) in replaced.iter().zip(previous.iter()){
+        let _res = match old {
+            Some(old) => {fs::rename(old, path)}
+            None      => {fs::remove_file(path)}
+        };
+    }
+}
+
+fn discard(staged: &[(PathBuf, PathBuf)], previous: &[Option<PathBuf>]) {
+    for (_path, tmp) in staged.iter(){
+        let _res = fs::remove_file(tmp);
+    }
+    for old in previous.iter().flatten(){
+        let _res = fs::remove_file(old);
+    }
+}
+
+fn stage(path: &Path, tmp: &Path, write: WriteFn) -> Result<(), SaveError> {
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+        }
+    }
+    let f = File::create(tmp).map_err(|e| io_error(tmp, e))?;
+    let mut writer = BufWriter::new(f);
+    write(&mut writer)?;
+    writer.flush().map_err(|e| io_error(tmp, e))?;
+    writer.get_ref().sync_all().map_err(|e| io_error(tmp, e))?;
+    return Ok(());
+}
+
+// shifts backups by one and copies current file into the newest one, target stays in place
+fn rotate(path: &Path, backups: usize) -> Result<(), SaveError> {
+    let _res = fs::remove_file(backup_path(path, backups));
+    for n in (1..backups).rev(){
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n+1)).map_err(|e| io_error(&from, e))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1)).map_err(|e| io_error(path, e))?;
+    return Ok(());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_rename_restores_earlier_files() {
+        let dir = std::env::temp_dir().join(format!("mtb_files_{}", std::process::id()));
+        let _res = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("blocked")).unwrap();
+        let first = dir.join("first.json");
+        fs::write(&first, "old").unwrap();
+
+        // renaming a file over a directory fails after first file was already replaced
+        let files: Vec<(PathBuf, WriteFn)> = vec![
+            (first.clone(), Box::new(|writer| writer.write_all(b"new").map_err(|e| SaveError::Io(e.to_string())))),
+            (dir.join("blocked"), Box::new(|writer| writer.write_all(b"new").map_err(|e| SaveError::Io(e.to_string()))))
+        ];
+        assert!(write_files(files, 1).is_err());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old");
+        assert!(!with_suffix(&first, ".old").exists());
+        assert!(!with_suffix(&first, ".tmp").exists());
+        let _res = fs::remove_dir_all(&dir);
+    }
+}
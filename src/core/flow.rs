@@ -0,0 +1,150 @@
+use serde::{Serialize, Deserialize};
+use std::slice::Iter;
+
+use super::grid::HeightGrid;
+use super::planes::PlaneData;
+use super::save::SavePlaneData;
+use super::vertex::Vertex;
+use super::world::WorldGraph;
+
+// Where water goes on a plane: flow direction, flow accumulation and watershed basins per vertex.
+// Arrays are ordered by vertex index.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum FlowRouting {
+    #[default]
+    D8,       // steepest descent to one of 8 neighbours, pits are sinks
+    Filled    // D8 over terrain with depressions filled, every vertex drains to the plane border
+}
+
+impl FlowRouting {
+    pub fn iterator() -> Iter<'static, FlowRouting> {
+        static ROUTING_OPTIONS: [FlowRouting; 2] = [FlowRouting::D8, FlowRouting::Filled];
+        ROUTING_OPTIONS.iter()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FlowView {
+    Accumulation,
+    Basins
+}
+
+impl FlowView {
+    pub fn iterator() -> Iter<'static, FlowView> {
+        static VIEW_OPTIONS: [FlowView; 2] = [FlowView::Accumulation, FlowView::Basins];
+        VIEW_OPTIONS.iter()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FlowData {
+    pub receivers:    Vec<Option<usize>>,   // vertex the water flows to, None for sinks and border outlets
+    pub accumulation: Vec<f32>,             // number of vertices draining through the vertex, including itself
+    pub basins:       Vec<usize>            // watershed id, vertices draining to the same sink share it
+}
+
+impl FlowData {
+    pub fn new(pd: &PlaneData, vertices: &[Vertex], routing: FlowRouting) -> Self {
+        let (receivers, order) = match routing {
+            FlowRouting::D8     => {d8(pd, vertices)}
+            FlowRouting::Filled => {filled(pd, vertices)}
+        };
+
+        let mut accumulation: Vec<f32> = vec![1.0; receivers.len()];
+        for index in order.iter().rev(){
+            if let Some(r) = receivers[*index] {
+                accumulation[r] += accumulation[*index];
+            }
+        }
+
+        let mut basins: Vec<usize> = vec![0; receivers.len()];
+        let mut count: usize = 0;
+        for index in order.iter(){
+            basins[*index] = match receivers[*index] {
+                Some(r) => {basins[r]}
+                None    => {count += 1; count - 1}
+            };
+        }
+
+        return FlowData{receivers, accumulation, basins};
+    }
+
+    // overlay colors per vertex
+    pub fn colors(&self, view: FlowView) -> Vec<[f32; 4]> {
+        match view {
+            FlowView::Accumulation => {
+                let max = self.accumulation.iter().fold(1.0f32, |m, a| m.max(*a)).ln().max(1.0);
+                return self.accumulation.iter().map(|a| {
+                    let t = a.ln()/max;
+                    [0.9 - 0.8*t, 0.9 - 0.6*t, 0.9 - 0.1*t, 1.0]
+                }).collect();
+            }
+            FlowView::Basins => {
+                return self.basins.iter().map(|b| basin_color(*b)).collect();
+            }
+        }
+    }
+}
+
+impl SavePlaneData {
+    // adds flow analysis to the save
+    pub fn analyse_flow(&mut self, routing: FlowRouting) {
+        self.flow = Some(FlowData::new(&self.plane, &self.vertex, routing));
+    }
+}
+
+// receivers and processing order, receiver always comes before its donors
+fn d8(pd: &PlaneData, vertices: &[Vertex]) -> (Vec<Option<usize>>, Vec<usize>) {
+    let all: Vec<usize> = vertices.iter().map(|v| v.index).collect();
+    let grid = HeightGrid::new(pd, vertices, &all);
+
+    let receivers: Vec<Option<usize>> = (0..grid.heights.len()).map(|index| {
+        let h = grid.heights[index];
+        let mut best: Option<(usize, f32)> = None;
+        for (n, dist) in grid.neighbours(index).iter(){
+            let slope = (h - grid.heights[*n])/dist;
+            let steeper = match best {
+                Some((_, s)) => {slope > s}
+                None         => {true}
+            };
+            if slope > 0.0 && steeper {
+                best = Some((*n, slope));
+            }
+        }
+        best.map(|(n, _)| n)
+    }).collect();
+
+    // receivers are strictly lower
+    let mut order: Vec<usize> = (0..grid.heights.len()).collect();
+    order.sort_by(|a, b| grid.heights[*a].total_cmp(&grid.heights[*b]));
+    return (receivers, order);
+}
+
+fn filled(pd: &PlaneData, vertices: &[Vertex]) -> (Vec<Option<usize>>, Vec<usize>) {
+    let graph = WorldGraph::from_plane(pd, vertices);
+    let routing = graph.flood();
+
+    let len = vertices.iter().map(|v| v.index + 1).max().unwrap_or(0);
+    let mut receivers: Vec<Option<usize>> = vec![None; len];
+    for (node, receiver) in routing.receivers.iter().enumerate(){
+        receivers[graph.vertex_index(node)] = receiver.map(|r| graph.vertex_index(r));
+    }
+    let order: Vec<usize> = routing.order.iter().map(|node| graph.vertex_index(*node)).collect();
+    return (receivers, order);
+}
+
+// distinct colors for neighbouring basin ids
+fn basin_color(id: usize) -> [f32; 4] {
+    let hue = (id as f32*0.618034).fract()*6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => {(1.0, x, 0.0)}
+        1 => {(x, 1.0, 0.0)}
+        2 => {(0.0, 1.0, x)}
+        3 => {(0.0, x, 1.0)}
+        4 => {(x, 0.0, 1.0)}
+        _ => {(1.0, 0.0, x)}
+    };
+    return [0.3 + 0.6*r, 0.3 + 0.6*g, 0.3 + 0.6*b, 1.0];
+}
@@ -0,0 +1,108 @@
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+
+// Plane vertices seen as a regular height grid, used by modifiers that need neighbours (erosion etc.)
+// Rows go along z, columns along x, index = row*cols + col
+#[derive(Debug, Clone)]
+pub struct HeightGrid {
+    pub rows:     usize,
+    pub cols:     usize,
+    pub cell_x:   f32,
+    pub cell_z:   f32,
+    pub heights:  Vec<f32>,
+    pub mask:     Vec<bool>   // vertices that can be modified
+}
+
+impl HeightGrid {
+    pub fn new(pd: &PlaneData, vertices: &[Vertex], selection: &[usize]) -> Self {
+        let rows = (pd.subdivisions[0] + 2) as usize;
+        let cols = (pd.subdivisions[1] + 2) as usize;
+        let mut heights: Vec<f32> = vec![0.0; rows*cols];
+        for v in vertices.iter(){
+            if let Some(h) = heights.get_mut(v.index) {
+                *h = v.loc[1];
+            }
+        }
+        let mut mask: Vec<bool> = vec![false; rows*cols];
+        for index in selection.iter(){
+            if let Some(m) = mask.get_mut(*index) {
+                *m = true;
+            }
+        }
+        HeightGrid{rows, cols, heights, mask,
+                   cell_x: pd.dims[0]/(cols-1) as f32,
+                   cell_z: pd.dims[1]/(rows-1) as f32}
+    }
+
+    pub fn index(&self, row: usize, col: usize) -> usize {
+        row*self.cols + col
+    }
+
+    pub fn row_col(&self, index: usize) -> (usize, usize) {
+        (index / self.cols, index % self.cols)
+    }
+
+    // 8 neighbours with distance to them
+    pub fn neighbours(&self, index: usize) -> Vec<(usize, f32)> {
+        let (row, col) = self.row_col(index);
+        let mut v: Vec<(usize, f32)> = Vec::with_capacity(8);
+        for dr in -1..=1i32 {
+            for dc in -1..=1i32 {
+                if dr == 0 && dc == 0 {
+                    continue;
+                }
+                let r = row as i32 + dr;
+                let c = col as i32 + dc;
+                if r < 0 || c < 0 || r >= self.rows as i32 || c >= self.cols as i32 {
+                    continue;
+                }
+                let dist = ((dr as f32*self.cell_z).powi(2) + (dc as f32*self.cell_x).powi(2)).sqrt();
+                v.push((self.index(r as usize, c as usize), dist));
+            }
+        }
+        return v;
+    }
+
+    // height and gradient (per cell) at position in grid space, x along columns, z along rows
+    pub fn height_gradient(&self, x: f32, z: f32) -> (f32, f32, f32) {
+        let col = (x.floor() as usize).min(self.cols-2);
+        let row = (z.floor() as usize).min(self.rows-2);
+        let u = x - col as f32;
+        let v = z - row as f32;
+
+        let h00 = self.heights[self.index(row, col)];
+        let h10 = self.heights[self.index(row, col+1)];
+        let h01 = self.heights[self.index(row+1, col)];
+        let h11 = self.heights[self.index(row+1, col+1)];
+
+        let grad_x = (h10 - h00)*(1.0 - v) + (h11 - h01)*v;
+        let grad_z = (h01 - h00)*(1.0 - u) + (h11 - h10)*u;
+        let height = h00*(1.0-u)*(1.0-v) + h10*u*(1.0-v) + h01*(1.0-u)*v + h11*u*v;
+        return (height, grad_x, grad_z);
+    }
+
+    // cell corners around grid position with bilinear weights
+    pub fn corners(&self, x: f32, z: f32) -> [(usize, f32); 4] {
+        let col = (x.floor() as usize).min(self.cols-2);
+        let row = (z.floor() as usize).min(self.rows-2);
+        let u = x - col as f32;
+        let v = z - row as f32;
+        return [(self.index(row, col),     (1.0-u)*(1.0-v)),
+                (self.index(row, col+1),   u*(1.0-v)),
+                (self.index(row+1, col),   (1.0-u)*v),
+                (self.index(row+1, col+1), u*v)];
+    }
+
+    pub fn contains(&self, x: f32, z: f32) -> bool {
+        x >= 0.0 && z >= 0.0 && x <= (self.cols-1) as f32 && z <= (self.rows-1) as f32
+    }
+
+    // writes heights of masked vertices back
+    pub fn apply(&self, vertices: &mut [Vertex]) {
+        for v in vertices.iter_mut(){
+            if *self.mask.get(v.index).unwrap_or(&false) {
+                v.loc[1] = self.heights[v.index];
+            }
+        }
+    }
+}
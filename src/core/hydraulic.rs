@@ -0,0 +1,181 @@
+use rand::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use super::grid::HeightGrid;
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
+
+// Droplet based hydraulic erosion. Each droplet starts on a random picked vertex and runs downhill,
+// picking up sediment on steep parts and dropping it where it slows down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HydraulicErosion {
+    pub seed:             u64,
+    pub iterations:       usize,    // number of droplets
+    pub lifetime:         usize,    // max steps of one droplet
+    pub inertia:          f32,      // 0 - droplet follows the slope, 1 - keeps its direction
+    pub capacity:         f32,      // how much sediment droplet can carry
+    pub min_capacity:     f32,
+    pub erosion:          f32,      // fraction of free capacity taken from terrain each step
+    pub deposition:       f32,      // fraction of surplus sediment dropped each step
+    pub evaporation:      f32,
+    pub gravity:          f32,
+    pub paint:            bool,     // write erosion/deposition amounts into vertex colors
+    pub erosion_color:    [f32; 4],
+    pub deposition_color: [f32; 4]
+}
+
+impl Default for HydraulicErosion {
+    fn default() -> Self {
+        HydraulicErosion::new()
+    }
+}
+
+impl HydraulicErosion {
+    pub fn new() -> Self {
+        HydraulicErosion{seed:             0,
+                         iterations:       2000,
+                         lifetime:         30,
+                         inertia:          0.05,
+                         capacity:         1.0,
+                         min_capacity:     0.01,
+                         erosion:          0.05,
+                         deposition:       0.1,
+                         evaporation:      0.01,
+                         gravity:          4.0,
+                         paint:            false,
+                         erosion_color:    [0.45, 0.35, 0.25, 1.0],
+                         deposition_color: [0.85, 0.80, 0.60, 1.0]}
+    }
+
+    // changes terrain at grid position, only picked vertices are changed. Returns amount actually changed
+    fn change(&self, grid: &mut HeightGrid, changes: &mut [f32], x: f32, z: f32, amount: f32) -> f32 {
+        let mut total: f32 = 0.0;
+        for (index, weight) in grid.corners(x, z).iter(){
+            if !grid.mask[*index] {
+                continue;
+            }
+            grid.heights[*index] += amount*weight;
+            changes[*index] += amount*weight;
+            total += amount*weight;
+        }
+        return total;
+    }
+
+    pub fn erode(&self, grid: &mut HeightGrid) -> Vec<f32> {
+        let mut changes: Vec<f32> = vec![0.0; grid.heights.len()];
+        let starts: Vec<usize> = (0..grid.heights.len()).filter(|index| grid.mask[*index]).collect();
+        if starts.is_empty() || grid.rows < 2 || grid.cols < 2 {
+            return changes;
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let max_x = (grid.cols - 1) as f32;
+        let max_z = (grid.rows - 1) as f32;
+
+        for _ in 0..self.iterations {
+            let (row, col) = grid.row_col(starts[rng.gen_range(0..starts.len())]);
+            let mut x = (col as f32 + rng.gen_range(-0.5..0.5)).clamp(0.0, max_x);
+            let mut z = (row as f32 + rng.gen_range(-0.5..0.5)).clamp(0.0, max_z);
+            let mut dir_x: f32 = 0.0;
+            let mut dir_z: f32 = 0.0;
+            let mut speed: f32 = 1.0;
+            let mut water: f32 = 1.0;
+            let mut sediment: f32 = 0.0;
+
+            let mut left_grid: bool = false;
+            for _ in 0..self.lifetime {
+                let (height, grad_x, grad_z) = grid.height_gradient(x, z);
+                dir_x = dir_x*self.inertia - grad_x*(1.0 - self.inertia);
+                dir_z = dir_z*self.inertia - grad_z*(1.0 - self.inertia);
+                let len = (dir_x*dir_x + dir_z*dir_z).sqrt();
+                if len < 1e-6 {
+                    break; // flat, droplet stops
+                }
+                dir_x /= len;
+                dir_z /= len;
+
+                let new_x = x + dir_x;
+                let new_z = z + dir_z;
+                if !grid.contains(new_x, new_z) {
+                    left_grid = true; // sediment is carried away
+                    break;
+                }
+
+                let (new_height, _, _) = grid.height_gradient(new_x, new_z);
+                let delta = new_height - height;
+                let capacity = (-delta*speed*water*self.capacity).max(self.min_capacity);
+
+                if sediment > capacity || delta > 0.0 {
+                    // uphill: fill the pit, otherwise drop surplus
+                    let amount = match delta > 0.0 {
+                        true  => {delta.min(sediment)}
+                        false => {(sediment - capacity)*self.deposition}
+                    };
+                    sediment -= self.change(grid, &mut changes, x, z, amount);
+                } else {
+                    let amount = ((capacity - sediment)*self.erosion).min(-delta);
+                    sediment -= self.change(grid, &mut changes, x, z, -amount);
+                }
+
+                speed = (speed*speed - delta*self.gravity).max(0.0).sqrt();
+                water *= 1.0 - self.evaporation;
+                x = new_x;
+                z = new_z;
+            }
+
+            // droplet dried out, drop what it carries
+            if !left_grid && sediment > 0.0 {
+                self.change(grid, &mut changes, x, z, sediment);
+            }
+        }
+        return changes;
+    }
+}
+
+impl TerrainModifier for HydraulicErosion {
+    fn name(&self) -> &'static str {
+        "HydraulicErosion"
+    }
+    fn apply(&self, pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        let mut grid = HeightGrid::new(pd, vertices, selection);
+        let changes = self.erode(&mut grid);
+        grid.apply(vertices);
+
+        if self.paint {
+            let max_change = changes.iter().fold(0.0f32, |acc, c| acc.max(c.abs()));
+            if max_change <= 0.0 {
+                return;
+            }
+            for index in selection.iter(){
+                let Some(v) = vertices.get_mut(*index) else {continue;};
+                let c = changes[*index]/max_change;
+                let target = match c < 0.0 {
+                    true  => {self.erosion_color}
+                    false => {self.deposition_color}
+                };
+                for (v_c, t) in v.clr.iter_mut().zip(target.iter()){
+                    *v_c += (t - *v_c)*c.abs();
+                }
+            }
+        }
+    }
+    fn is_height(&self) -> bool {
+        true
+    }
+    fn used_colors(&self) -> Vec<[f32; 4]> {
+        match self.paint {
+            true  => {vec![self.erosion_color, self.deposition_color]}
+            false => {Vec::new()}
+        }
+    }
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
+    }
+}
@@ -0,0 +1,427 @@
+use std::path::Path;
+
+use super::planes::PlaneData;
+use super::save::SavePlaneData;
+use super::version::SaveError;
+use super::vertex::Vertex;
+
+// Import of obj and ply meshes back into planes, e.g. after sculpting in other tools.
+// Vertices have to form a regular grid in xz (heights and colors can change, but not x and z),
+// faces are ignored and rebuilt from the grid. Vertices at the same xz (split by uvs or normals)
+// are merged. Obj objects (`o`) become separate planes, ply is split by `comment plane <vertex count> <label>`
+// lines written by core::export, otherwise it is one plane.
+// Plane y is read from `plane_y` comments written by core::export, without them it is 0 and
+// the whole world height stays in the vertices.
+
+struct ImportedMesh {
+    label:      String,
+    y:          f32,                // y of the plane location
+    positions:  Vec<[f32; 3]>,
+    colors:     Vec<[f32; 4]>
+}
+
+impl ImportedMesh {
+    fn new(label: &str) -> Self {
+        ImportedMesh{label: label.to_string(), y: 0.0, positions: Vec::new(), colors: Vec::new()}
+    }
+}
+
+fn plane_y(token: Option<&str>) -> Option<f32> {
+    token.and_then(|y| y.parse::<f32>().ok()).filter(|y| y.is_finite())
+}
+
+pub fn import_mesh(path: &Path, bytes: &[u8]) -> Result<Vec<SavePlaneData>, SaveError> {
+    let label = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("Imported Plane");
+    let meshes = match path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase()).as_deref() {
+        Some("obj") => {read_obj(bytes, label)?}
+        Some("ply") => {read_ply(bytes, label)?}
+        _ => {return Err(SaveError::Io(format!("{}: only .obj and .ply can be imported", path.display())));}
+    };
+    return meshes.iter().map(grid_plane).collect();
+}
+
+
+fn read_obj(bytes: &[u8], label: &str) -> Result<Vec<ImportedMesh>, SaveError> {
+    let text = std::str::from_utf8(bytes).map_err(|e| SaveError::Io(e.to_string()))?;
+    let mut meshes: Vec<ImportedMesh> = vec![ImportedMesh::new(label)];
+
+    for (line_number, line) in text.lines().enumerate(){
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("o") => {
+                let name = tokens.collect::<Vec<&str>>().join(" ");
+                if meshes.last().unwrap().positions.is_empty() {
+                    meshes.last_mut().unwrap().label = name;
+                } else {
+                    meshes.push(ImportedMesh::new(&name));
+                }
+            }
+            Some("#") if tokens.next() == Some("plane_y") => {
+                if let Some(y) = plane_y(tokens.next()) {
+                    meshes.last_mut().unwrap().y = y;
+                }
+            }
+            Some("v") => {
+                let values: Vec<f32> = tokens.map(|t| t.parse::<f32>())
+                                             .collect::<Result<Vec<f32>, _>>()
+                                             .map_err(|e| SaveError::Field{path: format!("line {}", line_number + 1), message: e.to_string()})?;
+                if values.len() < 3 {
+                    return Err(SaveError::Field{path: format!("line {}", line_number + 1), message: "vertex needs x y z".to_string()});
+                }
+                let mesh = meshes.last_mut().unwrap();
+                mesh.positions.push([values[0], values[1], values[2]]);
+                mesh.colors.push(match values.len() >= 6 {
+                    true  => {[values[3], values[4], values[5], 1.0]}
+                    false => {[1.0, 1.0, 1.0, 1.0]}
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // some tools write colors as 0..255
+    for mesh in meshes.iter_mut(){
+        if mesh.colors.iter().any(|c| c[0] > 1.0 || c[1] > 1.0 || c[2] > 1.0) {
+            mesh.colors.iter_mut().for_each(|c| *c = [c[0]/255.0, c[1]/255.0, c[2]/255.0, 1.0]);
+        }
+    }
+    meshes.retain(|mesh| !mesh.positions.is_empty());
+    return Ok(meshes);
+}
+
+
+#[derive(Clone)]
+enum PlyProperty {
+    Value{name: String, kind: String},
+    List{count: String, item: String}
+}
+
+struct PlyElement {
+    name:       String,
+    count:      usize,
+    properties: Vec<PlyProperty>
+}
+
+fn ply_error(message: &str) -> SaveError {
+    SaveError::Field{path: "ply".to_string(), message: message.to_string()}
+}
+
+fn ply_size(kind: &str) -> Result<usize, SaveError> {
+    match kind {
+        "char" | "uchar" | "int8" | "uint8"                     => {Ok(1)}
+        "short" | "ushort" | "int16" | "uint16"                 => {Ok(2)}
+        "int" | "uint" | "int32" | "uint32" | "float" | "float32" => {Ok(4)}
+        "double" | "float64"                                    => {Ok(8)}
+        _ => {Err(ply_error(&format!("unknown property type {}", kind)))}
+    }
+}
+
+// little endian value of given type
+fn ply_binary(bytes: &[u8], pos: &mut usize, kind: &str) -> Result<f64, SaveError> {
+    let size = ply_size(kind)?;
+    if *pos + size > bytes.len() {
+        return Err(ply_error("unexpected end of file"));
+    }
+    let b = &bytes[*pos..*pos+size];
+    *pos += size;
+    let value = match kind {
+        "char" | "int8"               => {b[0] as i8 as f64}
+        "uchar" | "uint8"             => {b[0] as f64}
+        "short" | "int16"             => {i16::from_le_bytes([b[0], b[1]]) as f64}
+        "ushort" | "uint16"           => {u16::from_le_bytes([b[0], b[1]]) as f64}
+        "int" | "int32"               => {i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64}
+        "uint" | "uint32"             => {u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64}
+        "float" | "float32"           => {f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64}
+        _                             => {f64::from_le_bytes(b.try_into().unwrap())}
+    };
+    return Ok(value);
+}
+
+fn read_ply(bytes: &[u8], label: &str) -> Result<Vec<ImportedMesh>, SaveError> {
+    let marker = b"end_header";
+    let Some(end) = bytes.windows(marker.len()).position(|w| w == marker) else {
+        return Err(ply_error("missing end_header"));
+    };
+    let Some(newline) = bytes[end..].iter().position(|b| *b == b'\n') else {
+        return Err(ply_error("missing end_header"));
+    };
+    let body = end + newline + 1;
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|e| SaveError::Io(e.to_string()))?;
+
+    let mut binary = false;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    let mut planes: Vec<(usize, String, f32)> = Vec::new();
+    for line in header.lines(){
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", "ascii", ..]                => {binary = false;}
+            ["format", "binary_little_endian", ..] => {binary = true;}
+            ["format", other, ..]                  => {return Err(ply_error(&format!("unsupported format {}", other)));}
+            ["comment", "plane", count, name @ ..] => {
+                if let Ok(count) = count.parse::<usize>() {
+                    planes.push((count, name.join(" "), 0.0));
+                }
+            }
+            ["comment", "plane_y", y]              => {
+                if let (Some(y), Some(plane)) = (plane_y(Some(y)), planes.last_mut()) {
+                    plane.2 = y;
+                }
+            }
+            ["element", name, count]               => {
+                let count = count.parse::<usize>().map_err(|e| ply_error(&e.to_string()))?;
+                elements.push(PlyElement{name: name.to_string(), count, properties: Vec::new()});
+            }
+            ["property", "list", count, item, _name] => {
+                let element = elements.last_mut().ok_or(ply_error("property before element"))?;
+                element.properties.push(PlyProperty::List{count: count.to_string(), item: item.to_string()});
+            }
+            ["property", kind, name]               => {
+                let element = elements.last_mut().ok_or(ply_error("property before element"))?;
+                element.properties.push(PlyProperty::Value{name: name.to_string(), kind: kind.to_string()});
+            }
+            _ => {}
+        }
+    }
+
+    let mut mesh = ImportedMesh::new(label);
+    let text = if binary {""} else {std::str::from_utf8(&bytes[body..]).map_err(|e| SaveError::Io(e.to_string()))?};
+    let mut tokens = text.split_whitespace();
+    let mut pos = body;
+
+    // reads next value of the element, from text or binary body
+    let mut next = |kind: &str| -> Result<f64, SaveError> {
+        if binary {
+            return ply_binary(bytes, &mut pos, kind);
+        }
+        let token = tokens.next().ok_or(ply_error("unexpected end of file"))?;
+        return token.parse::<f64>().map_err(|e| ply_error(&e.to_string()));
+    };
+
+    for element in elements.iter(){
+        if element.count > bytes.len() - body {
+            return Err(ply_error(&format!("{} {} elements don't fit into the file", element.count, element.name)));
+        }
+        for _i in 0..element.count {
+            let mut position = [0.0; 3];
+            let mut color = [1.0; 4];
+            for property in element.properties.iter(){
+                match property {
+                    PlyProperty::List{count, item} => {
+                        let n = next(count)? as usize;
+                        for _j in 0..n {
+                            next(item)?;
+                        }
+                    }
+                    PlyProperty::Value{name, kind} => {
+                        let value = next(kind)?;
+                        // integer colors are 0..255
+                        let c = if kind.starts_with("float") || kind == "double" {value as f32} else {value as f32/255.0};
+                        match name.as_str() {
+                            "x"     => {position[0] = value as f32;}
+                            "y"     => {position[1] = value as f32;}
+                            "z"     => {position[2] = value as f32;}
+                            "red"   => {color[0] = c;}
+                            "green" => {color[1] = c;}
+                            "blue"  => {color[2] = c;}
+                            "alpha" => {color[3] = c;}
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                mesh.positions.push(position);
+                mesh.colors.push(color);
+            }
+        }
+        if element.name == "vertex" {
+            break; // faces are not needed
+        }
+    }
+
+    let plane_vertices = planes.iter().try_fold(0usize, |sum, (count, _name, _y)| sum.checked_add(*count));
+    if planes.is_empty() || plane_vertices != Some(mesh.positions.len()) {
+        return Ok(vec![mesh]);
+    }
+    let mut meshes: Vec<ImportedMesh> = Vec::new();
+    let mut first: usize = 0;
+    for (count, name, y) in planes.iter(){
+        let mut plane = ImportedMesh::new(name);
+        plane.y = *y;
+        plane.positions = mesh.positions[first..first+count].to_vec();
+        plane.colors = mesh.colors[first..first+count].to_vec();
+        meshes.push(plane);
+        first += count;
+    }
+    return Ok(meshes);
+}
+
+
+// distinct values along one axis, values closer than eps are the same
+fn axis_values(mut values: Vec<f32>, eps: f32) -> Vec<f32> {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mut axis: Vec<f32> = Vec::new();
+    for v in values.into_iter(){
+        let next = match axis.last() {
+            Some(last) => {v - last > eps}
+            None       => {true}
+        };
+        if next {
+            axis.push(v);
+        }
+    }
+    return axis;
+}
+
+fn axis_index(axis: &[f32], v: f32, eps: f32) -> Option<usize> {
+    let index = axis.partition_point(|a| *a < v - eps);
+    if index < axis.len() && (axis[index] - v).abs() <= eps {
+        return Some(index);
+    }
+    return None;
+}
+
+fn is_even(axis: &[f32]) -> bool {
+    let step = (axis[axis.len()-1] - axis[0])/(axis.len() - 1) as f32;
+    return axis.iter().enumerate().all(|(i, v)| (v - (axis[0] + i as f32*step)).abs() <= step*0.01);
+}
+
+fn grid_plane(mesh: &ImportedMesh) -> Result<SavePlaneData, SaveError> {
+    let not_grid = |message: &str| SaveError::Field{path: mesh.label.clone(), message: format!("not a grid mesh: {}", message)};
+
+    let extent = mesh.positions.iter().fold(0.0f32, |m, p| m.max(p[0].abs()).max(p[2].abs()));
+    let eps = (extent*1e-4).max(1e-4);
+    let xs = axis_values(mesh.positions.iter().map(|p| p[0]).collect(), eps);
+    let zs = axis_values(mesh.positions.iter().map(|p| p[2]).collect(), eps);
+    if xs.len() < 2 || zs.len() < 2 {
+        return Err(not_grid("needs at least 2 rows and columns"));
+    }
+    if !is_even(&xs) || !is_even(&zs) {
+        return Err(not_grid("uneven spacing"));
+    }
+
+    // rows go along z, like in plane_mesh
+    let (cols, rows) = (xs.len(), zs.len());
+    if cols.checked_mul(rows).filter(|count| *count <= mesh.positions.len()).is_none() {
+        return Err(not_grid("missing vertices"));
+    }
+    let mut grid: Vec<Option<usize>> = vec![None; cols*rows];
+    for (i, p) in mesh.positions.iter().enumerate(){
+        let (Some(xi), Some(zi)) = (axis_index(&xs, p[0], eps), axis_index(&zs, p[2], eps)) else {
+            return Err(not_grid("vertex off the grid"));
+        };
+        grid[zi*cols + xi].get_or_insert(i);
+    }
+    if grid.iter().any(|slot| slot.is_none()) {
+        return Err(not_grid("missing vertices"));
+    }
+
+    let center = [(xs[0] + xs[cols-1])/2.0, (zs[0] + zs[rows-1])/2.0];
+    let mut plane = PlaneData::new();
+    plane.label = mesh.label.clone();
+    plane.loc = [center[0], mesh.y, center[1]];
+    plane.dims = [xs[cols-1] - xs[0], zs[rows-1] - zs[0]];
+    plane.subdivisions = [(rows - 2) as u32, (cols - 2) as u32];
+
+    let mut spd = SavePlaneData::from_pd(&plane);
+    for (index, slot) in grid.iter().enumerate(){
+        let i = slot.unwrap();
+        let p = mesh.positions[i];
+        spd.vertex.push(Vertex::new(index, &[p[0] - center[0], p[1] - mesh.y, p[2] - center[1]], &mesh.colors[i]));
+    }
+    return Ok(spd);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::Mesh;
+    use crate::core::export::{ExportFormat, ExportMesh};
+
+    fn test_plane(label: &str, loc: [f32; 3], subdivisions: [u32; 2]) -> SavePlaneData {
+        let mut spd = SavePlaneData::from_pd(&PlaneData::new());
+        spd.plane.label = label.to_string();
+        spd.plane.loc = loc;
+        spd.plane.subdivisions = subdivisions;
+        spd.vertex = spd.plane.base_vertices();
+        for v in spd.vertex.iter_mut(){
+            v.loc[1] = (v.index % 7) as f32*1.25 - 3.0;
+            v.clr = [0.0, (v.index % 5) as f32/4.0, 1.0, 1.0];
+        }
+        return spd;
+    }
+
+    fn export(format: ExportFormat, planes: &[SavePlaneData]) -> Vec<u8> {
+        let meshes: Vec<Mesh> = planes.iter().map(|spd| spd.mesh()).collect();
+        let v_planes: Vec<(&PlaneData, &Mesh)> = planes.iter().map(|spd| &spd.plane).zip(meshes.iter()).collect();
+        let mut bytes: Vec<u8> = Vec::new();
+        format.write(&mut bytes, &ExportMesh::from_planes(&v_planes)).unwrap();
+        return bytes;
+    }
+
+    #[test]
+    fn exported_planes_import_as_the_same_grid() {
+        let planes = vec![test_plane("Left", [-100.0, 0.0, 0.0], [4, 6]),
+                          test_plane("Right", [100.0, 0.0, 0.0], [10, 3]),
+                          test_plane("Raised", [0.0, 25.5, 200.0], [3, 3])];
+        for (format, name) in [(ExportFormat::Obj, "terrain.obj"), (ExportFormat::Ply, "terrain.ply")]{
+            let imported = import_mesh(Path::new(name), &export(format, &planes)).unwrap();
+            assert_eq!(imported.len(), planes.len());
+            for (a, b) in planes.iter().zip(imported.iter()){
+                assert_eq!(a.plane.label, b.plane.label);
+                assert_eq!(a.plane.subdivisions, b.plane.subdivisions);
+                assert_eq!(a.vertex.len(), b.vertex.len());
+                for i in 0..3 {
+                    assert!((a.plane.loc[i] - b.plane.loc[i]).abs() < 1e-3);
+                }
+                for (va, vb) in a.vertex.iter().zip(b.vertex.iter()){
+                    assert!((va.loc[1] - vb.loc[1]).abs() < 1e-4, "{} height {} != {}", name, va.loc[1], vb.loc[1]);
+                    assert!(va.clr.iter().zip(vb.clr.iter()).all(|(ca, cb)| (ca - cb).abs() <= 1.0/255.0),
+                            "{} color {:?} != {:?}", name, va.clr, vb.clr);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mesh_without_plane_y_keeps_world_heights() {
+        let planes = vec![test_plane("Raised", [0.0, 25.5, 0.0], [2, 2])];
+        let obj = String::from_utf8(export(ExportFormat::Obj, &planes)).unwrap();
+        let stripped: String = obj.lines().filter(|line| !line.starts_with("# plane_y")).map(|line| format!("{}\n", line)).collect();
+        let imported = import_mesh(Path::new("terrain.obj"), stripped.as_bytes()).unwrap();
+        assert_eq!(imported[0].plane.loc[1], 0.0);
+        for (va, vb) in planes[0].vertex.iter().zip(imported[0].vertex.iter()){
+            assert!((va.loc[1] + 25.5 - vb.loc[1]).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn truncated_or_corrupt_mesh_is_rejected() {
+        let planes = vec![test_plane("Plane", [0.0, 0.0, 0.0], [2, 3])];
+        for (format, name) in [(ExportFormat::Obj, "terrain.obj"), (ExportFormat::Ply, "terrain.ply")]{
+            let bytes = export(format, &planes);
+            // may still be a smaller grid, but must not panic
+            for len in 0..bytes.len(){
+                let _res = import_mesh(Path::new(name), &bytes[..len]);
+            }
+        }
+
+        let ply = export(ExportFormat::Ply, &planes);
+        let body = ply.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+        assert!(import_mesh(Path::new("a.ply"), &ply[..body + 10]).is_err());
+        assert!(import_mesh(Path::new("a.obj"), b"v 1.0 2.0\n").is_err());
+        assert!(import_mesh(Path::new("a.obj"), b"v 1.0 x 2.0\n").is_err());
+        assert!(import_mesh(Path::new("a.stl"), b"solid").is_err());
+
+        let huge = b"ply\nformat ascii 1.0\nelement vertex 18446744073709551615\nend_header\n";
+        assert!(import_mesh(Path::new("a.ply"), huge).is_err());
+        let counts = b"ply\nformat ascii 1.0\ncomment plane 18446744073709551615 a\ncomment plane 2 b\n\
+                       element vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n0 0 0\n";
+        assert!(import_mesh(Path::new("a.ply"), counts).is_err());
+
+        // vertices on a diagonal span a large grid with nothing in it
+        let diagonal: String = (0..2000).map(|i| format!("v {} 0 {}\n", i, i)).collect();
+        assert!(import_mesh(Path::new("a.obj"), diagonal.as_bytes()).is_err());
+    }
+}
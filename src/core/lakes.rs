@@ -0,0 +1,186 @@
+use serde::{Serialize, Deserialize};
+use std::slice::Iter;
+
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+use super::world::WorldGraph;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
+
+// Priority flood over the plane: depressions are found by flooding from the plane border,
+// each one fills up to the height where it would spill over to its neighbour.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lakes {
+    pub mode:      LakeMode,
+    pub epsilon:   f32,        // slope left on filled depressions so they still drain (Fill)
+    pub min_depth: f32,        // shallower depressions are ignored (Lake)
+    pub min_area:  f32,        // smaller depressions are ignored (Lake)
+    pub paint:     bool,       // color filled vertices
+    pub color:     [f32; 4]
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum LakeMode {
+    Fill,   // raises depressions to their spill height
+    Lake    // flat lake surface at spill height
+}
+
+impl LakeMode {
+    pub fn iterator() -> Iter<'static, LakeMode> {
+        static LAKE_OPTIONS: [LakeMode; 2] = [LakeMode::Fill, LakeMode::Lake];
+        LAKE_OPTIONS.iter()
+    }
+}
+
+// one depression on the selected vertices
+#[derive(Debug, Clone)]
+pub struct Lake {
+    pub vertices: Vec<usize>,
+    pub height:   f32,    // surface (spill) height, local to the plane
+    pub depth:    f32,
+    pub area:     f32
+}
+
+impl Default for Lakes {
+    fn default() -> Self {
+        Lakes::new()
+    }
+}
+
+impl Lakes {
+    pub fn new() -> Self {
+        Lakes{mode:      LakeMode::Lake,
+              epsilon:   0.001,
+              min_depth: 0.5,
+              min_area:  0.0,
+              paint:     true,
+              color:     [0.15, 0.35, 0.65, 1.0]}
+    }
+
+    // depressions touching selected vertices
+    pub fn lakes(&self, pd: &PlaneData, vertices: &[Vertex], selection: &[usize]) -> Vec<Lake> {
+        let graph = WorldGraph::from_plane(pd, vertices);
+        let filled = graph.flood().filled;
+        let rows = pd.subdivisions[0] + 2;
+        let cols = pd.subdivisions[1] + 2;
+        let cell_area = (pd.dims[0]/(cols-1) as f32)*(pd.dims[1]/(rows-1) as f32);
+
+        let wet = |node: usize| -> bool {filled[node] - graph.nodes[node].height > 1e-4};
+        let mut picked: Vec<bool> = vec![false; vertices.len()];
+        for index in selection.iter(){
+            if let Some(p) = picked.get_mut(*index) {
+                *p = true;
+            }
+        }
+        let selected: Vec<bool> = (0..graph.nodes.len()).map(|node| *picked.get(graph.vertex_index(node)).unwrap_or(&false)).collect();
+
+        // connected wet nodes with the same surface height
+        let mut visited: Vec<bool> = vec![false; graph.nodes.len()];
+        let mut lakes: Vec<Lake> = Vec::new();
+        for start in 0..graph.nodes.len() {
+            if visited[start] || !wet(start) {
+                continue;
+            }
+            visited[start] = true;
+            let surface = filled[start];
+            let mut stack: Vec<usize> = vec![start];
+            let mut lake = Lake{vertices: Vec::new(), height: surface, depth: 0.0, area: 0.0};
+            while let Some(node) = stack.pop() {
+                if selected[node] {
+                    lake.vertices.push(graph.vertex_index(node));
+                    lake.depth = lake.depth.max(surface - graph.nodes[node].height);
+                }
+                for nb in graph.nodes[node].neighbours.iter(){
+                    if !visited[*nb] && wet(*nb) && (filled[*nb] - surface).abs() <= 1e-4 {
+                        visited[*nb] = true;
+                        stack.push(*nb);
+                    }
+                }
+            }
+            lake.area = lake.vertices.len() as f32*cell_area;
+            if !lake.vertices.is_empty() {
+                lakes.push(lake);
+            }
+        }
+        return lakes;
+    }
+
+    fn accepted(&self, lake: &Lake) -> bool {
+        match self.mode {
+            LakeMode::Fill => {true}
+            LakeMode::Lake => {lake.depth >= self.min_depth && lake.area >= self.min_area}
+        }
+    }
+}
+
+impl TerrainModifier for Lakes {
+    fn name(&self) -> &'static str {
+        "Lakes"
+    }
+
+    fn apply(&self, pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        let mut changed: Vec<(usize, f32)> = Vec::new();
+        match self.mode {
+            LakeMode::Fill => {
+                let graph = WorldGraph::from_plane(pd, vertices);
+                let filled = graph.flood_epsilon(self.epsilon.max(0.0)).filled;
+                for (node, (f, n)) in filled.iter().zip(graph.nodes.iter()).enumerate(){
+                    if *f > n.height {
+                        changed.push((graph.vertex_index(node), *f));
+                    }
+                }
+            }
+            LakeMode::Lake => {
+                for lake in self.lakes(pd, vertices, selection).iter().filter(|lake| self.accepted(lake)){
+                    changed.extend(lake.vertices.iter().map(|index| (*index, lake.height)));
+                }
+            }
+        }
+
+        let mut picked: Vec<bool> = vec![false; vertices.len()];
+        for index in selection.iter(){
+            if let Some(p) = picked.get_mut(*index) {
+                *p = true;
+            }
+        }
+        for (index, height) in changed.iter(){
+            if !*picked.get(*index).unwrap_or(&false) {
+                continue;
+            }
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.loc[1] = *height;
+            if self.paint {
+                v.clr = self.color;
+            }
+        }
+    }
+
+    fn is_height(&self) -> bool {
+        self.mode == LakeMode::Fill
+    }
+
+    fn used_colors(&self) -> Vec<[f32; 4]> {
+        match self.paint {
+            true  => {vec![self.color]}
+            false => {Vec::new()}
+        }
+    }
+
+    fn report(&self, pd: &PlaneData, vertices: &Vec<Vertex>, selection: &Vec<usize>) -> Vec<String> {
+        let lakes: Vec<Lake> = self.lakes(pd, vertices, selection).into_iter().filter(|lake| self.accepted(lake)).collect();
+        let mut lines: Vec<String> = vec![format!("{}: {} lakes", pd.label, lakes.len())];
+        for (i, lake) in lakes.iter().enumerate(){
+            lines.push(format!("  {}: area {:.1}, surface {:.2}, depth {:.2}", i+1, lake.area, lake.height, lake.depth));
+        }
+        return lines;
+    }
+
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::prelude::warn;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error;
+use serde::ser::SerializeMap;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+
+use super::color::ColorGradient;
+use super::modifier::{ModifierRegistry, TerrainModifier};
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+
+
+// Parameters of the modifier captured at the moment it was applied.
+// Stored as {name: params}, the instance is built through ModifierRegistry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modifier {
+    pub name:   String,
+    pub params: JsonValue
+}
+
+impl Modifier {
+    pub fn from_instance(modifier: &dyn TerrainModifier) -> Self {
+        Modifier{name: modifier.name().to_string(), params: modifier.to_value()}
+    }
+}
+
+impl Serialize for Modifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&self.name, &self.params)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Modifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = BTreeMap::<String, JsonValue>::deserialize(deserializer)?;
+        if map.len() != 1 {
+            return Err(D::Error::custom("modifier has to be a single {name: params} entry"));
+        }
+        let (name, params) = map.into_iter().next().unwrap();
+        return Ok(Modifier{name, params});
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModifierLayer {
+    pub modifier:   Modifier,
+    pub selection:  Vec<usize>,             // vertex indices the modifier was applied to
+    pub gradient:   Option<ColorGradient>,  // set if gradient was applied automatically after modifier
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weights:    Vec<f32>,               // soft selection weight of each selected vertex, empty for hard selection
+    pub active:     bool,
+    #[serde(skip)]
+    pub open:       bool
+}
+
+// open is only ui state
+impl PartialEq for ModifierLayer {
+    fn eq(&self, other: &Self) -> bool {
+        self.modifier == other.modifier && self.selection == other.selection && self.gradient == other.gradient
+        && self.weights == other.weights && self.active == other.active
+    }
+}
+
+impl ModifierLayer {
+    pub fn new(modifier: Modifier, selection: Vec<usize>, gradient: Option<ColorGradient>) -> Self {
+        ModifierLayer{modifier, selection, gradient, weights: Vec::new(), active: true, open: false}
+    }
+
+    // vertices have to be ordered by index
+    pub fn apply(&self, registry: &ModifierRegistry, pd: &PlaneData, vertices: &mut Vec<Vertex>) {
+        if !self.active {
+            return;
+        }
+
+        let modifier = match registry.build(&self.modifier) {
+            Ok(modifier) => {modifier}
+            Err(e) => {
+                warn!("Skipping layer: {}", e);
+                return;
+            }
+        };
+        // result is blended with the original vertices by selection weights,
+        // selection and weights are paired before out of range indices are skipped
+        let original: Vec<(Vertex, f32)> = match self.weights.is_empty() {
+            true  => {Vec::new()}
+            false => {self.selection.iter().zip(self.weights.iter())
+                                    .filter_map(|(index, weight)| vertices.get(*index).map(|v| (*v, *weight)))
+                                    .collect()}
+        };
+
+        modifier.apply(pd, vertices, &self.selection);
+
+        if let Some(gradient) = &self.gradient {
+            if modifier.is_height() {
+                for index in self.selection.iter(){
+                    let Some(v) = vertices.get_mut(*index) else {continue;};
+                    v.clr = gradient.apply(v.loc[1]);
+                }
+            }
+        }
+
+        for (orig, weight) in original.iter(){
+            let Some(v) = vertices.get_mut(orig.index) else {continue;};
+            for i in 0..3 {
+                v.loc[i] = orig.loc[i] + (v.loc[i] - orig.loc[i])*weight;
+            }
+            for i in 0..4 {
+                v.clr[i] = orig.clr[i] + (v.clr[i] - orig.clr[i])*weight;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_stay_with_their_vertex() {
+        let registry = ModifierRegistry::new();
+        let mut pd = PlaneData::new();
+        pd.subdivisions = [2, 2];
+        let mut vertices = pd.base_vertices();
+
+        // index 100 is outside of the plane and is skipped
+        let mut layer = ModifierLayer::new(registry.modifier("Value").unwrap(), vec![100, 3], None);
+        layer.weights = vec![1.0, 0.5];
+        layer.apply(&registry, &pd, &mut vertices);
+        assert_eq!(vertices[3].loc[1], 5.0);
+    }
+}
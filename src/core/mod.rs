@@ -3,9 +3,29 @@ pub mod noises;
 pub mod vertex;
 pub mod planes;
 pub mod utils;
+pub mod binary;
 pub mod easings;
+pub mod files;
+pub mod export;
+pub mod import;
 pub mod color;
+pub mod coastline;
 pub mod value;
 pub mod wave;
 pub mod terrace;
 pub mod offset;
+pub mod grid;
+pub mod flow;
+pub mod hydraulic;
+pub mod thermal;
+pub mod wanders;
+pub mod world;
+pub mod lakes;
+pub mod layers;
+pub mod modifier;
+pub mod recipe;
+pub mod rivers;
+pub mod save;
+pub mod stitch;
+pub mod terrain;
+pub mod version;
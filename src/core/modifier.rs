@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value as JsonValue;
+
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
+
+use super::coastline::Coastline;
+use super::color::{Color, ColorGradient};
+use super::hydraulic::HydraulicErosion;
+use super::lakes::Lakes;
+use super::layers::Modifier;
+use super::noises::Noise;
+use super::offset::Offset;
+use super::planes::PlaneData;
+use super::terrace::Terrace;
+use super::thermal::ThermalErosion;
+use super::value::Value;
+use super::vertex::Vertex;
+use super::wanders::TargetWanderNoise;
+use super::wave::Wave;
+
+
+// Interface of terrain modifiers. Custom modifiers implement it, derive Serialize, Deserialize, Clone and Default,
+// and are added to the app with app.register_modifier::<T>()
+pub trait TerrainModifier: ModifierData + Send + Sync + 'static {
+    // unique name, used as key in save files and recipes
+    fn name(&self) -> &'static str;
+
+    // applies modifier to selected vertices of the plane, vertices are ordered by index
+    // (signature is implemented by modifiers outside of the crate, so it keeps &Vec)
+    #[allow(clippy::ptr_arg)]
+    fn apply(&self, pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>);
+
+    // height modifiers are followed by automatic gradient if enabled
+    fn is_height(&self) -> bool {
+        false
+    }
+
+    // colors added to the palette when modifier is applied
+    fn used_colors(&self) -> Vec<[f32; 4]> {
+        Vec::new()
+    }
+
+    // fixes random parts of parameters (seeds) before modifier is stored in a layer,
+    // so evaluating the layer again gives the same result
+    fn prepare(&mut self) {}
+
+    // summary lines shown in the editor, computed on vertices before the modifier is applied
+    fn report(&self, _pd: &PlaneData, _vertices: &Vec<Vertex>, _selection: &Vec<usize>) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, _ui: &mut Ui, _colors: &mut Colors) {}
+}
+
+// Serialization and cloning of modifiers, implemented for every serializable TerrainModifier
+pub trait ModifierData {
+    fn to_value(&self) -> JsonValue;
+    fn clone_box(&self) -> Box<dyn TerrainModifier>;
+}
+
+impl<T: TerrainModifier + Serialize + Clone> ModifierData for T {
+    fn to_value(&self) -> JsonValue {
+        return serde_json::to_value(self).unwrap_or(JsonValue::Null);
+    }
+    fn clone_box(&self) -> Box<dyn TerrainModifier> {
+        return Box::new(self.clone());
+    }
+}
+
+impl Clone for Box<dyn TerrainModifier> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+
+type ModifierLoader = fn(JsonValue) -> Result<Box<dyn TerrainModifier>, serde_json::Error>;
+
+// All modifiers known to the app. Holds one instance per modifier with the current editor parameters.
+#[derive(Resource, Clone)]
+pub struct ModifierRegistry {
+    pub modifiers:  Vec<Box<dyn TerrainModifier>>,
+    loaders:        HashMap<String, ModifierLoader>
+}
+
+impl ModifierRegistry {
+    // registry with the core modifiers
+    pub fn new() -> Self {
+        let mut registry = ModifierRegistry{modifiers: Vec::new(), loaders: HashMap::new()};
+        registry.register::<Color>();
+        registry.register::<ColorGradient>();
+        registry.register::<Noise>();
+        registry.register::<Offset>();
+        registry.register::<Value>();
+        registry.register::<Wave>();
+        registry.register::<Terrace>();
+        registry.register::<HydraulicErosion>();
+        registry.register::<ThermalErosion>();
+        registry.register::<TargetWanderNoise>();
+        registry.register::<Lakes>();
+        registry.register::<Coastline>();
+        return registry;
+    }
+
+    pub fn register<T: TerrainModifier + DeserializeOwned + Default>(&mut self) {
+        let modifier = T::default();
+        let name = modifier.name();
+        if self.loaders.contains_key(name) {
+            warn!("Modifier {} is already registered", name);
+            return;
+        }
+        self.loaders.insert(name.to_string(), |value| Ok(Box::new(serde_json::from_value::<T>(value)?)));
+        self.modifiers.push(Box::new(modifier));
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        return self.modifiers.iter().map(|m| m.name()).collect();
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn TerrainModifier> {
+        return self.modifiers.iter().find(|m| m.name() == name).map(|m| m.as_ref());
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Box<dyn TerrainModifier>> {
+        return self.modifiers.iter_mut().find(|m| m.name() == name);
+    }
+
+    // modifier instance from stored parameters
+    pub fn build(&self, modifier: &Modifier) -> Result<Box<dyn TerrainModifier>, String> {
+        let Some(loader) = self.loaders.get(&modifier.name) else {
+            return Err(format!("Unknown modifier: {}", modifier.name));
+        };
+        return loader(modifier.params.clone()).map_err(|e| format!("Invalid parameters of {}: {}", modifier.name, e));
+    }
+
+    // current parameters of a registered modifier, prepared to be stored in a layer
+    pub fn modifier(&self, name: &str) -> Option<Modifier> {
+        let mut instance = self.modifiers.iter().find(|m| m.name() == name)?.clone();
+        instance.prepare();
+        return Some(Modifier::from_instance(instance.as_ref()));
+    }
+
+    // replaces current parameters of registered modifiers, unknown names are skipped
+    pub fn set_params(&mut self, modifiers: &[Modifier]) {
+        for modifier in modifiers.iter(){
+            match self.build(modifier) {
+                Ok(instance) => {
+                    if let Some(m) = self.get_mut(&modifier.name) {
+                        *m = instance;
+                    }
+                }
+                Err(e) => {warn!("{}", e);}
+            }
+        }
+    }
+
+    pub fn params(&self) -> Vec<Modifier> {
+        return self.modifiers.iter().map(|m| Modifier::from_instance(m.as_ref())).collect();
+    }
+}
+
+impl Default for ModifierRegistry {
+    fn default() -> Self {
+        ModifierRegistry::new()
+    }
+}
+
+
+pub trait RegisterModifier {
+    fn register_modifier<T: TerrainModifier + DeserializeOwned + Default>(&mut self) -> &mut Self;
+}
+
+impl RegisterModifier for App {
+    fn register_modifier<T: TerrainModifier + DeserializeOwned + Default>(&mut self) -> &mut Self {
+        self.world.get_resource_or_insert_with(ModifierRegistry::new).register::<T>();
+        return self;
+    }
+}
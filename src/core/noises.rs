@@ -4,12 +4,17 @@ use noise::{NoiseFn, OpenSimplex, Perlin, PerlinSurflet, Simplex, SuperSimplex,
 use serde::{Serialize, Deserialize};
 use std::slice::Iter;
 use super::easings::Easings;
-use bevy_egui::{egui, egui::Ui};
-use crate::editor::mtb_ui::ModResources;
-use bevy::prelude::ResMut;
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
 
 
 #[derive(Clone, Resource, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Noise {
     pub noise:          Noises,
     pub seed:           u32,
@@ -21,6 +26,12 @@ pub struct Noise {
     pub reset:          bool,
     pub reset_value:    f32
 }
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise::new()
+    }
+}
 impl Noise {
     pub fn new() -> Self {
         Noise { 
@@ -40,7 +51,7 @@ impl Noise {
 
 impl Noise {
     pub fn set(&self) -> NoiseFunction {
-        let nfn = NoiseFunction::new(self.noise.clone(), self.seed, self.octaves, self.freq);
+        let nfn = NoiseFunction::new(self.noise, self.seed, self.octaves, self.freq);
         return nfn;
     }
     pub fn apply(&self, noise_fn: &NoiseFunction, pos: &[f32; 3], loc: &[f32; 3]) -> f32 {
@@ -60,45 +71,6 @@ impl Noise {
         return eased_r * gpos[1];    
     }
 
-    pub fn ui(ui: &mut Ui, mod_res: &mut ResMut<ModResources>) {
-
-        egui::ComboBox::from_label("Noise")
-        .width(140.0)
-        .selected_text(format!("{:?}", mod_res.noise.noise))
-        .show_ui(ui, |ui| {
-          for &p in Noises::iterator(){
-            ui.selectable_value(&mut mod_res.noise.noise, p, format!("{p:?}"));
-          }
-        });
-
-        ui.separator();
-
-        ui.columns(2, |columns| {
-          columns[1].label("Seed");
-          columns[0].add(egui::DragValue::new(&mut mod_res.noise.seed).speed(1.0));
-          columns[1].label("Scale");
-          columns[0].add(egui::DragValue::new(&mut mod_res.noise.scale).speed(0.0001));
-          columns[1].label("Frequency");
-          columns[0].add(egui::DragValue::new(&mut mod_res.noise.freq).speed(0.1));
-          columns[1].label("Octaves");
-          columns[0].add(egui::DragValue::new(&mut mod_res.noise.octaves).speed(1.0));
-        });
-
-        egui::ComboBox::from_label("Easing")
-        .width(140.0)
-        .selected_text(format!("{:?}", mod_res.noise.easing))
-        .show_ui(ui, |ui| {
-          for &p in Easings::iterator(){
-            ui.selectable_value(&mut mod_res.noise.easing, p, format!("{p:?}"));
-          }
-        });
-        ui.checkbox(&mut mod_res.noise.global, "Use global position?");
-
-        ui.checkbox(&mut mod_res.noise.reset, "Reset everytime?");
-        if mod_res.noise.reset {
-            ui.add(egui::DragValue::new(&mut mod_res.noise.reset_value).speed(1.0));
-        }
-    }
 }
 
 
@@ -138,7 +110,7 @@ pub enum Noises {
 }
 
 
-impl<'a> Noises {
+impl Noises {
       pub fn iterator() -> Iter<'static, Noises> {
     static NOISES_OPTIONS: [Noises; 27] = [
         Noises::Perlin,
@@ -206,72 +178,70 @@ pub enum NoiseFunction {
 
 impl NoiseFunction {
     pub fn apply(&self, scale: f64, x: f64, z: f64) -> f64 {
-        let r: f64;
-        match &self {
+        let r: f64 = match &self {
             // XD but I really dont know how to make it better
-            NoiseFunction::Perlin(f)                     => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::PerlinSurflet(f)              => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::Value(f)                      => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::OpenSimplex(f)                => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::SuperSimplex(f)               => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::Worley(f)                     => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::Simplex(f)                    => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::FBMPerlin(f)                  => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::BMPerlin(f)                   => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::BPerlin(f)                    => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::RMPerlin(f)                   => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::HMPerlin(f)                   => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::FBMPerlinSurflet(f)           => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::BMPerlinSurflet(f)            => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::BPerlinSurflet(f)             => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::RMPerlinSurflet(f)            => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::HMPerlinSurflet(f)            => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::FBMValue(f)                   => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::BMValue(f)                    => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::BValue(f)                     => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::RMValue(f)                    => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::HMValue(f)                    => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::FBMSS(f)                      => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::BMSS(f)                       => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::BSS(f)                        => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::RMSS(f)                       => {r = f.get([x* scale, z * scale])}
-            NoiseFunction::HMSS(f)                       => {r = f.get([x* scale, z * scale])}
-        }
+            NoiseFunction::Perlin(f)                     => {f.get([x* scale, z * scale])}
+            NoiseFunction::PerlinSurflet(f)              => {f.get([x* scale, z * scale])}
+            NoiseFunction::Value(f)                      => {f.get([x* scale, z * scale])}
+            NoiseFunction::OpenSimplex(f)                => {f.get([x* scale, z * scale])}
+            NoiseFunction::SuperSimplex(f)               => {f.get([x* scale, z * scale])}
+            NoiseFunction::Worley(f)                     => {f.get([x* scale, z * scale])}
+            NoiseFunction::Simplex(f)                    => {f.get([x* scale, z * scale])}
+            NoiseFunction::FBMPerlin(f)                  => {f.get([x* scale, z * scale])}
+            NoiseFunction::BMPerlin(f)                   => {f.get([x* scale, z * scale])}
+            NoiseFunction::BPerlin(f)                    => {f.get([x* scale, z * scale])}
+            NoiseFunction::RMPerlin(f)                   => {f.get([x* scale, z * scale])}
+            NoiseFunction::HMPerlin(f)                   => {f.get([x* scale, z * scale])}
+            NoiseFunction::FBMPerlinSurflet(f)           => {f.get([x* scale, z * scale])}
+            NoiseFunction::BMPerlinSurflet(f)            => {f.get([x* scale, z * scale])}
+            NoiseFunction::BPerlinSurflet(f)             => {f.get([x* scale, z * scale])}
+            NoiseFunction::RMPerlinSurflet(f)            => {f.get([x* scale, z * scale])}
+            NoiseFunction::HMPerlinSurflet(f)            => {f.get([x* scale, z * scale])}
+            NoiseFunction::FBMValue(f)                   => {f.get([x* scale, z * scale])}
+            NoiseFunction::BMValue(f)                    => {f.get([x* scale, z * scale])}
+            NoiseFunction::BValue(f)                     => {f.get([x* scale, z * scale])}
+            NoiseFunction::RMValue(f)                    => {f.get([x* scale, z * scale])}
+            NoiseFunction::HMValue(f)                    => {f.get([x* scale, z * scale])}
+            NoiseFunction::FBMSS(f)                      => {f.get([x* scale, z * scale])}
+            NoiseFunction::BMSS(f)                       => {f.get([x* scale, z * scale])}
+            NoiseFunction::BSS(f)                        => {f.get([x* scale, z * scale])}
+            NoiseFunction::RMSS(f)                       => {f.get([x* scale, z * scale])}
+            NoiseFunction::HMSS(f)                       => {f.get([x* scale, z * scale])}
+        };
         return r;
     }
 
     pub fn _apply3d(&self, scale: f64, x: f64, y: f64, z: f64) -> f64 {
-        let r: f64;
-        match &self {
+        let r: f64 = match &self {
             // XD but I really dont know how to make it better
-            NoiseFunction::Perlin(f)                     => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::PerlinSurflet(f)              => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::Value(f)                      => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::OpenSimplex(f)                => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::SuperSimplex(f)               => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::Worley(f)                     => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::Simplex(f)                    => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::FBMPerlin(f)                  => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::BMPerlin(f)                   => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::BPerlin(f)                    => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::RMPerlin(f)                   => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::HMPerlin(f)                   => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::FBMPerlinSurflet(f)           => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::BMPerlinSurflet(f)            => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::BPerlinSurflet(f)             => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::RMPerlinSurflet(f)            => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::HMPerlinSurflet(f)            => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::FBMValue(f)                   => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::BMValue(f)                    => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::BValue(f)                     => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::RMValue(f)                    => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::HMValue(f)                    => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::FBMSS(f)                      => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::BMSS(f)                       => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::BSS(f)                        => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::RMSS(f)                       => {r = f.get([x* scale, y*scale, z * scale])}
-            NoiseFunction::HMSS(f)                       => {r = f.get([x* scale, y*scale, z * scale])}
-        }
+            NoiseFunction::Perlin(f)                     => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::PerlinSurflet(f)              => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::Value(f)                      => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::OpenSimplex(f)                => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::SuperSimplex(f)               => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::Worley(f)                     => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::Simplex(f)                    => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::FBMPerlin(f)                  => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::BMPerlin(f)                   => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::BPerlin(f)                    => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::RMPerlin(f)                   => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::HMPerlin(f)                   => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::FBMPerlinSurflet(f)           => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::BMPerlinSurflet(f)            => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::BPerlinSurflet(f)             => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::RMPerlinSurflet(f)            => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::HMPerlinSurflet(f)            => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::FBMValue(f)                   => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::BMValue(f)                    => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::BValue(f)                     => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::RMValue(f)                    => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::HMValue(f)                    => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::FBMSS(f)                      => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::BMSS(f)                       => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::BSS(f)                        => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::RMSS(f)                       => {f.get([x* scale, y*scale, z * scale])}
+            NoiseFunction::HMSS(f)                       => {f.get([x* scale, y*scale, z * scale])}
+        };
         return r;
     }
 
@@ -412,3 +382,24 @@ impl NoiseFunction {
         return nfn;
     }
 }
+
+
+impl TerrainModifier for Noise {
+    fn name(&self) -> &'static str {
+        "Noise"
+    }
+    fn apply(&self, _pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        let nfn = self.set();
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.loc[1] = Noise::apply(self, &nfn, &v.loc, &v.loc);
+        }
+    }
+    fn is_height(&self) -> bool {
+        true
+    }
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
+    }
+}
@@ -1,16 +1,27 @@
-use bevy_egui::{egui, egui::Ui};
-use bevy::prelude::ResMut;
 use serde::{Serialize,Deserialize};
-use crate::editor::mtb_ui::ModResources;
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
 
 
 #[derive(Clone, Debug, Copy, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Offset {
     pub x: f32,
     pub y: f32,
     pub z: f32
 }
 
+impl Default for Offset {
+    fn default() -> Self {
+        Offset::new()
+    }
+}
+
 
 impl Offset {
     pub fn new() -> Self {
@@ -21,19 +32,21 @@ impl Offset {
         return [loc[0] + self.x, loc[1] + self.y, loc[2] + self.z];
     }
 
-    pub fn ui(ui: &mut Ui, mod_res: &mut ResMut<ModResources>) {
-
-        ui.separator();
-        ui.vertical(|ui| {
-            ui.label("Offsets");
-            ui.columns(2, |columns| {
-              columns[1].label("X");
-              columns[0].add(egui::DragValue::new(&mut mod_res.offset.x).speed(0.1));
-              columns[1].label("Y");
-              columns[0].add(egui::DragValue::new(&mut mod_res.offset.y).speed(0.1));
-              columns[1].label("Z");
-              columns[0].add(egui::DragValue::new(&mut mod_res.offset.z).speed(0.1));
-            });
-        });
+}
+
+
+impl TerrainModifier for Offset {
+    fn name(&self) -> &'static str {
+        "Offset"
+    }
+    fn apply(&self, _pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.loc = Offset::apply(self, &v.loc);
+        }
     }
-}
\ No newline at end of file
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
+    }
+}
@@ -1,175 +1,31 @@
-
 use bevy::prelude::*;
-use bevy::input::common_conditions::{input_pressed, input_just_pressed};
-use bevy_egui::EguiContexts;
-use bevy_egui::egui::{DragValue, Window};
-use bevy_mod_picking::prelude::*;
 use bevy::render::mesh::{Indices, PrimitiveTopology};
 use bevy::reflect::{TypeUuid, TypePath};
+use bevy::utils::{HashMap, HashSet};
+use std::collections::VecDeque;
 use serde::{Serialize, Deserialize};
-use crate::editor::{AppState, DoubleClick};
-use super::utils::{AABB, get_mesh_stats};
-use crate::editor::mtb_grid::{hover_check, HoverData, Hoverables};
-
-pub struct PlanesPlugin;
-
-impl Plugin for PlanesPlugin {
-    fn build(&self, app: &mut App) {
-        app
-        .add_event::<SpawnNewPlaneEvent>()
-        .add_event::<PickPlane>()
-        .add_systems(PreUpdate,  clear.run_if(input_just_pressed(MouseButton::Right)).run_if(in_state(AppState::Object)))
-        .add_systems(Update,     spawn_new_plane.run_if(on_event::<SpawnNewPlaneEvent>()).run_if(in_state(AppState::Object)))
-        .add_systems(Update,     pick_plane.run_if(in_state(AppState::Object)))
-        .add_systems(Update,     drag.run_if(input_pressed(MouseButton::Left)
-                                 .and_then(in_state(AppState::Object)))
-                                 .after(hover_check))
-        .add_systems(PostUpdate, highlight_picked_plane
-                                        .after(pick_plane)
-                                        .run_if(in_state(AppState::Object))
-                                    )
-        .add_systems(Update,     drop_plane.run_if(input_just_pressed(KeyCode::Back)))
-        .add_systems(OnExit(AppState::Object), deselect_plane)
-
-        .add_systems(PostUpdate, spawn_edit_plane.run_if(in_state(AppState::Object).and_then(on_event::<DoubleClick>())))
-        .add_systems(PostUpdate, edit_plane.run_if(in_state(AppState::Object)))
-        ;
-    }
-  }
-
-  pub fn spawn_edit_plane(hover_data:        Res<HoverData>,
-                          mut planes:        Query<&mut PlaneEdit>) {
-    
-      if let Hoverables::Entity(entity) = hover_data.hoverable {
-          if let Ok(mut pd) = planes.get_mut(entity) {
-              pd.0 = true;
-          }
-      }
-      
-    }
-
-  pub fn edit_plane(mut contexts:      EguiContexts,
-                    mut planes:        Query<(&mut PlaneData, &mut Transform, &mut AABB,  &mut PlaneEdit)>){
-
-    let ctx = contexts.ctx_mut();
-    for (mut pd, mut tr, mut aabb, mut pe) in planes.iter_mut() {
-        if !pe.0 {
-            continue;
-        }
-
-        Window::new(format!("Plane Edit {}", pd.label))
-                .open(&mut pe.0)
-                .resizable(true)
-                .default_width(280.0)
-                .show(ctx, |ui| {
-                    
-                    ui.columns(2, |columns| {
-                        columns[0].label("Loc X ");
-                        columns[1].add(DragValue::new(&mut pd.loc[0]).speed(1.0));
-                        columns[0].label("Loc Y ");
-                        columns[1].add(DragValue::new(&mut pd.loc[1]).speed(1.0));
-                        columns[0].label("Loc Z ");
-                        columns[1].add(DragValue::new(&mut pd.loc[2]).speed(1.0));
-                    }
-                );
-
-        });
-
-        tr.translation.x = pd.loc[0];  
-        tr.translation.y = pd.loc[1];  
-        tr.translation.z = pd.loc[2];   
-        *aabb = pd.get_aabb();
-    }
-  }
-
-
-  pub fn drop_plane(mut commands: Commands, 
-                    planes:       Query<(Entity, &PickedPlane)>) {
-    for (entity, picked) in planes.iter(){
-        if picked.0 {
-            commands.entity(entity).despawn_recursive();
-        }
-    }
-  }
-
-  #[derive(Event)]
-  pub struct PickPlane {
-    pub entity: Entity
-  }
-
-  impl  From<ListenerInput<Pointer<Down>>> for PickPlane {
-    fn from(event: ListenerInput<Pointer<Down>>) -> Self {
-        PickPlane{entity: event.target}
-    }
-}
-
-  #[derive(Event)]
-  pub struct SpawnNewPlaneEvent {
-    pub pd: PlaneData
-  }
-  impl SpawnNewPlaneEvent {
-    pub fn new() -> Self {
-        SpawnNewPlaneEvent{pd: PlaneData::new()}
-    }
-  }
-
-#[derive(Component)]
-pub struct PickedPlane(pub bool);
-
-// Click on grid in object mode
-fn clear(mut planes: Query<&mut PickedPlane>){
-    for mut picked in planes.iter_mut(){
-        picked.0 = false;
-    }
-}
-
-
-pub fn pick_plane(mut pick_plane_event:    EventReader<PickPlane>,
-                  mut planes:              Query<&mut PickedPlane>){
-    for ev in pick_plane_event.iter(){
-        if let Ok(mut picked) = planes.get_mut(ev.entity){
-            picked.0 = true;
-        }
-    }
-}
-
-// it shouldnt work all the time
-pub fn highlight_picked_plane(
-    mut materials:         ResMut<Assets<StandardMaterial>>,
-    planes:                Query<(&mut Handle<StandardMaterial>, &PickedPlane), Changed<PickedPlane>>){
-    
-    for (handle_mat, picked) in planes.iter(){
-        if let Some(mat) = materials.get_mut(handle_mat){
-            if picked.0 {
-                mat.base_color.set_g(0.4);
-                mat.base_color.set_b(0.4);
-            } else {
-                mat.base_color.set_g(1.0);
-                mat.base_color.set_b(1.0);
-            }
-        }
-    }
-}
-  pub fn spawn_new_plane(mut commands:     Commands, 
-                         mut meshes:       ResMut<Assets<Mesh>>,
-                         mut materials:    ResMut<Assets<StandardMaterial>>,
-                         mut spawn_plane:  EventReader<SpawnNewPlaneEvent>,
-                        ){
-    for ev in spawn_plane.iter(){
-        let _entity = ev.pd.spawn(&mut commands, &mut meshes, &mut materials);
-    }
-  }
-
-#[derive(Serialize, Deserialize, Debug, Clone, Component, Resource)]
+use super::utils::AABB;
+use super::layers::ModifierLayer;
+use super::modifier::ModifierRegistry;
+use super::save::SavePlaneData;
+use super::vertex::Vertex;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Component, Resource)]
+#[serde(default)]
 pub struct PlaneData {
     pub label:        String,
     pub loc:          [f32; 3],
     pub subdivisions: [u32; 2],
-    pub dims:         [f32; 2]
+    pub dims:         [f32; 2],
+    #[serde(default)]
+    pub layers:       Vec<ModifierLayer>
 }
 
-#[derive(Component)]
-pub struct PlaneEdit(pub bool);
+impl Default for PlaneData {
+  fn default() -> Self {
+    PlaneData::new()
+  }
+}
 
 impl PlaneData {
 
@@ -187,45 +43,96 @@ impl PlaneData {
     return PlaneData{label: "Default Plane".to_string(),
                      loc: [0.0, 0.0, 0.0], 
                      dims: [200.0, 200.0], 
-                     subdivisions: [10,10]
+                     subdivisions: [10,10],
+                     layers: Vec::new()
                     };
     }
 
+    pub fn rect_plane(&self) -> RectPlane {
+      RectPlane {
+        width: self.dims[0],
+        length: self.dims[1],
+        x_subdivisions: self.subdivisions[0],
+        z_subdivisions: self.subdivisions[1]
+      }
+    }
+
+    // vertices of flat plane, before any modifier
+    pub fn base_vertices(&self) -> Vec<Vertex> {
+      let clr: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+      return self.rect_plane().positions().iter().enumerate().map(|(index, pos)| Vertex::new(index, pos, &clr)).collect();
+    }
 
+    pub fn evaluate_layers(&self, registry: &ModifierRegistry) -> Vec<Vertex> {
+      return self.evaluate(&self.layers, registry);
+    }
 
+    fn evaluate(&self, layers: &[ModifierLayer], registry: &ModifierRegistry) -> Vec<Vertex> {
+      let mut vertices = self.base_vertices();
+      for layer in layers.iter(){
+        layer.apply(registry, self, &mut vertices);
+      }
+      return vertices;
+    }
 
-    pub fn spawn(&self,
-                 commands:           &mut Commands, 
-                 meshes:             &mut ResMut<Assets<Mesh>>,
-                 materials:          &mut ResMut<Assets<StandardMaterial>>) -> Entity {
-
-        let mesh = plane_mesh(&self.subdivisions, &self.dims);
-        get_mesh_stats(&mesh);
-    
-        let entity = commands.spawn((PbrBundle {
-            material: materials.add(StandardMaterial{alpha_mode: AlphaMode::Mask(0.5),..default()}),
-            mesh: meshes.add(mesh),
-            transform: Transform::from_translation(self.loc.into()),
-            ..default()
-            },
-            PickedPlane(false),
-            TerrainPlane,
-            PlaneEdit(false),
-            PickableBundle::default(),
-            RaycastPickTarget::default(),
-            On::<Pointer<Down>>::send_event::<PickPlane>(),
-            self.clone(),
-            self.get_aabb()
-        )).id();
-    
-        return entity;
+    // Evaluates layers again after they changed from previous_layers. Edits that are not layers
+    // (vertex drags, sculpt, paint, stitching, rivers) are the difference between current vertices
+    // and the previous evaluation, they are kept on top of the new one.
+    // current is ordered by index
+    pub fn reevaluate_layers(&self, registry: &ModifierRegistry, previous_layers: &[ModifierLayer], current: &[Vertex]) -> Vec<Vertex> {
+      let previous = self.evaluate(previous_layers, registry);
+      let mut vertices = self.evaluate_layers(registry);
+      for v in vertices.iter_mut(){
+        let (Some(prev), Some(cur)) = (previous.get(v.index), current.get(v.index)) else {continue;};
+        for i in 0..3 {
+          v.loc[i] += cur.loc[i] - prev.loc[i];
+        }
+        for i in 0..4 {
+          v.clr[i] = (v.clr[i] + cur.clr[i] - prev.clr[i]).clamp(0.0, 1.0);
+        }
+      }
+      return vertices;
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, TypeUuid, TypePath)]
+// Contents of the save file loaded through asset server
+#[derive(Serialize, Deserialize, Clone, TypeUuid, TypePath)]
 #[uuid = "413be529-bfeb-41b3-9db0-4b8b380a2c46"]
 pub struct Planes {
-    pub planes: Vec<PlaneData>
+    pub planes: Vec<SavePlaneData>
+}
+
+// Label not used by any other plane: "Plane", "Plane 2", "Plane 3", ...
+// Planes are matched by label on reload, so spawned planes should get unique labels
+pub fn unique_label(label: &str, labels: &HashSet<String>) -> String {
+    let mut unique = label.to_string();
+    let mut n = 2;
+    while labels.contains(&unique) {
+        unique = format!("{} {}", label, n);
+        n += 1;
+    }
+    return unique;
+}
+
+// Pairs saved planes with existing ones by label, planes sharing a label are paired in order.
+// Returns the existing plane for every saved plane (None if it has to be spawned)
+// and the existing planes left without a saved plane.
+pub fn match_planes<T: Copy>(existing: &[(String, T)], saved: &[SavePlaneData]) -> (Vec<Option<T>>, Vec<T>) {
+    let mut by_label: HashMap<&str, VecDeque<usize>> = HashMap::new();
+    for (index, (label, _item)) in existing.iter().enumerate(){
+        by_label.entry(label.as_str()).or_default().push_back(index);
+    }
+    let mut used = vec![false; existing.len()];
+    let mut matched: Vec<Option<T>> = Vec::with_capacity(saved.len());
+    for spd in saved.iter(){
+        let index = by_label.get_mut(spd.plane.label.as_str()).and_then(|indices| indices.pop_front());
+        if let Some(index) = index {
+            used[index] = true;
+        }
+        matched.push(index.map(|index| existing[index].1));
+    }
+    let left = existing.iter().zip(used.iter()).filter(|(_, used)| !**used).map(|((_, item), _)| *item).collect();
+    return (matched, left);
 }
 
 #[derive(Resource)]
@@ -253,6 +160,22 @@ pub struct RectPlane {
 }
 
 
+impl RectPlane {
+    pub fn positions(&self) -> Vec<[f32; 3]> {
+        let z_vertex_count = self.x_subdivisions + 2;
+        let x_vertex_count = self.z_subdivisions + 2;
+        let mut positions: Vec<[f32; 3]> = Vec::with_capacity((z_vertex_count * x_vertex_count) as usize);
+        for y in 0..z_vertex_count {
+            for x in 0..x_vertex_count {
+                let tx = x as f32 / (x_vertex_count - 1) as f32;
+                let ty = y as f32 / (z_vertex_count - 1) as f32;
+                positions.push([(-0.5 + tx) * self.width, 0.0, (-0.5 + ty) * self.length]);
+            }
+        }
+        return positions;
+    }
+}
+
 // Based stronly on plane code
 
 impl From<RectPlane> for Mesh {
@@ -263,7 +186,7 @@ impl From<RectPlane> for Mesh {
         let num_indices = ((z_vertex_count - 1) * (x_vertex_count - 1) * 6) as usize;
         let up = Vec3::Y.to_array();
 
-        let mut positions: Vec<[f32; 3]> = Vec::with_capacity(num_vertices);
+        let positions: Vec<[f32; 3]> = rect_plane.positions();
         let mut normals: Vec<[f32; 3]> = Vec::with_capacity(num_vertices);
         let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(num_vertices);
         let mut indices: Vec<u32> = Vec::with_capacity(num_indices);
@@ -272,7 +195,6 @@ impl From<RectPlane> for Mesh {
             for x in 0..x_vertex_count {
                 let tx = x as f32 / (x_vertex_count - 1) as f32;
                 let ty = y as f32 / (z_vertex_count - 1) as f32;
-                positions.push([(-0.5 + tx) * rect_plane.width, 0.0, (-0.5 + ty) * rect_plane.length]);
                 normals.push(up);
                 uvs.push([tx, 1.0 - ty]);
             }
@@ -299,36 +221,70 @@ impl From<RectPlane> for Mesh {
     }
 }
 
-pub fn deselect_plane(mut materials:    ResMut<Assets<StandardMaterial>>,
-                      mut planes:       Query<(&mut Handle<StandardMaterial>, &mut PickedPlane)>){
-    for (handle_mat, mut picked) in planes.iter_mut(){
-        if picked.0 {
-            picked.0 = false;
-            if let Some(mat) = materials.get_mut(&handle_mat){
-                mat.base_color.set_g(1.0);
-                mat.base_color.set_b(1.0);
-            }
 
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_edit_survives_layer_toggle() {
+        let registry = ModifierRegistry::new();
+        let mut pd = PlaneData::new();
+        pd.subdivisions = [2, 2];
+        let modifier = registry.modifier("Value").unwrap();  // sets height to 10
+        let selection: Vec<usize> = (0..16).collect();
+        pd.layers.push(ModifierLayer::new(modifier, selection, None));
+
+        let mut current = pd.evaluate_layers(&registry);
+        assert_eq!(current[3].loc[1], 10.0);
+        current[3].loc[1] += 5.0;                       // sculpt
+        current[4].clr = [1.0, 0.0, 0.0, 1.0];          // paint
+
+        let previous = pd.layers.clone();
+        pd.layers[0].active = false;
+        let off = pd.reevaluate_layers(&registry, &previous, &current);
+        assert_eq!(off[3].loc[1], 5.0);
+        assert_eq!(off[2].loc[1], 0.0);
+        assert_eq!(off[4].clr, [1.0, 0.0, 0.0, 1.0]);
+
+        let previous = pd.layers.clone();
+        pd.layers[0].active = true;
+        let on = pd.reevaluate_layers(&registry, &previous, &off);
+        assert_eq!(on[3].loc[1], 15.0);
+        assert_eq!(on[2].loc[1], 10.0);
+        assert_eq!(on[4].clr, [1.0, 0.0, 0.0, 1.0]);
     }
-}
 
-pub fn drag(mut picked_plane:  Query<(&mut Transform, &mut AABB, &mut PlaneData, &PickedPlane)>, 
-            hover_data:        Res<HoverData>){
-
-    let delta_x = hover_data.hovered_xz.0 - hover_data.old_hovered_xz.0;
-    let delta_y = hover_data.hovered_xz.1 - hover_data.old_hovered_xz.1;
-
-    for (mut tr, mut aabb, mut pd, picked)  in picked_plane.iter_mut(){
-        if picked.0 {
-            tr.translation.x += delta_x;
-            tr.translation.z += delta_y;
-    
-            pd.loc[0] = tr.translation.x;
-            pd.loc[2] = tr.translation.z;
-    
-            *aabb = pd.get_aabb();
-        }
+    fn saved_plane(label: &str) -> SavePlaneData {
+        let mut spd = SavePlaneData{plane: PlaneData::new(), vertex: Vec::new(), flow: None};
+        spd.plane.label = label.to_string();
+        return spd;
     }
 
+    #[test]
+    fn planes_sharing_a_label_reload_in_order() {
+        let saved = vec![saved_plane("Hill"), saved_plane("Hill"), saved_plane("Lake")];
+        let existing: Vec<(String, u32)> = Vec::new();
+        let (matched, left) = match_planes(&existing, &saved);
+        assert_eq!(matched, vec![None, None, None]);
+        assert!(left.is_empty());
+
+        // reload after spawning, every plane keeps its entity
+        let existing = vec![("Hill".to_string(), 1), ("Hill".to_string(), 2), ("Lake".to_string(), 3)];
+        let (matched, left) = match_planes(&existing, &saved);
+        assert_eq!(matched, vec![Some(1), Some(2), Some(3)]);
+        assert!(left.is_empty());
+
+        // one of the duplicates removed from the save
+        let (matched, left) = match_planes(&existing, &saved[1..]);
+        assert_eq!(matched, vec![Some(1), Some(3)]);
+        assert_eq!(left, vec![2]);
+    }
+
+    #[test]
+    fn unique_label_is_numbered() {
+        let labels: HashSet<String> = ["Default Plane".to_string(), "Default Plane 2".to_string()].into_iter().collect();
+        assert_eq!(unique_label("Default Plane", &labels), "Default Plane 3");
+        assert_eq!(unique_label("Hill", &labels), "Hill");
+    }
 }
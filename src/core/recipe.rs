@@ -0,0 +1,164 @@
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+
+use super::save::SavePlaneData;
+use super::color::ColorGradient;
+use super::flow::FlowRouting;
+use super::layers::{Modifier, ModifierLayer};
+use super::modifier::ModifierRegistry;
+use super::rivers::Rivers;
+use super::planes::PlaneData;
+use super::stitch::stitch_planes;
+use super::utils::{AABB, get_distance_euclidean};
+use super::vertex::Vertex;
+
+
+// Declarative description of terrain: planes and modifiers applied to them in order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recipe {
+    pub planes: Vec<RecipePlane>,
+    #[serde(default)]
+    pub stitch: bool,                // weld borders of adjacent planes after all modifiers
+    #[serde(default)]
+    pub rivers: Option<Rivers>,      // river network carved over all planes at the end
+    #[serde(default)]
+    pub flow:   Option<FlowRouting>  // exports flow analysis of the final terrain into the save
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecipePlane {
+    pub plane:      PlaneData,
+    #[serde(default)]
+    pub modifiers:  Vec<RecipeModifier>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecipeModifier {
+    pub modifier:   Modifier,
+    #[serde(default)]
+    pub selection:  Selection,
+    #[serde(default)]
+    pub gradient:   Option<ColorGradient>
+}
+
+// Selection rules, positions are local to the plane
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub enum Selection {
+    #[default]
+    All,
+    Indices(Vec<usize>),
+    Area(AABB),
+    Circle{x: f32, z: f32, radius: f32},
+    Height{min: f32, max: f32}
+}
+
+impl Selection {
+    pub fn select(&self, vertices: &[Vertex]) -> Vec<usize> {
+        match self {
+            Selection::All => {
+                return vertices.iter().map(|v| v.index).collect();
+            }
+            Selection::Indices(indices) => {
+                return indices.iter().filter(|index| **index < vertices.len()).copied().collect();
+            }
+            Selection::Area(aabb) => {
+                return vertices.iter().filter(|v| aabb.has_point(&v.loc)).map(|v| v.index).collect();
+            }
+            Selection::Circle{x, z, radius} => {
+                return vertices.iter().filter(|v| get_distance_euclidean(&(v.loc[0], v.loc[2]), &(*x, *z)) <= *radius)
+                                      .map(|v| v.index).collect();
+            }
+            Selection::Height{min, max} => {
+                return vertices.iter().filter(|v| v.loc[1] >= *min && v.loc[1] <= *max).map(|v| v.index).collect();
+            }
+        }
+    }
+}
+
+
+impl Recipe {
+
+    // Reads recipe from .toml or .json file
+    pub fn load(path: &str) -> Result<Recipe, String> {
+        let data = fs::read_to_string(path).map_err(|e| format!("Failed to read recipe {}: {}", path, e))?;
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => {
+                return toml::from_str::<Recipe>(&data).map_err(|e| format!("Failed to parse recipe {}: {}", path, e));
+            }
+            Some("json") => {
+                return serde_json::from_str::<Recipe>(&data).map_err(|e| format!("Failed to parse recipe {}: {}", path, e));
+            }
+            _ => {
+                return Err(format!("Unknown recipe format: {}, expected .toml or .json", path));
+            }
+        }
+    }
+
+    // Applies all modifiers and returns planes in the save format.
+    // Fails on the first modifier that can't be built (unknown name or invalid parameters)
+    pub fn generate(&self, registry: &ModifierRegistry) -> Result<Vec<SavePlaneData>, String> {
+        let mut v_planes: Vec<SavePlaneData> = Vec::new();
+        for rp in self.planes.iter(){
+            let mut pd = rp.plane.clone();
+            let mut vertices = pd.evaluate_layers(registry);
+
+            for (index, rm) in rp.modifiers.iter().enumerate(){
+                let selection = rm.selection.select(&vertices);
+                // random seeds are fixed in the stored layer
+                let mut instance = registry.build(&rm.modifier)
+                                           .map_err(|e| format!("Plane '{}', modifier {}: {}", pd.label, index, e))?;
+                instance.prepare();
+                let modifier = Modifier::from_instance(instance.as_ref());
+                let layer = ModifierLayer::new(modifier, selection, rm.gradient);
+                layer.apply(registry, &pd, &mut vertices);
+                pd.layers.push(layer);
+            }
+
+            v_planes.push(SavePlaneData{plane: pd, vertex: vertices, flow: None});
+        }
+        if self.stitch {
+            stitch_planes(&mut v_planes);
+        }
+        if let Some(rivers) = &self.rivers {
+            rivers.apply(&mut v_planes);
+        }
+        if let Some(routing) = self.flow {
+            for spd in v_planes.iter_mut(){
+                spd.analyse_flow(routing);
+            }
+        }
+        return Ok(v_planes);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe(modifier: Modifier) -> Recipe {
+        let mut plane = PlaneData::new();
+        plane.label = "Hill".to_string();
+        plane.subdivisions = [2, 2];
+        let rm = RecipeModifier{modifier, selection: Selection::All, gradient: None};
+        return Recipe{planes: vec![RecipePlane{plane, modifiers: vec![rm]}], stitch: false, rivers: None, flow: None};
+    }
+
+    #[test]
+    fn invalid_modifier_fails_generation() {
+        let registry = ModifierRegistry::new();
+        let value = registry.modifier("Value").unwrap();
+        let v_planes = recipe(value.clone()).generate(&registry).unwrap();
+        assert_eq!(v_planes[0].plane.layers.len(), 1);
+
+        let mut unknown = value.clone();
+        unknown.name = "Missing".to_string();
+        let Err(e) = recipe(unknown).generate(&registry) else {panic!("unknown modifier was accepted")};
+        assert!(e.contains("'Hill'") && e.contains("modifier 0"), "{}", e);
+
+        let mut invalid = value;
+        invalid.params = serde_json::json!("not parameters");
+        assert!(recipe(invalid).generate(&registry).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::Resource;
+use bevy::utils::HashMap;
+use rand::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use super::easings::Easings;
+use super::save::SavePlaneData;
+use super::world::WorldGraph;
+
+// River network over all planes treated as one world. Sources are picked on high ground,
+// rivers follow the flood routing downhill to the world border and join where their paths meet.
+// Channel width and depth grow with the accumulated flow, riverbeds never go uphill.
+#[derive(Debug, Clone, Serialize, Deserialize, Resource)]
+#[serde(default)]
+pub struct Rivers {
+    pub seed:          u64,
+    pub sources:       usize,
+    pub source_height: f32,       // sources are picked above this fraction of the height range
+    pub spacing:       f32,       // min distance between sources
+    pub min_width:     f32,
+    pub max_width:     f32,
+    pub min_depth:     f32,
+    pub max_depth:     f32,
+    pub easing:        Easings,   // bank profile, from the edge (0) to the center (1)
+    pub paint:         bool,      // write river mask into vertex colors
+    pub color:         [f32; 4]
+}
+
+impl Default for Rivers {
+    fn default() -> Self {
+        Rivers::new()
+    }
+}
+
+// part of the river between node and its receiver
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    a:     (f32, f32),
+    b:     (f32, f32),
+    width: (f32, f32),
+    bed:   (f32, f32)
+}
+
+impl Segment {
+    // distance from the centerline, width and bed height at the closest point
+    fn closest(&self, p: &(f32, f32)) -> (f32, f32, f32) {
+        let ab = (self.b.0 - self.a.0, self.b.1 - self.a.1);
+        let len2 = ab.0*ab.0 + ab.1*ab.1;
+        let t = match len2 > 0.0 {
+            true  => {(((p.0 - self.a.0)*ab.0 + (p.1 - self.a.1)*ab.1)/len2).clamp(0.0, 1.0)}
+            false => {0.0}
+        };
+        let dist = ((self.a.0 + t*ab.0 - p.0).powi(2) + (self.a.1 + t*ab.1 - p.1).powi(2)).sqrt();
+        return (dist,
+                self.width.0 + t*(self.width.1 - self.width.0),
+                self.bed.0 + t*(self.bed.1 - self.bed.0));
+    }
+}
+
+impl Rivers {
+    pub fn new() -> Self {
+        Rivers{seed:          0,
+               sources:       8,
+               source_height: 0.6,
+               spacing:       50.0,
+               min_width:     4.0,
+               max_width:     16.0,
+               min_depth:     1.0,
+               max_depth:     6.0,
+               easing:        Easings::SmoothStep,
+               paint:         true,
+               color:         [0.2, 0.35, 0.6, 1.0]}
+    }
+
+    // carves rivers into the planes, vertices have to be ordered by index. Returns number of sources used
+    pub fn apply(&self, planes: &mut [SavePlaneData]) -> usize {
+        let graph = WorldGraph::new(planes);
+        if graph.nodes.is_empty() {
+            return 0;
+        }
+        let routing = graph.flood();
+        let acc = routing.accumulation();
+
+        let sources = self.pick_sources(&graph);
+        let mut river: Vec<bool> = vec![false; graph.nodes.len()];
+        for source in sources.iter(){
+            let mut id = *source;
+            loop {
+                if river[id] {
+                    break; // joined another river
+                }
+                river[id] = true;
+                match routing.receivers[id] {
+                    Some(r) => {id = r;}
+                    None    => {break;}
+                }
+            }
+        }
+
+        // channel size from flow, relative to the largest river
+        let max_acc = (0..graph.nodes.len()).filter(|id| river[*id]).fold(1.0f32, |m, id| m.max(acc[id]));
+        let min_acc = (0..graph.nodes.len()).filter(|id| river[*id]).fold(max_acc, |m, id| m.min(acc[id]));
+        let flow = |id: usize| -> f32 {
+            match max_acc > min_acc {
+                true  => {((acc[id] - min_acc)/(max_acc - min_acc)).sqrt()}
+                false => {0.0}
+            }
+        };
+
+        // riverbeds from sources downstream, never higher than the bed upstream
+        let mut bed: Vec<f32> = vec![f32::MAX; graph.nodes.len()];
+        let mut limit: Vec<f32> = vec![f32::MAX; graph.nodes.len()];
+        for id in routing.order.iter().rev(){
+            if !river[*id] {
+                continue;
+            }
+            let depth = self.min_depth + (self.max_depth - self.min_depth)*flow(*id);
+            bed[*id] = (graph.nodes[*id].height - depth).min(limit[*id]);
+            if let Some(r) = routing.receivers[*id] {
+                limit[r] = limit[r].min(bed[*id]);
+            }
+        }
+
+        let mut segments: Vec<Segment> = Vec::new();
+        for id in 0..graph.nodes.len() {
+            if !river[id] {
+                continue;
+            }
+            let node = &graph.nodes[id];
+            let width = self.min_width + (self.max_width - self.min_width)*flow(id);
+            let (b, width_b, bed_b) = match routing.receivers[id] {
+                Some(r) => {((graph.nodes[r].x, graph.nodes[r].z), self.min_width + (self.max_width - self.min_width)*flow(r), bed[r])}
+                None    => {((node.x, node.z), width, bed[id])}
+            };
+            segments.push(Segment{a: (node.x, node.z), b, width: (width, width_b), bed: (bed[id], bed_b)});
+        }
+        self.carve(planes, &segments);
+        return sources.len();
+    }
+
+    fn pick_sources(&self, graph: &WorldGraph) -> Vec<usize> {
+        let min_h = graph.nodes.iter().fold(f32::MAX, |m, n| m.min(n.height));
+        let max_h = graph.nodes.iter().fold(f32::MIN, |m, n| m.max(n.height));
+        let threshold = min_h + (max_h - min_h)*self.source_height.clamp(0.0, 1.0);
+
+        let mut candidates: Vec<usize> = (0..graph.nodes.len()).filter(|id| !graph.nodes[*id].outlet && graph.nodes[*id].height >= threshold).collect();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        candidates.shuffle(&mut rng);
+
+        let mut sources: Vec<usize> = Vec::new();
+        for id in candidates.iter(){
+            if sources.len() >= self.sources {
+                break;
+            }
+            if sources.iter().all(|s| graph.distance(*s, *id) >= self.spacing) {
+                sources.push(*id);
+            }
+        }
+        return sources;
+    }
+
+    fn carve(&self, planes: &mut [SavePlaneData], segments: &[Segment]) {
+        let cell = self.min_width.max(self.max_width).max(1.0);
+        let bucket = |x: f32, z: f32| -> (i64, i64) {((x/cell).floor() as i64, (z/cell).floor() as i64)};
+
+        let mut buckets: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (i, s) in segments.iter().enumerate(){
+            let (x0, z0) = bucket(s.a.0.min(s.b.0), s.a.1.min(s.b.1));
+            let (x1, z1) = bucket(s.a.0.max(s.b.0), s.a.1.max(s.b.1));
+            for bx in x0-1..=x1+1 {
+                for bz in z0-1..=z1+1 {
+                    buckets.entry((bx, bz)).or_default().push(i);
+                }
+            }
+        }
+
+        for spd in planes.iter_mut(){
+            let loc = spd.plane.loc;
+            for v in spd.vertex.iter_mut(){
+                let p = (v.loc[0] + loc[0], v.loc[2] + loc[2]);
+                let h = v.loc[1] + loc[1];
+                let Some(candidates) = buckets.get(&bucket(p.0, p.1)) else {continue;};
+
+                let mut new_h = h;
+                let mut mask: f32 = 0.0;
+                for i in candidates.iter(){
+                    let (dist, width, bed) = segments[*i].closest(&p);
+                    if dist >= width || width <= 0.0 {
+                        continue;
+                    }
+                    let weight = self.easing.apply(1.0 - dist/width);
+                    mask = mask.max(weight);
+                    if bed < h {
+                        new_h = new_h.min(h + (bed - h)*weight);
+                    }
+                }
+                v.loc[1] = new_h - loc[1];
+                if self.paint && mask > 0.0 {
+                    for i in 0..4 {
+                        v.clr[i] += (self.color[i] - v.clr[i])*mask;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::render::mesh::VertexAttributeValues;
+use bevy::utils::BoxedFuture;
+use serde::{Serialize, Deserialize};
+use serde_json::{Value, json};
+use std::io::Write;
+use std::path::Path;
+
+use super::binary::{BinaryOptions, load_binary, write_binary};
+use super::flow::FlowData;
+use super::layers::ModifierLayer;
+use super::planes::{PlaneData, Planes, plane_mesh};
+use super::version::{Migration, SaveError, field, items, migrate};
+use super::vertex::Vertex;
+
+// migrations of the save file, see core::version
+pub const PLANES_MIGRATIONS: [Migration; 1] = [planes_v1];
+pub const PLANES_VERSION: u32 = PLANES_MIGRATIONS.len() as u32;
+
+// version 0 saves are a bare list of planes
+fn planes_v1(value: Value) -> Result<Value, SaveError> {
+    match value {
+        Value::Array(planes) => {Ok(json!({"planes": planes}))}
+        other => {Ok(other)}
+    }
+}
+
+#[derive(Serialize)]
+struct PlanesFile<'a> {
+    version: u32,
+    planes:  &'a Vec<SavePlaneData>
+}
+
+// reads save file of any version
+pub fn load_planes(bytes: &[u8]) -> Result<Vec<SavePlaneData>, SaveError> {
+    let value: Value = serde_json::from_slice(bytes).map_err(|e| SaveError::Json(e.to_string()))?;
+    let value = migrate(value, &PLANES_MIGRATIONS)?;
+    let Some(Value::Array(planes)) = value.get("planes") else {
+        return Err(SaveError::Field{path: "planes".to_string(), message: "missing list of planes".to_string()});
+    };
+    return planes.iter().enumerate().map(|(i, plane)| {
+        let path = format!("planes[{}]", i);
+        let spd = plane_from_value(plane, &path)?;
+        spd.check_vertex(&path)?;
+        Ok(spd)
+    }).collect();
+}
+
+fn plane_from_value(value: &Value, path: &str) -> Result<SavePlaneData, SaveError> {
+    if let Ok(spd) = SavePlaneData::deserialize(value) {
+        return Ok(spd);
+    }
+    // narrow the error down to the broken part
+    if let Some(plane) = value.get("plane") {
+        items::<ModifierLayer>(plane.get("layers"), &format!("{}.plane.layers", path))?;
+        field::<PlaneData>(plane, &format!("{}.plane", path))?;
+    }
+    items::<Vertex>(value.get("vertex"), &format!("{}.vertex", path))?;
+    return field::<SavePlaneData>(value, path);
+}
+
+// Plane saved in given version of the save file, upgraded and checked like the planes of json saves.
+// Used by binary saves, which keep the plane as json next to the vertex data
+pub fn load_plane(value: Value, version: u32, path: &str) -> Result<SavePlaneData, SaveError> {
+    let value = migrate(json!({"version": version, "planes": [value]}), &PLANES_MIGRATIONS)?;
+    let Some(plane) = value.get("planes").and_then(|planes| planes.get(0)) else {
+        return Err(SaveError::Field{path: path.to_string(), message: "plane was dropped by migration".to_string()});
+    };
+    return plane_from_value(plane, path);
+}
+
+// writes save file in the latest version
+pub fn write_planes<W: Write>(writer: W, planes: &Vec<SavePlaneData>) -> Result<(), SaveError> {
+    serde_json::to_writer(writer, &PlanesFile{version: PLANES_VERSION, planes}).map_err(|e| SaveError::Io(e.to_string()))
+}
+
+// save format picked by file extension: .mtb is binary (see core::binary), anything else json
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaveFormat {
+    Json,
+    Binary(BinaryOptions)
+}
+
+impl SaveFormat {
+    pub fn from_path(path: &Path, options: BinaryOptions) -> SaveFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("mtb") => {SaveFormat::Binary(options)}
+            _           => {SaveFormat::Json}
+        }
+    }
+
+    pub fn load(&self, bytes: &[u8]) -> Result<Vec<SavePlaneData>, SaveError> {
+        match self {
+            SaveFormat::Json      => {load_planes(bytes)}
+            SaveFormat::Binary(_) => {load_binary(bytes)}
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: W, planes: &Vec<SavePlaneData>) -> Result<(), SaveError> {
+        match self {
+            SaveFormat::Json            => {write_planes(writer, planes)}
+            SaveFormat::Binary(options) => {write_binary(writer, planes, *options)}
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavePlaneData {
+    pub plane:        PlaneData,
+    pub vertex:       Vec<Vertex>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow:         Option<FlowData>    // exported flow analysis, see core::flow
+}
+impl SavePlaneData {
+    pub fn from_pd(pd: &PlaneData) -> Self {
+        SavePlaneData{plane: pd.clone(), vertex: Vec::new(), flow: None}
+    }
+
+    // number of vertices in the plane grid
+    pub fn vertex_count(&self) -> usize {
+        (self.plane.subdivisions[0] as usize + 2)*(self.plane.subdivisions[1] as usize + 2)
+    }
+
+    // saved vertex indices have to point into the plane grid
+    pub fn check_vertex(&self, path: &str) -> Result<(), SaveError> {
+        let count = self.vertex_count();
+        for (k, vertex) in self.vertex.iter().enumerate(){
+            if vertex.index >= count {
+                return Err(SaveError::Field{path: format!("{}.vertex[{}]", path, k),
+                                            message: format!("index {} out of range for {} vertices", vertex.index, count)});
+            }
+        }
+        return Ok(());
+    }
+
+    // plane mesh with saved vertex positions and colors, vertices outside of the grid are skipped
+    pub fn mesh(&self) -> Mesh {
+        let mut mesh = plane_mesh(&self.plane.subdivisions, &self.plane.dims);
+
+        // Adjust vertices:
+        let mut v_pos: Vec<[f32; 3]> = mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap().to_vec();
+        let mut v_clr: Vec<[f32; 4]> = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(vcolors)) => {vcolors.to_vec()}
+            _ => {vec![[1.0, 1.0, 1.0, 1.0]; v_pos.len()]}
+        };
+
+        for vertex in self.vertex.iter(){
+            if let Some(loc) = v_pos.get_mut(vertex.index) {
+                *loc = vertex.loc;
+            }
+            if let Some(clr) = v_clr.get_mut(vertex.index) {
+                *clr = vertex.clr;
+            }
+        }
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, v_clr);
+
+        return mesh;
+    }
+}
+
+
+// Loads save file (any version, json or binary) as Planes asset. Json saves use the compound
+// .mtb.json extension, so other json assets are not picked up as saves
+#[derive(Default)]
+pub struct PlanesLoader;
+
+impl AssetLoader for PlanesLoader {
+    fn load<'a>(&'a self,
+                bytes:          &'a [u8],
+                load_context:   &'a mut LoadContext) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let format = SaveFormat::from_path(load_context.path(), BinaryOptions::default());
+            let planes = format.load(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(Planes{planes}));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mtb.json", "mtb"]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_plane(subdivisions: [u32; 2]) -> SavePlaneData {
+        let mut spd = SavePlaneData::from_pd(&PlaneData::new());
+        spd.plane.subdivisions = subdivisions;
+        spd.vertex = (0..spd.vertex_count()).map(|index| Vertex::new(index, &[0.0, index as f32, 0.0], &[1.0, 1.0, 1.0, 1.0])).collect();
+        return spd;
+    }
+
+    #[test]
+    fn version_0_save_is_migrated() {
+        let bytes = include_bytes!("../../assets/saves/test.mtb.json");
+        let planes = load_planes(bytes).unwrap();
+        assert_eq!(planes.len(), 1);
+        assert_eq!(planes[0].vertex.len(), planes[0].vertex_count());
+
+        // written again in the latest version, with the same content
+        let mut written: Vec<u8> = Vec::new();
+        write_planes(&mut written, &planes).unwrap();
+        let value: Value = serde_json::from_slice(&written).unwrap();
+        assert_eq!(value["version"], json!(PLANES_VERSION));
+        let reloaded = load_planes(&written).unwrap();
+        assert_eq!(reloaded[0].plane, planes[0].plane);
+        assert!(reloaded[0].vertex.iter().zip(planes[0].vertex.iter()).all(|(a, b)| a.loc == b.loc && a.clr == b.clr));
+    }
+
+    #[test]
+    fn newer_or_broken_save_is_rejected() {
+        assert!(matches!(load_planes(br#"{"version": 99, "planes": []}"#), Err(SaveError::Version{found: 99, ..})));
+        assert!(matches!(load_planes(br#"{"version": 1, "planes": [{"plane": {"label": "a"}, "vertex": "no"}]}"#),
+                         Err(SaveError::Field{..})));
+        assert!(matches!(load_planes(br#"{"version": 1}"#), Err(SaveError::Field{..})));
+        for broken in [&br#"{"version": "1", "planes": []}"#[..], br#"{"version": -1, "planes": []}"#,
+                       br#"{"version": 4294967297, "planes": []}"#, br#"{"version": 1.5, "planes": []}"#] {
+            match load_planes(broken) {
+                Err(SaveError::Field{path, ..}) => {assert_eq!(path, "version");}
+                other => {panic!("expected version error, got {:?}", other.map(|v| v.len()));}
+            }
+        }
+
+        let mut bytes: Vec<u8> = Vec::new();
+        write_planes(&mut bytes, &vec![grid_plane([2, 2])]).unwrap();
+        assert!(matches!(load_planes(&bytes[..bytes.len()/2]), Err(SaveError::Json(_))));
+    }
+
+    #[test]
+    fn out_of_range_vertex_is_rejected() {
+        let mut spd = grid_plane([2, 2]);
+        spd.vertex[3].index = 16;
+        let mut bytes: Vec<u8> = Vec::new();
+        write_planes(&mut bytes, &vec![spd.clone()]).unwrap();
+
+        match load_planes(&bytes) {
+            Err(SaveError::Field{path, ..}) => {assert_eq!(path, "planes[0].vertex[3]");}
+            other => {panic!("expected field error, got {:?}", other.map(|v| v.len()));}
+        }
+        // mesh of a plane that was not checked skips the vertex instead of panicking
+        assert_eq!(spd.mesh().count_vertices(), 16);
+    }
+}
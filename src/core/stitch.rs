@@ -0,0 +1,253 @@
+use bevy::utils::HashMap;
+
+use super::planes::PlaneData;
+use super::save::SavePlaneData;
+
+// Welds borders of planes placed edge to edge, so multi-plane terrains have no gaps.
+// Border vertices of all planes are first grouped by world position (like compute_seamless_normals),
+// each group shared by more than one plane gets the average of its vertices, so corners where
+// three or four planes meet end up with one value. Then shared edges are found from plane AABBs and
+// on each edge the border vertices of the finer plane without a match on the coarser one are
+// interpolated along the coarser border. That removes T-junction cracks when subdivisions differ.
+
+const EDGE_EPSILON: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    MinX,
+    MaxX,
+    MinZ,
+    MaxZ
+}
+
+impl Side {
+    fn opposite(&self) -> Side {
+        match self {
+            Side::MinX => {Side::MaxX}
+            Side::MaxX => {Side::MinX}
+            Side::MinZ => {Side::MaxZ}
+            Side::MaxZ => {Side::MinZ}
+        }
+    }
+}
+
+// point on the border: position along the edge, world height, color
+#[derive(Debug, Clone, Copy)]
+struct BorderPoint {
+    index:  usize,
+    t:      f32,
+    height: f32,
+    clr:    [f32; 4]
+}
+
+// vertex indices on given side of the plane grid
+fn border_indices(pd: &PlaneData, side: Side) -> Vec<usize> {
+    let rows = (pd.subdivisions[0] + 2) as usize; // along z
+    let cols = (pd.subdivisions[1] + 2) as usize; // along x
+    match side {
+        Side::MinX => {(0..rows).map(|row| row*cols).collect()}
+        Side::MaxX => {(0..rows).map(|row| row*cols + cols - 1).collect()}
+        Side::MinZ => {(0..cols).collect()}
+        Side::MaxZ => {(0..cols).map(|col| (rows-1)*cols + col).collect()}
+    }
+}
+
+fn border_points(spd: &SavePlaneData, side: Side) -> Vec<BorderPoint> {
+    let mut points: Vec<BorderPoint> = Vec::new();
+    for index in border_indices(&spd.plane, side).iter(){
+        let Some(v) = spd.vertex.get(*index) else {continue;};
+        let t = match side {
+            Side::MinX | Side::MaxX => {v.loc[2] + spd.plane.loc[2]}
+            Side::MinZ | Side::MaxZ => {v.loc[0] + spd.plane.loc[0]}
+        };
+        points.push(BorderPoint{index: *index, t, height: v.loc[1] + spd.plane.loc[1], clr: v.clr});
+    }
+    points.sort_by(|a, b| a.t.total_cmp(&b.t));
+    return points;
+}
+
+// height and color along the border polyline, None outside of it
+fn sample(points: &[BorderPoint], t: f32) -> Option<(f32, [f32; 4])> {
+    let first = points.first()?;
+    let last = points.last()?;
+    if t < first.t - EDGE_EPSILON || t > last.t + EDGE_EPSILON {
+        return None;
+    }
+    for pair in points.windows(2){
+        let (a, b) = (pair[0], pair[1]);
+        if t > b.t + EDGE_EPSILON {
+            continue;
+        }
+        let span = b.t - a.t;
+        let s = match span > EDGE_EPSILON {
+            true  => {((t - a.t)/span).clamp(0.0, 1.0)}
+            false => {0.0}
+        };
+        let clr: [f32; 4] = std::array::from_fn(|i| a.clr[i] + s*(b.clr[i] - a.clr[i]));
+        return Some((a.height + s*(b.height - a.height), clr));
+    }
+    return Some((first.height, first.clr)); // single point border
+}
+
+// side of plane a touching plane b, if they share an edge
+fn shared_side(a: &PlaneData, b: &PlaneData) -> Option<Side> {
+    let aa = a.get_aabb();
+    let bb = b.get_aabb();
+    let overlap_x = aa.min_x.max(bb.min_x) < aa.max_x.min(bb.max_x) - EDGE_EPSILON;
+    let overlap_z = aa.min_z.max(bb.min_z) < aa.max_z.min(bb.max_z) - EDGE_EPSILON;
+
+    if overlap_z && (aa.max_x - bb.min_x).abs() < EDGE_EPSILON {
+        return Some(Side::MaxX);
+    }
+    if overlap_z && (aa.min_x - bb.max_x).abs() < EDGE_EPSILON {
+        return Some(Side::MinX);
+    }
+    if overlap_x && (aa.max_z - bb.min_z).abs() < EDGE_EPSILON {
+        return Some(Side::MaxZ);
+    }
+    if overlap_x && (aa.min_z - bb.max_z).abs() < EDGE_EPSILON {
+        return Some(Side::MinZ);
+    }
+    return None;
+}
+
+fn set_point(spd: &mut SavePlaneData, index: usize, height: f32, clr: [f32; 4]) {
+    let plane_y = spd.plane.loc[1];
+    if let Some(v) = spd.vertex.get_mut(index) {
+        v.loc[1] = height - plane_y;
+        v.clr = clr;
+    }
+}
+
+type PositionKey = (i64, i64);
+
+fn position_key(spd: &SavePlaneData, index: usize) -> Option<PositionKey> {
+    let v = spd.vertex.get(index)?;
+    return Some((((v.loc[0] + spd.plane.loc[0])*100.0).round() as i64, ((v.loc[2] + spd.plane.loc[2])*100.0).round() as i64));
+}
+
+// border vertices of all planes by world position, (plane, vertex index)
+fn border_groups(planes: &[SavePlaneData]) -> HashMap<PositionKey, Vec<(usize, usize)>> {
+    let mut groups: HashMap<PositionKey, Vec<(usize, usize)>> = HashMap::new();
+    for (plane, spd) in planes.iter().enumerate(){
+        for side in [Side::MinX, Side::MaxX, Side::MinZ, Side::MaxZ]{
+            for index in border_indices(&spd.plane, side).into_iter(){
+                let Some(key) = position_key(spd, index) else {continue;};
+                let refs = groups.entry(key).or_insert(Vec::new());
+                if !refs.contains(&(plane, index)) { // corners are on two sides
+                    refs.push((plane, index));
+                }
+            }
+        }
+    }
+    groups.retain(|_key, refs| refs.iter().any(|(plane, _index)| *plane != refs[0].0));
+    return groups;
+}
+
+// Vertices of each plane have to be ordered by index. Returns number of stitched edges.
+pub fn stitch_planes(planes: &mut [SavePlaneData]) -> usize {
+    let groups = border_groups(planes);
+    for refs in groups.values(){
+        let mut height: f32 = 0.0;
+        let mut clr = [0.0; 4];
+        for (plane, index) in refs.iter(){
+            let spd = &planes[*plane];
+            let v = &spd.vertex[*index];
+            height += v.loc[1] + spd.plane.loc[1];
+            for (c, v_c) in clr.iter_mut().zip(v.clr.iter()){
+                *c += v_c;
+            }
+        }
+        let n = refs.len() as f32;
+        for (plane, index) in refs.iter(){
+            set_point(&mut planes[*plane], *index, height/n, clr.map(|c| c/n));
+        }
+    }
+
+    let mut count: usize = 0;
+    for a in 0..planes.len(){
+        for b in (a+1)..planes.len(){
+            let Some(side) = shared_side(&planes[a].plane, &planes[b].plane) else {continue;};
+            stitch_edge(planes, &groups, a, side, b, side.opposite());
+            count += 1;
+        }
+    }
+    return count;
+}
+
+// interpolates vertices of the finer side that have no match on the coarser side
+fn stitch_edge(planes:   &mut [SavePlaneData],
+               groups:   &HashMap<PositionKey, Vec<(usize, usize)>>,
+               a:        usize,
+               side_a:   Side,
+               b:        usize,
+               side_b:   Side) {
+    let points_a = border_points(&planes[a], side_a);
+    let points_b = border_points(&planes[b], side_b);
+
+    // coarser side leads
+    let (lead, lead_points, follow, follow_points) = match points_a.len() <= points_b.len() {
+        true  => {(a, points_a, b, points_b)}
+        false => {(b, points_b, a, points_a)}
+    };
+
+    for p in follow_points.iter(){
+        let Some(key) = position_key(&planes[follow], p.index) else {continue;};
+        let refs = groups.get(&key);
+        if refs.is_some_and(|refs| refs.iter().any(|(plane, _index)| *plane == lead)) {
+            continue; // already welded
+        }
+        let Some((height, clr)) = sample(&lead_points, p.t) else {continue;};
+        // other planes sharing the vertex get the same value
+        match refs {
+            Some(refs) => {
+                for (plane, index) in refs.iter(){
+                    set_point(&mut planes[*plane], *index, height, clr);
+                }
+            }
+            None => {set_point(&mut planes[follow], p.index, height, clr);}
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat_plane(loc: [f32; 3], subdivisions: [u32; 2], height: f32) -> SavePlaneData {
+        let mut spd = SavePlaneData::from_pd(&PlaneData::new());
+        spd.plane.loc = loc;
+        spd.plane.subdivisions = subdivisions;
+        spd.vertex = spd.plane.base_vertices();
+        spd.vertex.iter_mut().for_each(|v| v.loc[1] = height);
+        return spd;
+    }
+
+    fn corner_heights(planes: &[SavePlaneData]) -> Vec<f32> {
+        let mut heights: Vec<f32> = Vec::new();
+        for spd in planes.iter(){
+            for v in spd.vertex.iter(){
+                if (v.loc[0] + spd.plane.loc[0]).abs() < 0.01 && (v.loc[2] + spd.plane.loc[2]).abs() < 0.01 {
+                    heights.push(v.loc[1] + spd.plane.loc[1]);
+                }
+            }
+        }
+        return heights;
+    }
+
+    #[test]
+    fn four_planes_share_one_corner() {
+        let mut planes = vec![flat_plane([-100.0, 0.0, -100.0], [2, 2], 1.0),
+                              flat_plane([ 100.0, 0.0, -100.0], [2, 2], 2.0),
+                              flat_plane([-100.0, 0.0,  100.0], [4, 4], 3.0),
+                              flat_plane([ 100.0, 0.0,  100.0], [2, 2], 6.0)];
+        assert_eq!(stitch_planes(&mut planes), 4);
+
+        let heights = corner_heights(&planes);
+        assert_eq!(heights.len(), 4);
+        for h in heights.iter(){
+            assert!((h - 3.0).abs() < 1e-5, "corner heights {:?}", heights);
+        }
+    }
+}
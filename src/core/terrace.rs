@@ -1,16 +1,27 @@
 
-use bevy_egui::egui::{Ui, DragValue};
-use bevy::prelude::ResMut;
 use serde::{Serialize, Deserialize};
-use crate::editor::mtb_ui::ModResources;
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Terrace {
   pub from:   f32,
   pub to:     f32,
   pub value:  f32
 }
 
+impl Default for Terrace {
+  fn default() -> Self {
+    Terrace::new()
+  }
+}
+
 
 impl Terrace {
   pub fn new() -> Self {
@@ -22,18 +33,26 @@ impl Terrace {
     } else {
       return v;
     }
-  } 
-  pub fn ui(ui: &mut Ui, mod_res: &mut ResMut<ModResources>) {
-    ui.label("Terrace");
-    ui.separator();
-
-    ui.columns(2, |columns| {
-        columns[1].label("From");
-        columns[0].add(DragValue::new(&mut mod_res.terrace.from).speed(1.0));
-        columns[1].label("To");
-        columns[0].add(DragValue::new(&mut mod_res.terrace.to).speed(1.0));
-        columns[1].label("Value");
-        columns[0].add(DragValue::new(&mut mod_res.terrace.value).speed(1.0));
-    });
   }
-}
\ No newline at end of file
+
+}
+
+
+impl TerrainModifier for Terrace {
+    fn name(&self) -> &'static str {
+        "Terrace"
+    }
+    fn apply(&self, _pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.loc[1] = Terrace::apply(self, v.loc[1]);
+        }
+    }
+    fn is_height(&self) -> bool {
+        true
+    }
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
+    }
+}
@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+
+use super::planes::{PlaneData, Planes, PlanesAsset, TerrainPlane, match_planes};
+use super::save::{SavePlaneData, PlanesLoader};
+use super::utils::compute_seamless_normals;
+
+// Lightweight plugin for games: loads save file through asset server and spawns plain terrain meshes.
+// Usage: add the plugin and insert PlanesAsset(asset_server.load("saves/my_terrain.mtb.json"))
+// (or a binary .mtb save)
+// With asset watching enabled, changes to the save file are hot-reloaded. Planes are matched by label.
+
+pub struct MTBTerrainPlugin;
+
+impl Plugin for MTBTerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_asset::<Planes>()
+        .init_asset_loader::<PlanesLoader>()
+        .add_systems(Update, sync_terrain.run_if(resource_exists::<PlanesAsset>()))
+        .add_systems(Last, update_terrain_normals)
+        ;
+    }
+}
+
+pub fn sync_terrain(mut commands:     Commands,
+                    mut asset_events: EventReader<AssetEvent<Planes>>,
+                    planes_asset:     Res<PlanesAsset>,
+                    planes:           Res<Assets<Planes>>,
+                    mut terrain:      Query<(Entity, &PlaneData, &Handle<Mesh>, &mut Transform), With<TerrainPlane>>,
+                    mut meshes:       ResMut<Assets<Mesh>>,
+                    mut materials:    ResMut<Assets<StandardMaterial>>){
+
+    for ev in asset_events.iter(){
+        let handle = match ev {
+            AssetEvent::Created{handle}  => {handle}
+            AssetEvent::Modified{handle} => {handle}
+            AssetEvent::Removed{..}      => {continue;}
+        };
+        if *handle != planes_asset.0 {
+            continue; // not our terrain
+        }
+        let Some(terrain_data) = planes.get(handle) else {continue;};
+
+        // planes sharing a label are matched in query order
+        let existing: Vec<(String, Entity)> = terrain.iter().map(|(entity, pd, _handle_mesh, _transform)| (pd.label.clone(), entity)).collect();
+        let (matched, left) = match_planes(&existing, &terrain_data.planes);
+
+        for (spd, entity) in terrain_data.planes.iter().zip(matched){
+            if let Some(entity) = entity {
+                // keep the entity, replace its data
+                let Ok((_entity, _pd, handle_mesh, mut transform)) = terrain.get_mut(entity) else {continue;};
+                if let Some(mesh) = meshes.get_mut(handle_mesh){
+                    *mesh = spd.mesh();
+                }
+                transform.translation = spd.plane.loc.into();
+                commands.entity(entity).insert(spd.plane.clone());
+            } else {
+                spawn_terrain_plane(spd, &mut commands, &mut meshes, &mut materials);
+            }
+        }
+
+        // planes no longer in the save file
+        for entity in left.iter(){
+            commands.entity(*entity).despawn_recursive();
+        }
+
+        info!("Synced terrain with {} planes", terrain_data.planes.len());
+    }
+}
+
+pub fn spawn_terrain_plane(spd:        &SavePlaneData,
+                           commands:   &mut Commands,
+                           meshes:     &mut ResMut<Assets<Mesh>>,
+                           materials:  &mut ResMut<Assets<StandardMaterial>>) -> Entity {
+
+    let mesh = spd.mesh();
+
+    let entity = commands.spawn((PbrBundle {
+        material: materials.add(StandardMaterial{..default()}),
+        mesh: meshes.add(mesh),
+        transform: Transform::from_translation(spd.plane.loc.into()),
+        ..default()
+        },
+        TerrainPlane,
+        spd.plane.clone()
+    )).id();
+
+    return entity;
+}
+
+// Recomputes normals of all terrain planes whenever any of their meshes is created or changed, or a plane is moved or removed.
+// Normals are only written if they differ, so writing them doesn't trigger another update.
+pub fn update_terrain_normals(mut mesh_events: EventReader<AssetEvent<Mesh>>,
+                              planes:          Query<(&Handle<Mesh>, &Transform), With<TerrainPlane>>,
+                              moved:           Query<(), (With<TerrainPlane>, Changed<Transform>)>,
+                              mut removed:     RemovedComponents<TerrainPlane>,
+                              mut meshes:      ResMut<Assets<Mesh>>){
+
+    // borders to neighbours could have changed
+    let mut changed: bool = !moved.is_empty() || removed.iter().count() > 0;
+    for ev in mesh_events.iter(){
+        match ev {
+            AssetEvent::Created{handle} | AssetEvent::Modified{handle} => {
+                if planes.iter().any(|(handle_mesh, _tr)| handle_mesh == handle) {
+                    changed = true;
+                }
+            }
+            AssetEvent::Removed{..} => {}
+        }
+    }
+    if !changed {
+        return;
+    }
+
+    let mut handles: Vec<Handle<Mesh>> = Vec::new();
+    let mut v_meshes: Vec<(Vec3, &Mesh)> = Vec::new();
+    for (handle_mesh, tr) in planes.iter(){
+        if let Some(mesh) = meshes.get(handle_mesh){
+            handles.push(handle_mesh.clone_weak());
+            v_meshes.push((tr.translation, mesh));
+        }
+    }
+
+    let normals = compute_seamless_normals(&v_meshes);
+    let mut updates: Vec<(Handle<Mesh>, Vec<[f32; 3]>)> = Vec::new();
+    for ((handle_mesh, (_offset, mesh)), mesh_normals) in handles.into_iter().zip(v_meshes.iter()).zip(normals){
+        let current = mesh.attribute(Mesh::ATTRIBUTE_NORMAL).and_then(|attr| attr.as_float3());
+        if current != Some(&mesh_normals[..]) {
+            updates.push((handle_mesh, mesh_normals));
+        }
+    }
+
+    for (handle_mesh, mesh_normals) in updates.into_iter(){
+        if let Some(mesh) = meshes.get_mut(&handle_mesh){
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, mesh_normals);
+        }
+    }
+}
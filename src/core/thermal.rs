@@ -0,0 +1,90 @@
+use serde::{Serialize, Deserialize};
+
+use super::grid::HeightGrid;
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
+
+// Thermal erosion: wherever slope to a neighbour is steeper than talus angle,
+// material slides down to the lower neighbours. Smooths knife edges left by noises and terraces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalErosion {
+    pub talus:      f32,    // angle in degrees
+    pub iterations: usize,
+    pub strength:   f32     // fraction of the excess moved each iteration
+}
+
+impl Default for ThermalErosion {
+    fn default() -> Self {
+        ThermalErosion::new()
+    }
+}
+
+impl ThermalErosion {
+    pub fn new() -> Self {
+        ThermalErosion{talus: 35.0, iterations: 20, strength: 0.5}
+    }
+
+    pub fn erode(&self, grid: &mut HeightGrid) {
+        let talus = self.talus.clamp(0.0, 89.9).to_radians().tan();
+        let cells: Vec<usize> = (0..grid.heights.len()).filter(|index| grid.mask[*index]).collect();
+        let neighbours: Vec<Vec<(usize, f32)>> = cells.iter().map(|index| grid.neighbours(*index)
+                                                                             .into_iter()
+                                                                             .filter(|(n, _dist)| grid.mask[*n])
+                                                                             .collect()).collect();
+
+        for _ in 0..self.iterations {
+            let mut deltas: Vec<f32> = vec![0.0; grid.heights.len()];
+            for (index, cell_neighbours) in cells.iter().zip(neighbours.iter()){
+                let h = grid.heights[*index];
+
+                // height differences above the talus limit
+                let mut excess: Vec<(usize, f32)> = Vec::new();
+                let mut max_excess: f32 = 0.0;
+                for (n, dist) in cell_neighbours.iter(){
+                    let diff = h - grid.heights[*n] - talus*dist;
+                    if diff > 0.0 {
+                        excess.push((*n, diff));
+                        max_excess = max_excess.max(diff);
+                    }
+                }
+                if excess.is_empty() {
+                    continue;
+                }
+
+                let total: f32 = excess.iter().map(|(_n, diff)| diff).sum();
+                let moved = self.strength*max_excess/2.0;
+                deltas[*index] -= moved;
+                for (n, diff) in excess.iter(){
+                    deltas[*n] += moved*diff/total;
+                }
+            }
+            for (h, d) in grid.heights.iter_mut().zip(deltas.iter()){
+                *h += d;
+            }
+        }
+    }
+}
+
+impl TerrainModifier for ThermalErosion {
+    fn name(&self) -> &'static str {
+        "ThermalErosion"
+    }
+    fn apply(&self, pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        let mut grid = HeightGrid::new(pd, vertices, selection);
+        self.erode(&mut grid);
+        grid.apply(vertices);
+    }
+    fn is_height(&self) -> bool {
+        true
+    }
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
+    }
+}
@@ -1,5 +1,7 @@
 use libm::{fabsf, powf, sqrtf};
 use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::utils::HashMap;
 use std::slice::Iter;
 use serde::{Serialize, Deserialize};
 
@@ -8,7 +10,7 @@ pub enum Axis {
   X,Z
 }
 
-impl<'a> Axis {
+impl Axis {
   pub fn iterator() -> Iter<'static, Axis> {
       static OPTIONS: [Axis; 2] = [
           Axis::X,
@@ -89,6 +91,77 @@ pub fn get_mesh_stats(mesh: &Mesh){
 
 }
 
+// area weighted smooth normals: unnormalized face normals are summed per vertex, so larger triangles weigh more
+pub fn accumulate_face_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<Vec3> {
+  let mut normals: Vec<Vec3> = vec![Vec3::ZERO; positions.len()];
+  for tri in indices.chunks_exact(3){
+    let a = Vec3::from(positions[tri[0] as usize]);
+    let b = Vec3::from(positions[tri[1] as usize]);
+    let c = Vec3::from(positions[tri[2] as usize]);
+    let face_normal = (b - a).cross(c - a);
+    for index in tri.iter(){
+      normals[*index as usize] += face_normal;
+    }
+  }
+  return normals;
+}
+
+pub fn compute_smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+  let normals = accumulate_face_normals(positions, indices);
+  return normals.iter().map(|n| n.try_normalize().unwrap_or(Vec3::Y).to_array()).collect();
+}
+
+pub fn mesh_positions_indices(mesh: &Mesh) -> Option<(Vec<[f32; 3]>, Vec<u32>)> {
+  let v_pos = mesh.attribute(Mesh::ATTRIBUTE_POSITION)?.as_float3()?.to_vec();
+  let indices: Vec<u32> = match mesh.indices() {
+    Some(Indices::U32(indices)) => {indices.clone()}
+    Some(Indices::U16(indices)) => {indices.iter().map(|i| *i as u32).collect()}
+    None => {(0..v_pos.len() as u32).collect()}
+  };
+  return Some((v_pos, indices));
+}
+
+// recomputes normals of the mesh from its current positions
+pub fn update_normals(mesh: &mut Mesh) {
+  let Some((v_pos, indices)) = mesh_positions_indices(mesh) else {return;};
+  let normals = compute_smooth_normals(&v_pos, &indices);
+  mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+}
+
+// Smooth normals of multiple meshes placed at given offsets.
+// Vertices of different meshes at the same world xz (shared borders) get the same normal, so seams are not lit differently.
+pub fn compute_seamless_normals(meshes: &Vec<(Vec3, &Mesh)>) -> Vec<Vec<[f32; 3]>> {
+  let mut accumulated: Vec<Vec<Vec3>> = Vec::with_capacity(meshes.len());
+  let mut shared: HashMap<(i64, i64), Vec<(usize, usize)>> = HashMap::new();
+
+  for (mesh_index, (offset, mesh)) in meshes.iter().enumerate(){
+    let Some((v_pos, indices)) = mesh_positions_indices(mesh) else {
+      accumulated.push(Vec::new());
+      continue;
+    };
+    for (index, pos) in v_pos.iter().enumerate(){
+      let key = (((pos[0] + offset.x)*100.0).round() as i64, ((pos[2] + offset.z)*100.0).round() as i64);
+      shared.entry(key).or_default().push((mesh_index, index));
+    }
+    accumulated.push(accumulate_face_normals(&v_pos, &indices));
+  }
+
+  let mut normals: Vec<Vec<Vec3>> = accumulated.clone();
+  for refs in shared.values(){
+    if refs.iter().all(|(mesh_index, _index)| *mesh_index == refs[0].0) {
+      continue; // not on a shared border
+    }
+    let sum: Vec3 = refs.iter().map(|(mesh_index, index)| accumulated[*mesh_index][*index]).sum();
+    for (mesh_index, index) in refs.iter(){
+      normals[*mesh_index][*index] = sum;
+    }
+  }
+
+  return normals.iter().map(|mesh_normals| mesh_normals.iter()
+                                                       .map(|n| n.try_normalize().unwrap_or(Vec3::Y).to_array())
+                                                       .collect()).collect();
+}
+
 pub fn get_distance_manhattan(xz: &(f32, f32), target: &(f32, f32)) -> f32 {
   return fabsf(target.0 - xz.0) + fabsf(target.1 - xz.1);
 }
@@ -1,12 +1,16 @@
-use bevy_egui::{egui, egui::Ui};
-use bevy::prelude::ResMut;
 use serde::{Deserialize,Serialize};
 
-use crate::editor::mtb_ui::ModResources;
 
 use super::easings::Easings;
 use super::utils::{Axis, get_distance_euclidean};
 use std::slice::Iter;
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum ValueType {
@@ -24,7 +28,7 @@ impl ValueType {
     }
 }
 
-impl<'a> ValueType {
+impl ValueType {
     pub fn iterator() -> Iter<'static, ValueType> {
         static OPTIONS: [ValueType; 3] = [
             ValueType::Value,
@@ -44,7 +48,7 @@ pub enum ValueScaling {
     DistanceAxisRev
 }
 
-impl<'a> ValueScaling {
+impl ValueScaling {
     pub fn iterator() -> Iter<'static, ValueScaling> {
         static OPTIONS: [ValueScaling; 5] = [
             ValueScaling::None,
@@ -60,6 +64,7 @@ impl<'a> ValueScaling {
 
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
 pub struct Value {
     pub _value:     f32,
     pub _scale:     f32,
@@ -71,6 +76,12 @@ pub struct Value {
     pub scaling:    ValueScaling,
 }
 
+impl Default for Value {
+    fn default() -> Self {
+        Value::new()
+    }
+}
+
 impl Value {    
 
     pub fn new() -> Self {
@@ -89,21 +100,19 @@ impl Value {
 
         match self.scaling {
             ValueScaling::DistanceAxisRev => {
-                let dist: f32;
-                match self._axis {
-                    Axis::X => {dist = get_distance_euclidean(&(pos[0], pos[2]), &(self._axis_v, pos[2]));}
-                    Axis::Z => {dist = get_distance_euclidean(&(pos[0], pos[2]), &(pos[0], self._axis_v));}
-                }
+                let dist: f32 = match self._axis {
+                    Axis::X => {get_distance_euclidean(&(pos[0], pos[2]), &(self._axis_v, pos[2]))}
+                    Axis::Z => {get_distance_euclidean(&(pos[0], pos[2]), &(pos[0], self._axis_v))}
+                };
                 let mut scale: f32 = 1.0 - 1.0/dist;
                 scale = self.easing.apply(scale);
                 return v2*scale;
             }
             ValueScaling::DistanceAxis => {
-                let dist: f32;
-                match self._axis {
-                    Axis::X => {dist = get_distance_euclidean(&(pos[0], pos[2]), &(self._axis_v, pos[2]));}
-                    Axis::Z => {dist = get_distance_euclidean(&(pos[0], pos[2]), &(pos[0], self._axis_v));}
-                }
+                let dist: f32 = match self._axis {
+                    Axis::X => {get_distance_euclidean(&(pos[0], pos[2]), &(self._axis_v, pos[2]))}
+                    Axis::Z => {get_distance_euclidean(&(pos[0], pos[2]), &(pos[0], self._axis_v))}
+                };
                 let mut scale: f32 = 1.0/dist;
                 scale = self.easing.apply(scale);
                 return v2*scale;
@@ -122,63 +131,27 @@ impl Value {
             }
             ValueScaling::None => {return v2;}
         }
-    }   
-
-    pub fn ui(ui: &mut Ui, mod_res: &mut ResMut<ModResources>) {
-        
-        ui.label("Value");
-        ui.add(egui::DragValue::new(&mut mod_res.value._value).speed(0.1));
-        ui.vertical(|ui| {
-          ui.label("Type:");
-          for &p in ValueType::iterator(){
-              if ui.radio_value(&mut mod_res.value.value_type, p, format!("{p:?}")).clicked() {
-                mod_res.value.value_type = p;
-              };
-          }
-        });
-        ui.separator();
-        ui.vertical(|ui| {
-          ui.label("Scaling:");
-          for &p in ValueScaling::iterator(){
-              if ui.radio_value(&mut mod_res.value.scaling, p, format!("{p:?}")).clicked() {
-                mod_res.value.scaling = p;
-              };
-          }
-        });
-
-        match mod_res.value.scaling {
-          ValueScaling::DistanceAxis | ValueScaling::DistanceAxisRev => {
-            ui.vertical(|ui| {
-              ui.label("Axis:");
-              for &p in Axis::iterator(){
-                  if ui.radio_value(&mut mod_res.value._axis, p, format!("{p:?}")).clicked() {
-                    mod_res.value._axis = p;
-                  };
-              }
-              ui.label("Axis Value:");
-              ui.add(egui::DragValue::new(&mut mod_res.value._axis_v).speed(1.0));
-              ui.label("Scale:");
-              ui.add(egui::DragValue::new(&mut mod_res.value._scale).speed(0.1));
-            });
-          }
-          ValueScaling::DistancePoint | ValueScaling::DistancePointRev => {
-            ui.vertical(|ui| {
-              ui.label("Point:");
-              ui.columns(2, |columns| {
-                columns[1].label("X");
-                columns[0].add(egui::DragValue::new(&mut mod_res.value._point.0).speed(1.0));
-                columns[1].label("Y");
-                columns[0].add(egui::DragValue::new(&mut mod_res.value._point.1).speed(1.0));
-              });
-              ui.label("Scale:");
-              ui.add(egui::DragValue::new(&mut mod_res.value._scale).speed(0.1));
-            });
-          }
-          _ => {}
-        }
+    }
 
-        ui.separator();
 
-    }
+}
 
-}
\ No newline at end of file
+
+impl TerrainModifier for Value {
+    fn name(&self) -> &'static str {
+        "Value"
+    }
+    fn apply(&self, _pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.loc[1] = Value::apply(self, &v.loc);
+        }
+    }
+    fn is_height(&self) -> bool {
+        true
+    }
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
+    }
+}
@@ -0,0 +1,92 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+// Save files carry a schema version. Older saves are upgraded on load by a chain of migrations
+// over the raw json, one step per version, and only then deserialized. Files without a version
+// are version 0.
+
+// migration from version i to i+1 is at index i
+pub type Migration = fn(Value) -> Result<Value, SaveError>;
+
+// check of one field, gets the value and its path
+pub type FieldCheck = fn(&Value, &str) -> Result<(), SaveError>;
+
+#[derive(Debug, Clone)]
+pub enum SaveError {
+    Io(String),
+    Json(String),                           // not a json file
+    Field{path: String, message: String},   // json does not match the save structs
+    Version{found: u32, supported: u32}     // saved by newer version of the crate
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(message)              => {write!(f, "{}", message)}
+            SaveError::Json(message)            => {write!(f, "Invalid json: {}", message)}
+            SaveError::Field{path, message}     => {write!(f, "{}: {}", path, message)}
+            SaveError::Version{found, supported} => {
+                write!(f, "Save version {} is newer than supported version {}", found, supported)
+            }
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+// missing version is 0, anything else than an integer that fits u32 is an error
+pub fn version(value: &Value) -> Result<u32, SaveError> {
+    let Some(found) = value.get("version") else {return Ok(0);};
+    return found.as_u64().and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| SaveError::Field{path: "version".to_string(), message: format!("invalid version {}", found)});
+}
+
+// runs migrations from the version of the value up to the latest
+pub fn migrate(mut value: Value, migrations: &[Migration]) -> Result<Value, SaveError> {
+    let latest = migrations.len() as u32;
+    let found = version(&value)?;
+    if found > latest {
+        return Err(SaveError::Version{found, supported: latest});
+    }
+    for step in migrations[found as usize..].iter(){
+        value = step(value)?;
+    }
+    if let Value::Object(map) = &mut value {
+        map.insert("version".to_string(), Value::from(latest));
+    }
+    return Ok(value);
+}
+
+// deserializes value, errors point at the path of the value
+pub fn field<T: DeserializeOwned>(value: &Value, path: &str) -> Result<T, SaveError> {
+    T::deserialize(value).map_err(|e| SaveError::Field{path: path.to_string(), message: e.to_string()})
+}
+
+// checks items of a list one by one to find the broken one
+pub fn items<T: DeserializeOwned>(value: Option<&Value>, path: &str) -> Result<(), SaveError> {
+    if let Some(Value::Array(list)) = value {
+        for (i, item) in list.iter().enumerate(){
+            field::<T>(item, &format!("{}[{}]", path, i))?;
+        }
+    }
+    return Ok(());
+}
+
+// checks fields of an object one by one, for each (key, check)
+pub fn fields(value: &Value, path: &str, checks: &[(&str, FieldCheck)]) -> Result<(), SaveError> {
+    for (key, check) in checks.iter(){
+        if let Some(v) = value.get(key) {
+            check(v, &format!("{}.{}", path, key))?;
+        }
+    }
+    return Ok(());
+}
+
+pub fn as_object(value: Value, path: &str) -> Result<Map<String, Value>, SaveError> {
+    match value {
+        Value::Object(map) => {Ok(map)}
+        _ => {Err(SaveError::Field{path: path.to_string(), message: "expected an object".to_string()})}
+    }
+}
@@ -1,61 +1,93 @@
-
 use serde::{Serialize, Deserialize};
 use libm::atan2f;
+use noise::{NoiseFn, Perlin};
 use rand::prelude::*;
+use std::f32::consts::PI;
+use std::slice::Iter;
 
 use super::easings::Easings;
+use super::modifier::TerrainModifier;
 use super::planes::PlaneData;
 use super::utils::{AABB, get_distance_manhattan};
-
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
+
+// Carves wandering trench, canyon or path between two points or plane edges.
+// Positions are local to the plane.
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct TargetWanderNoiseData {
+#[serde(default)]
+pub struct TargetWanderNoise {
     pub wander:               TargetWanders,
-    pub width:                f32,
-    pub height:               f32,
+    pub carve:                WanderCarve,
+    pub width:                f32,        // distance from the path center affected by carving
+    pub height:               f32,        // depth of the trench
     pub step:                 f32,
     pub max_steps:            u32,
     pub source:               WanderLoc,
     pub target:               WanderLoc,
     pub seed:                 NoiseSeed,
-    pub easing:               Easings
+    pub easing:               Easings     // profile of the cross section, from the edge (0) to the center (1)
 }
 
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct TargetWanderNoise {
-    pub wander:               TargetWanders,
-    pub width:                f32,
-    pub height:               f32,
-    pub step:                 f32,
-    pub max_steps:            u32,
-    pub source:               WanderLoc,
-    pub target:               WanderLoc,
-    pub seed:                 NoiseSeed,
-    pub easing:               Easings
+impl Default for TargetWanderNoise {
+    fn default() -> Self {
+        TargetWanderNoise::new()
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, PartialEq)]
 pub enum NoiseSeed {
     SetSeed(u64),
-    NoSeed
+    NoSeed      // random seed picked when modifier is applied
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, PartialEq)]
 pub enum WanderLoc {
     Point((f32,f32)),
     Edge(Edge)
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, PartialEq)]
+pub enum Edge {
+    X,   // max x
+    NX,  // min x
+    Z,   // max z
+    NZ   // min z
+}
+
+impl Edge {
+    pub fn iterator() -> Iter<'static, Edge> {
+        static EDGE_OPTIONS: [Edge; 4] = [Edge::X, Edge::NX, Edge::Z, Edge::NZ];
+        EDGE_OPTIONS.iter()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, PartialEq)]
+pub enum WanderCarve {
+    Trench,   // lowers terrain along the path
+    Path      // flattens terrain across the path to the height of its center
+}
+
+impl WanderCarve {
+    pub fn iterator() -> Iter<'static, WanderCarve> {
+        static CARVE_OPTIONS: [WanderCarve; 2] = [WanderCarve::Trench, WanderCarve::Path];
+        CARVE_OPTIONS.iter()
+    }
+}
+
 impl WanderLoc {
-    fn get_point(&self, pab: &AABB, seed: NoiseSeed) -> (f32, f32) {
+    fn get_point(&self, pab: &AABB, rng: &mut StdRng) -> (f32, f32) {
         match self {
             WanderLoc::Point(p)   => {return *p;}
             WanderLoc::Edge(edge) =>  {
                 match edge {
-                    Edge::X  => {(pab.max_x, get_random_range(pab.min_z, pab.max_z, seed))}
-                    Edge::NX => {(pab.min_x, get_random_range(pab.min_z, pab.max_z, seed))}
-                    Edge::Z  => {(pab.max_z, get_random_range(pab.min_x, pab.max_x, seed))}
-                    Edge::NZ => {(pab.min_z, get_random_range(pab.min_x, pab.max_x, seed))}
+                    Edge::X  => {(pab.max_x, rng.gen_range(pab.min_z..=pab.max_z))}
+                    Edge::NX => {(pab.min_x, rng.gen_range(pab.min_z..=pab.max_z))}
+                    Edge::Z  => {(rng.gen_range(pab.min_x..=pab.max_x), pab.max_z)}
+                    Edge::NZ => {(rng.gen_range(pab.min_x..=pab.max_x), pab.min_z)}
                 }
             }
         }
@@ -63,146 +95,149 @@ impl WanderLoc {
 }
 
 
-impl TargetWanderNoiseData {
-    pub fn set(&self, pd: &PlaneData) -> TargetWanderNoise {
-        let pab = pd.get_aabb();
-        let mut xz: (f32, f32) = self.source.get_point(&pab, self.seed);
-        let end: (f32, f32) = self.target.get_point(&pab, self.seed);
+impl TargetWanderNoise {
+    pub fn new() -> Self {
+        TargetWanderNoise{wander:    TargetWanders::Emu(60.0),
+                          carve:     WanderCarve::Trench,
+                          width:     20.0,
+                          height:    10.0,
+                          step:      10.0,
+                          max_steps: 200,
+                          source:    WanderLoc::Edge(Edge::NX),
+                          target:    WanderLoc::Edge(Edge::X),
+                          seed:      NoiseSeed::SetSeed(0),
+                          easing:    Easings::SmoothStep}
+    }
+
+    // Generates step points of the path using wandering function
+    pub fn points(&self, pd: &PlaneData) -> Vec<(f32, f32)> {
+        let seed = match self.seed {
+            NoiseSeed::SetSeed(seed) => {seed}
+            NoiseSeed::NoSeed        => {0}  // resolved in prepare
+        };
+        let mut rng = StdRng::seed_from_u64(seed);
+        let perlin = Perlin::new(seed as u32);
+
+        // local plane bounds
+        let pab = AABB{min_x: -pd.dims[0]/2.0, max_x: pd.dims[0]/2.0, min_z: -pd.dims[1]/2.0, max_z: pd.dims[1]/2.0};
+        let mut xz: (f32, f32) = self.source.get_point(&pab, &mut rng);
+        let end: (f32, f32) = self.target.get_point(&pab, &mut rng);
 
-        // Generate step points using wandering function. Step points will be central points of aabb boxes
         let mut points: Vec<(f32, f32)> = vec![xz];
-        let mut i: u32 = 0;
         let mut direction = get_direction(&xz, &end);
-        let mut last_angle: f32 = direction;
         let mut distance = get_distance_manhattan(&xz, &end);
-        let inloop: bool = true;
-        // println!(" WANDERS DEBUG from source: ({:?}) to target ({:?}), direction: {}", xz, end, direction);
-
-        while inloop {
-            i += 1;
-
-            // end conditions
-            if i >= self.max_steps {
-                // println!(" WANDERS DEBUG breaking becasue of max steps. points count: {}", points.len());
-                break;
-            }
 
+        for i in 1..self.max_steps {
             if distance <= self.step {
-                // println!(" WANDERS DEBUG breaking becasue of distance. points count: {}", points.len());
+                points.push(end);
                 break;
             }
 
-            let (w_x, w_z, w_angle) = self.wander.apply(&xz, last_angle, self.step, direction, self.seed, i as u64);
+            let (w_x, w_z) = self.wander.apply(&xz, self.step, direction, &mut rng, &perlin, i);
             points.push((w_x,w_z));
             xz.0 = w_x;
             xz.1 = w_z;
-            last_angle = w_angle;
 
             direction = get_direction(&xz, &end);
             distance = get_distance_manhattan(&xz, &end);
-            
         }
+        return points;
+    }
+}
 
-        // construct aabbs from points
-        let mut aabbs = Vec::new();
-        for p in points.iter(){
-            aabbs.push(AABB::from_point(p, &(self.step*2.0, self.width)));
-        }
+impl TerrainModifier for TargetWanderNoise {
+    fn name(&self) -> &'static str {
+        "Wander"
+    }
 
-        // construct ellipses from points
-        let mut ellipses = Vec::new();
-        for p in points.iter(){
-            ellipses.push(Ellipse{a: self.step*2.0, b: self.width, x: p.0, z: p.1});
+    fn apply(&self, pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        let points = self.points(pd);
+        if points.len() < 2 || self.width <= 0.0 {
+            return;
         }
 
+        // terrain height under each path point, used by Path carving
+        let heights: Vec<f32> = points.iter().map(|p| {
+            vertices.iter().min_by(|a, b| dist2(&(a.loc[0], a.loc[2]), p).total_cmp(&dist2(&(b.loc[0], b.loc[2]), p)))
+                           .map(|v| v.loc[1]).unwrap_or(0.0)
+        }).collect();
+
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            let Some((dist, center_height)) = closest_on_path(&points, &heights, &(v.loc[0], v.loc[2])) else {continue;};
+            if dist >= self.width {
+                continue;
+            }
+            let scale = self.easing.apply(1.0 - dist/self.width);
+            match self.carve {
+                WanderCarve::Trench => {v.loc[1] -= self.height*scale;}
+                WanderCarve::Path   => {v.loc[1] += (center_height - v.loc[1])*scale;}
+            }
+        }
+    }
 
-        return TargetWanderNoise{wander: self.wander, width: self.width, height: self.height, step: self.step,
-            max_steps: self.max_steps, source: self.source, target: self.target, seed: self.seed, aabbs, ellipses, easing: self.easing};
-
+    fn is_height(&self) -> bool {
+        true
     }
-}
 
-impl TargetWanderNoise {
-    pub fn apply(&self, pos: &[f32; 3]) -> f32 {
-        for ellipse in self.ellipses.iter(){
-            if let Some(dist) = ellipse.has_point_dist(pos){
-                let scale = 1.0 - dist/ellipse.b.clamp(0.0, 1.0)/100.0;
-                return self.height*self.easing.apply(scale);
-            }
+    // random seed is fixed when modifier is applied, so evaluating layer again gives the same path
+    fn prepare(&mut self) {
+        if let NoiseSeed::NoSeed = self.seed {
+            self.seed = NoiseSeed::SetSeed(thread_rng().gen());
         }
+    }
 
-        return pos[1];
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
     }
 }
 
 
-
-
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TargetWanders {
-    Emu(f32),     // not, grid, steers randomly in degrees range
-    EmuPerlin, // next angle is generated by perlin
-    Wanderer   // nswe, goal goal goal, when you have a start and end point
+    Emu(f32),       // steers randomly in degrees range
+    EmuPerlin(f32), // next angle in degrees range is generated by perlin
+    Wanderer        // nswe, goal goal goal, when you have a start and end point
 }
 
 impl TargetWanders {
-    // returns new x, z, last_angle
-    fn apply(&self, xz: &(f32,f32), last_angle: f32, step: f32, direction: f32, seed: NoiseSeed, i: u64) -> (f32, f32, f32) {
-        let mut new_x: f32 = xz.0;
-        let mut new_z: f32 = xz.1;
-        let mut new_angle: f32 = last_angle;
+    pub fn iterator() -> Iter<'static, TargetWanders> {
+        static WANDER_OPTIONS: [TargetWanders; 3] = [TargetWanders::Emu(60.0), TargetWanders::EmuPerlin(90.0), TargetWanders::Wanderer];
+        WANDER_OPTIONS.iter()
+    }
+}
 
-        match self {
-            // Wanders::Robot => {
-            //     // Change direction every 5 steps
-            //     if step_count % 5 == 0 {
-            //         let angles: Vec<f32> = vec![0.0, 1.578, 3.142, -1.578]; 
-            //         let final_angle = get_random_element(&angles);
-            //         new_angle = final_angle;
-            //     }
-            //     (new_x, new_z) = get_point_angle(xz, step, new_angle);
-            // }
-            // Wanders::Zax => {
-            //     // Change direction every 5 steps
-            //     if step_count % 5 == 0 {
-            //         let angles: Vec<f32> = vec![0.0, 1.578, 3.142, -1.578]; 
-            //         let final_angle = get_random_element(&angles);
-            //         new_angle = final_angle;
-            //     }
-            //     (new_x, new_z) = get_point_angle(xz, step, new_angle);
-            // }
+impl TargetWanders {
+    // returns next point of the path, direction is the angle towards the target
+    fn apply(&self, xz: &(f32,f32), step: f32, direction: f32, rng: &mut StdRng, perlin: &Perlin, i: u32) -> (f32, f32) {
+        let new_x: f32;
+        let new_z: f32;
 
+        match self {
             TargetWanders::Emu(degrees) => {
                 let r2 = degrees.to_radians()/2.0;
-                (new_x, new_z, new_angle) = get_random_arc(xz,step, direction-r2, direction+r2, seed, i);
+                (new_x, new_z) = get_random_arc(xz, step, direction-r2, direction+r2, rng);
+            }
+            TargetWanders::EmuPerlin(degrees) => {
+                let r2 = degrees.to_radians()/2.0;
+                let n = perlin.get([i as f64 * 0.13, 0.5]) as f32;
+                (new_x, new_z) = get_point_angle(xz, step, direction + n.clamp(-1.0, 1.0)*r2);
             }
             TargetWanders::Wanderer => {
-                let angles: Vec<f32> = vec![direction+1.578, direction-1.578, direction - 3.142, direction, direction, direction]; // :)
-                let final_angle = get_random_element(&angles, seed, i);
-                new_angle = final_angle;
-                (new_x, new_z) = get_point_angle(xz, step, new_angle);
+                let angles: Vec<f32> = vec![direction+1.578, direction-1.578, direction - PI, direction, direction, direction]; // :)
+                let final_angle = get_random_element(&angles, rng);
+                (new_x, new_z) = get_point_angle(xz, step, final_angle);
             }
-            _ => {}
         }
 
-        return (new_x, new_z, new_angle);
-
+        return (new_x, new_z);
     }
 }
 
-pub fn get_random_element(elements: &Vec<f32>, seed: NoiseSeed, i: u64) -> f32{
-    match seed {
-        NoiseSeed::NoSeed => {
-            let mut rng  = thread_rng();
-            let random_index = rng.gen_range(0..elements.len());
-            return elements[random_index];
-        }
-        NoiseSeed::SetSeed(seed) => {
-            let mut rng = StdRng::seed_from_u64(seed+i);
-            let random_index = rng.gen_range(0..elements.len());
-            return elements[random_index];
-        }
-    }
+pub fn get_random_element(elements: &[f32], rng: &mut StdRng) -> f32{
+    let random_index = rng.gen_range(0..elements.len());
+    return elements[random_index];
 }
 
 fn get_point_angle(xz: &(f32,f32), r: f32, angle: f32) -> (f32, f32){
@@ -211,38 +246,35 @@ fn get_point_angle(xz: &(f32,f32), r: f32, angle: f32) -> (f32, f32){
     (point_x, point_z)
 }
 
-fn get_random_arc(xz: &(f32,f32), r: f32, start_angle: f32, end_angle: f32, seed: NoiseSeed, i: u64) -> (f32, f32, f32) {
-    let random_angle: f32;
-    match seed {
-        NoiseSeed::NoSeed => {
-            let mut rng  = thread_rng();
-            random_angle = rng.gen_range(start_angle..=end_angle);
-        }
-        NoiseSeed::SetSeed(seed) => {
-            let mut rng = StdRng::seed_from_u64(seed+i);
-            random_angle = rng.gen_range(start_angle..=end_angle);
-        }
-    }
-    let point_x = xz.0 + r * random_angle.cos();
-    let point_z = xz.1 + r * random_angle.sin();
-    (point_x, point_z, random_angle)
-}
-
-fn get_random_range(min: f32, max: f32, seed: NoiseSeed) -> f32 {
-    match seed {
-        NoiseSeed::NoSeed => {
-            let mut rng  = thread_rng();
-            return rng.gen_range(min..=max);
-            
-        }
-        NoiseSeed::SetSeed(seed) => {
-            let mut rng = StdRng::seed_from_u64(seed);
-            return rng.gen_range(min..=max);
-        }
-    }
+fn get_random_arc(xz: &(f32,f32), r: f32, start_angle: f32, end_angle: f32, rng: &mut StdRng) -> (f32, f32) {
+    let random_angle: f32 = rng.gen_range(start_angle..=end_angle);
+    get_point_angle(xz, r, random_angle)
 }
 
 pub fn get_direction(xz: &(f32, f32), target: &(f32, f32)) -> f32 {
     return atan2f(target.1 - xz.1, target.0 - xz.0);
 }
 
+fn dist2(a: &(f32, f32), b: &(f32, f32)) -> f32 {
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)
+}
+
+// distance to the closest point of the path and path height there
+fn closest_on_path(points: &[(f32, f32)], heights: &[f32], p: &(f32, f32)) -> Option<(f32, f32)> {
+    let mut best: Option<(f32, f32)> = None;
+    for i in 0..points.len()-1 {
+        let (a, b) = (points[i], points[i+1]);
+        let ab = (b.0 - a.0, b.1 - a.1);
+        let len2 = ab.0*ab.0 + ab.1*ab.1;
+        let t = match len2 > 0.0 {
+            true  => {(((p.0 - a.0)*ab.0 + (p.1 - a.1)*ab.1)/len2).clamp(0.0, 1.0)}
+            false => {0.0}
+        };
+        let closest = (a.0 + t*ab.0, a.1 + t*ab.1);
+        let dist = dist2(&closest, p).sqrt();
+        if best.is_none() || dist < best.unwrap().0 {
+            best = Some((dist, heights[i] + t*(heights[i+1] - heights[i])));
+        }
+    }
+    return best;
+}
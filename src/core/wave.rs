@@ -1,17 +1,27 @@
-use bevy_egui::{egui, egui::Ui};
-use bevy::prelude::ResMut;
 use serde::{Serialize, Deserialize};
-use crate::core::easings::Easings;
-use crate::editor::mtb_ui::ModResources;
-use super::noises::{Noise, NoiseFunction, Noises};
+use super::noises::{Noise, NoiseFunction};
+use super::modifier::TerrainModifier;
+use super::planes::PlaneData;
+use super::vertex::Vertex;
+#[cfg(feature = "editor")]
+use bevy_egui::egui::{Context, Ui};
+#[cfg(feature = "editor")]
+use crate::editor::colors::Colors;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Wave {
     pub noise:     Noise,
     pub scale_x:   f32,
     pub scale_z:   f32,
 }
 
+impl Default for Wave {
+    fn default() -> Self {
+        Wave::new()
+    }
+}
+
 impl Wave {
     pub fn new() -> Wave {
         Wave{noise: Noise::new(), scale_x: 0.01, scale_z: 0.01}
@@ -27,52 +37,22 @@ impl Wave {
         return [pos[0]+nudged_x, pos[1], pos[2]+nudged_z];
     }
 
-    pub fn ui(ui: &mut Ui, mod_res: &mut ResMut<ModResources>) {
-        
-        
-        ui.label("Wave");
-        ui.separator();
-
-        ui.columns(2, |columns| {
-            columns[1].label("Scale X");
-            columns[0].add(egui::DragValue::new(&mut mod_res.wave.scale_x).speed(0.1));
-            columns[1].label("Scale Z");
-            columns[0].add(egui::DragValue::new(&mut mod_res.wave.scale_z).speed(0.1));
-        });
-
-        ui.separator();
-        egui::ComboBox::from_label("Noise")
-        .width(140.0)
-        .selected_text(format!("{:?}", mod_res.wave.noise.noise))
-        .show_ui(ui, |ui| {
-          for &p in Noises::iterator(){
-            ui.selectable_value(&mut mod_res.wave.noise.noise, p, format!("{p:?}"));
-          }
-        });
-
-        ui.separator();
+}
 
-        ui.columns(2, |columns| {
-          columns[1].label("Seed");
-          columns[0].add(egui::DragValue::new(&mut mod_res.wave.noise.seed).speed(1.0));
-          columns[1].label("Scale");
-          columns[0].add(egui::DragValue::new(&mut mod_res.wave.noise.scale).speed(1.0));
-          columns[1].label("Frequency");
-          columns[0].add(egui::DragValue::new(&mut mod_res.wave.noise.freq).speed(0.1));
-          columns[1].label("Octaves");
-          columns[0].add(egui::DragValue::new(&mut mod_res.wave.noise.octaves).speed(1.0));
-        });
 
-        egui::ComboBox::from_label("Easing")
-        .width(140.0)
-        .selected_text(format!("{:?}", mod_res.wave.noise.easing))
-        .show_ui(ui, |ui| {
-          for &p in Easings::iterator(){
-            ui.selectable_value(&mut mod_res.wave.noise.easing, p, format!("{p:?}"));
-          }
-        });
-        ui.checkbox(&mut mod_res.wave.noise.global, "Use global position?");
-    
+impl TerrainModifier for Wave {
+    fn name(&self) -> &'static str {
+        "Wave"
+    }
+    fn apply(&self, _pd: &PlaneData, vertices: &mut Vec<Vertex>, selection: &Vec<usize>) {
+        let nfn = self.noise.set();
+        for index in selection.iter(){
+            let Some(v) = vertices.get_mut(*index) else {continue;};
+            v.loc = Wave::apply(self, &nfn, &v.loc);
+        }
+    }
+    #[cfg(feature = "editor")]
+    fn ui(&mut self, _ctx: &Context, ui: &mut Ui, _colors: &mut Colors) {
+        self.settings_ui(ui);
     }
 }
-
@@ -0,0 +1,241 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use bevy::utils::HashMap;
+
+use super::planes::PlaneData;
+use super::save::SavePlaneData;
+use super::vertex::Vertex;
+
+// Vertices of several planes seen as one world graph. Vertices of different planes at the same
+// world position become one node, border vertices that don't coincide are linked to the closest
+// border vertices of the other planes. Nodes on borders not touching any other plane are outlets.
+
+const KEY_SCALE: f32 = 100.0;
+
+#[derive(Debug, Clone)]
+pub struct WorldNode {
+    pub x:          f32,
+    pub z:          f32,
+    pub height:     f32,                  // world height, average of member vertices
+    pub members:    Vec<(usize, usize)>,  // (plane, vertex index)
+    pub neighbours: Vec<usize>,
+    pub outlet:     bool
+}
+
+#[derive(Debug, Clone)]
+pub struct WorldGraph {
+    pub nodes:  Vec<WorldNode>,
+    pub lookup: Vec<Vec<usize>>           // node of each vertex, per plane
+}
+
+// result of priority flood routing
+#[derive(Debug, Clone)]
+pub struct FloodRouting {
+    pub receivers: Vec<Option<usize>>,    // where water of the node flows, None for outlets
+    pub order:     Vec<usize>,            // nodes from outlets upstream, receiver always comes before its donors
+    pub filled:    Vec<f32>               // heights with depressions filled
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FloodCell {
+    height: f32,
+    node:   usize
+}
+
+impl Eq for FloodCell {}
+
+impl Ord for FloodCell {
+    // reversed, BinaryHeap pops the lowest cell first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.height.total_cmp(&self.height).then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for FloodCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn key(x: f32, z: f32) -> (i64, i64) {
+    ((x*KEY_SCALE).round() as i64, (z*KEY_SCALE).round() as i64)
+}
+
+impl WorldGraph {
+    // vertices of planes have to be ordered by index
+    pub fn new(planes: &[SavePlaneData]) -> Self {
+        let mut nodes: Vec<WorldNode> = Vec::new();
+        let mut keys: HashMap<(i64, i64), usize> = HashMap::new();
+        let mut lookup: Vec<Vec<usize>> = Vec::with_capacity(planes.len());
+
+        for (p, spd) in planes.iter().enumerate(){
+            let mut plane_lookup: Vec<usize> = Vec::with_capacity(spd.vertex.len());
+            for v in spd.vertex.iter(){
+                let x = v.loc[0] + spd.plane.loc[0];
+                let z = v.loc[2] + spd.plane.loc[2];
+                let h = v.loc[1] + spd.plane.loc[1];
+                let id = *keys.entry(key(x, z)).or_insert_with(|| {
+                    nodes.push(WorldNode{x, z, height: 0.0, members: Vec::new(), neighbours: Vec::new(), outlet: false});
+                    nodes.len() - 1
+                });
+                nodes[id].height += h;
+                nodes[id].members.push((p, v.index));
+                plane_lookup.push(id);
+            }
+            lookup.push(plane_lookup);
+        }
+        for node in nodes.iter_mut(){
+            node.height /= node.members.len() as f32;
+        }
+
+        let mut graph = WorldGraph{nodes, lookup};
+        let mut borders: Vec<(usize, usize, f32)> = Vec::new(); // node, plane, link radius
+        for (p, spd) in planes.iter().enumerate(){
+            let rows = (spd.plane.subdivisions[0] + 2) as usize;
+            let cols = (spd.plane.subdivisions[1] + 2) as usize;
+            if graph.lookup[p].len() != rows*cols {
+                continue;
+            }
+            let cell = (spd.plane.dims[0]/(cols-1) as f32).max(spd.plane.dims[1]/(rows-1) as f32);
+            for row in 0..rows {
+                for col in 0..cols {
+                    let id = graph.lookup[p][row*cols + col];
+                    for dr in -1..=1i32 {
+                        for dc in -1..=1i32 {
+                            let r = row as i32 + dr;
+                            let c = col as i32 + dc;
+                            if (dr == 0 && dc == 0) || r < 0 || c < 0 || r >= rows as i32 || c >= cols as i32 {
+                                continue;
+                            }
+                            graph.link(id, graph.lookup[p][r as usize*cols + c as usize]);
+                        }
+                    }
+                    if row == 0 || col == 0 || row == rows-1 || col == cols-1 {
+                        borders.push((id, p, cell*1.5));
+                    }
+                }
+            }
+        }
+
+        // border vertices shared with other planes, or linked to their borders
+        let max_radius = borders.iter().fold(0.0f32, |acc, b| acc.max(b.2));
+        let mut buckets: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        if max_radius > 0.0 {
+            for (i, (id, _p, _r)) in borders.iter().enumerate(){
+                let node = &graph.nodes[*id];
+                buckets.entry(((node.x/max_radius).floor() as i64, (node.z/max_radius).floor() as i64)).or_default().push(i);
+            }
+        }
+        for (id, p, radius) in borders.iter(){
+            let (x, z) = (graph.nodes[*id].x, graph.nodes[*id].z);
+            if graph.nodes[*id].members.iter().any(|(mp, _)| mp != p) {
+                continue; // coincides with vertex of another plane
+            }
+            let bx = (x/max_radius).floor() as i64;
+            let bz = (z/max_radius).floor() as i64;
+            let mut linked: bool = false;
+            for dx in -1..=1 {
+                for dz in -1..=1 {
+                    let Some(bucket) = buckets.get(&(bx+dx, bz+dz)) else {continue;};
+                    for i in bucket.iter(){
+                        let (other, op, _) = borders[*i];
+                        if op == *p || other == *id {
+                            continue;
+                        }
+                        let o = &graph.nodes[other];
+                        if (o.x - x).powi(2) + (o.z - z).powi(2) <= radius*radius {
+                            linked = true;
+                            graph.link(*id, other);
+                        }
+                    }
+                }
+            }
+            graph.nodes[*id].outlet = !linked;
+        }
+        return graph;
+    }
+
+    // graph of a single plane in its local space
+    pub fn from_plane(pd: &PlaneData, vertices: &[Vertex]) -> Self {
+        let mut sorted = vertices.to_vec();
+        sorted.sort_by_key(|v| v.index);
+        let plane = PlaneData{layers: Vec::new(), loc: [0.0, 0.0, 0.0], ..pd.clone()};
+        return WorldGraph::new(&[SavePlaneData{plane, vertex: sorted, flow: None}]);
+    }
+
+    // vertex index of the node, for graphs of a single plane
+    pub fn vertex_index(&self, node: usize) -> usize {
+        self.nodes[node].members[0].1
+    }
+
+    fn link(&mut self, a: usize, b: usize) {
+        if a == b || self.nodes[a].neighbours.contains(&b) {
+            return;
+        }
+        self.nodes[a].neighbours.push(b);
+        self.nodes[b].neighbours.push(a);
+    }
+
+    pub fn distance(&self, a: usize, b: usize) -> f32 {
+        ((self.nodes[a].x - self.nodes[b].x).powi(2) + (self.nodes[a].z - self.nodes[b].z).powi(2)).sqrt()
+    }
+
+    // Priority flood from the outlets: every node drains to the neighbour it was reached from,
+    // so water always finds a way out, also through pits. Parts of the world without an outlet
+    // drain into their lowest node.
+    pub fn flood(&self) -> FloodRouting {
+        return self.flood_epsilon(0.0);
+    }
+
+    // flood where filled heights rise by epsilon along the flow, so filled depressions still drain
+    pub fn flood_epsilon(&self, epsilon: f32) -> FloodRouting {
+        let n = self.nodes.len();
+        let mut receivers: Vec<Option<usize>> = vec![None; n];
+        let mut filled: Vec<f32> = self.nodes.iter().map(|node| node.height).collect();
+        let mut visited: Vec<bool> = vec![false; n];
+        let mut order: Vec<usize> = Vec::with_capacity(n);
+        let mut heap: BinaryHeap<FloodCell> = BinaryHeap::new();
+
+        for (id, node) in self.nodes.iter().enumerate(){
+            if node.outlet {
+                visited[id] = true;
+                heap.push(FloodCell{height: node.height, node: id});
+            }
+        }
+
+        loop {
+            while let Some(cell) = heap.pop() {
+                order.push(cell.node);
+                for nb in self.nodes[cell.node].neighbours.iter(){
+                    if visited[*nb] {
+                        continue;
+                    }
+                    visited[*nb] = true;
+                    receivers[*nb] = Some(cell.node);
+                    filled[*nb] = filled[*nb].max(cell.height + epsilon);
+                    heap.push(FloodCell{height: filled[*nb], node: *nb});
+                }
+            }
+            // closed parts of the world
+            let lowest = (0..n).filter(|id| !visited[*id]).min_by(|a, b| self.nodes[*a].height.total_cmp(&self.nodes[*b].height));
+            let Some(lowest) = lowest else {break;};
+            visited[lowest] = true;
+            heap.push(FloodCell{height: self.nodes[lowest].height, node: lowest});
+        }
+
+        return FloodRouting{receivers, order, filled};
+    }
+}
+
+impl FloodRouting {
+    // number of nodes draining through each node, including itself
+    pub fn accumulation(&self) -> Vec<f32> {
+        let mut acc: Vec<f32> = vec![1.0; self.receivers.len()];
+        for id in self.order.iter().rev(){
+            if let Some(r) = self.receivers[*id] {
+                acc[r] += acc[*id];
+            }
+        }
+        return acc;
+    }
+}
@@ -1,86 +1,491 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::mem::size_of;
 
-use std::collections::VecDeque;
-
-use bevy::input::common_conditions::{input_just_pressed, input_pressed};
+use bevy::input::InputSystem;
+use bevy::input::common_conditions::input_just_pressed;
 use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
 use bevy::utils::HashMap;
+use bevy_egui::egui::Ui;
 
-use super::mtb_ui::ApplyModifierEvent;
-use crate::core::vertex::{Vertex, PickedVertex, apply_modifiers};
+use crate::core::layers::ModifierLayer;
+use crate::core::planes::{Planes, PlaneData, TerrainPlane};
+use crate::core::save::SavePlaneData;
+use crate::core::utils::AABB;
+use crate::core::vertex::Vertex;
+use super::GlobalSettings;
+use super::planes::{PickedPlane, PlaneId};
+use super::vertex::PickedVertex;
 
+// Command based undo/redo. Every gesture (mouse press to release, Enter, Backspace, loaded save)
+// is recorded as the differences of planes before and after it: added and removed planes,
+// plane data, vertices and selection. Undo applies them backwards, redo forwards.
+// History keeps the last recorded state of each plane, planes changed since then are tracked
+// every frame and only these are read and compared when an action begins or ends.
 
 pub struct ActionsPlugin;
 
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut App) {
         app
-        .insert_resource(SceneStates::new())
-        .add_systems(Update, save_state.before(apply_modifiers))
-        .add_systems(Update, undo.run_if(input_just_pressed(KeyCode::Z)
-                                 .and_then(input_pressed(KeyCode::ControlLeft))))
-      ;                      
+        .add_event::<HistoryAction>()
+        .insert_resource(History::new())
+        .add_systems(PreUpdate, begin_action.after(InputSystem).run_if(starts_action))
+        .add_systems(Update, begin_action.before(super::io::sync_planes).run_if(on_event::<AssetEvent<Planes>>()))
+        .add_systems(Update, input_undo.run_if(input_just_pressed(KeyCode::Z)
+                                       .and_then(is_ctrl_pressed)))
+        .add_systems(Update, input_redo.run_if(input_just_pressed(KeyCode::Y)
+                                       .and_then(is_ctrl_pressed)))
+        .add_systems(PostUpdate, undo_redo.run_if(on_event::<HistoryAction>()))
+        .add_systems(Last, track_changes.before(end_action))
+        .add_systems(Last, end_action.run_if(ends_action))
+      ;
     }
   }
 
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum HistoryAction {
+    Undo,
+    Redo
+}
+
+// planes as read by history and written by undo/redo
+type PlaneStates<'w, 's> = Query<'w, 's, (&'static PlaneId, &'static PlaneData, &'static Handle<Mesh>, Option<&'static PickedPlane>, Option<&'static Children>), With<TerrainPlane>>;
+type PlaneStatesMut<'w, 's> = Query<'w, 's, (Entity, &'static PlaneId, &'static mut PlaneData, &'static mut Transform, &'static mut AABB,
+                                             Option<&'static mut PickedPlane>, &'static Handle<Mesh>, Option<&'static Children>), With<TerrainPlane>>;
+
+// state of one plane, vertices ordered by index
+#[derive(Clone)]
+pub struct PlaneState {
+    pub plane:    PlaneData,
+    pub vertex:   Vec<Vertex>,
+    pub picked:   Vec<bool>,
+    pub selected: bool
+}
+
+// single change of one plane
+#[derive(Clone)]
+pub enum Command {
+    Spawn{id: PlaneId, state: PlaneState},
+    Despawn{id: PlaneId, state: PlaneState},
+    Plane{id: PlaneId, before: PlaneData, after: PlaneData},       // move, plane edit, modifier layers
+    Vertices{id: PlaneId, changes: Vec<(Vertex, Vertex)>},         // before, after
+    Selection{id: PlaneId, vertices: Vec<(usize, bool)>, plane: Option<bool>}  // picked after
+}
+
+impl Command {
+    pub fn inverse(&self) -> Command {
+        match self {
+            Command::Spawn{id, state}           => {Command::Despawn{id: *id, state: state.clone()}}
+            Command::Despawn{id, state}         => {Command::Spawn{id: *id, state: state.clone()}}
+            Command::Plane{id, before, after}   => {Command::Plane{id: *id, before: after.clone(), after: before.clone()}}
+            Command::Vertices{id, changes}      => {Command::Vertices{id: *id, changes: changes.iter().map(|(b, a)| (*a, *b)).collect()}}
+            Command::Selection{id, vertices, plane} => {
+                Command::Selection{id: *id, vertices: vertices.iter().map(|(i, p)| (*i, !p)).collect(), plane: plane.map(|p| !p)}
+            }
+        }
+    }
+
+    // approximate memory used by the command
+    pub fn size(&self) -> usize {
+        match self {
+            Command::Spawn{state, ..}        => {state_size(state)}
+            Command::Despawn{state, ..}      => {state_size(state)}
+            Command::Plane{before, after, ..}=> {plane_size(before) + plane_size(after)}
+            Command::Vertices{changes, ..}   => {changes.len()*2*size_of::<Vertex>()}
+            Command::Selection{vertices, ..} => {vertices.len()*size_of::<(usize, bool)>()}
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Spawn{..}     => {"Add plane"}
+            Command::Despawn{..}   => {"Delete plane"}
+            Command::Plane{..}     => {"Edit plane"}
+            Command::Vertices{..}  => {"Edit vertices"}
+            Command::Selection{..} => {"Selection"}
+        }
+    }
+}
+
+fn plane_size(pd: &PlaneData) -> usize {
+    size_of::<PlaneData>() + pd.layers.iter().map(|layer| {
+        size_of::<ModifierLayer>() + layer.selection.len()*size_of::<usize>() + layer.weights.len()*size_of::<f32>()
+        + layer.modifier.params.to_string().len()
+    }).sum::<usize>()
+}
+
+fn state_size(state: &PlaneState) -> usize {
+    plane_size(&state.plane) + state.vertex.len()*(size_of::<Vertex>() + size_of::<bool>())
+}
+
+// one undo step
+pub struct HistoryEntry {
+    pub name:     String,
+    pub commands: Vec<Command>,
+    pub size:     usize
+}
+
 #[derive(Resource)]
-pub struct SceneStates {
-    pub index: u32,
-    pub data: VecDeque<SceneState>
+pub struct History {
+    pub undo:     VecDeque<HistoryEntry>,
+    pub redo:     Vec<HistoryEntry>,
+    pub used:     usize,                                   // bytes in undo and redo
+    pub revision: u64,                                     // changes on every push, undo and redo
+    pub label:    Option<String>,                          // name of the open action, set by the editing system
+    open:         bool,                                    // action is being recorded
+    states:       BTreeMap<PlaneId, PlaneState>,           // state of each plane at the last begin or end of an action
+    dirty:        BTreeSet<PlaneId>                        // planes changed since their state was taken
 }
-impl SceneStates {
+
+impl Default for History {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+impl History {
     pub fn new() -> Self {
-        SceneStates{index: 0, data: VecDeque::new()}
+        History{undo: VecDeque::new(), redo: Vec::new(), used: 0, revision: 0, label: None,
+                open: false, states: BTreeMap::new(), dirty: BTreeSet::new()}
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry, budget: usize) {
+        for old in self.redo.drain(..){
+            self.used -= old.size;
+        }
+        self.used += entry.size;
+        self.revision += 1;
+        self.undo.push_back(entry);
+        while self.used > budget && self.undo.len() > 1 {
+            if let Some(old) = self.undo.pop_front() {
+                self.used -= old.size;
+            }
+        }
+    }
+
+    // names the open action, the first name wins
+    pub fn set_label(&mut self, label: &str) {
+        if self.open && self.label.is_none() {
+            self.label = Some(label.to_string());
+        }
     }
+
+    pub fn ui(&self, ui: &mut Ui, actions: &mut EventWriter<HistoryAction>) {
+        ui.horizontal(|ui| {
+          let undo = self.undo.back().map(|e| e.name.as_str()).unwrap_or("");
+          if ui.add_enabled(!self.undo.is_empty(), bevy_egui::egui::Button::new(format!("Undo {}", undo))).clicked() {
+            actions.send(HistoryAction::Undo);
+          }
+          let redo = self.redo.last().map(|e| e.name.as_str()).unwrap_or("");
+          if ui.add_enabled(!self.redo.is_empty(), bevy_egui::egui::Button::new(format!("Redo {}", redo))).clicked() {
+            actions.send(HistoryAction::Redo);
+          }
+        });
+        ui.label(format!("History: {} steps, {:.1} MB", self.undo.len(), self.used as f32/1_048_576.0));
+    }
+}
+
+fn is_ctrl_pressed(keys: Res<Input<KeyCode>>) -> bool {
+    keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+}
+
+fn is_gesture(mouse: &Input<MouseButton>, keys: &Input<KeyCode>) -> bool {
+    mouse.any_pressed([MouseButton::Left, MouseButton::Right]) || keys.any_pressed([KeyCode::Return, KeyCode::Back])
 }
 
-pub struct SceneState {
-    pub vertex_entities: HashMap<Entity, (Transform, Vertex, bool)>
+fn starts_action(history: Res<History>, mouse: Res<Input<MouseButton>>, keys: Res<Input<KeyCode>>) -> bool {
+    !history.open && (mouse.any_just_pressed([MouseButton::Left, MouseButton::Right])
+                                 || keys.any_just_pressed([KeyCode::Return, KeyCode::Back]))
 }
 
-pub fn save_state(mut apply_mod:      EventReader<ApplyModifierEvent>,
-                  mut scene_states:   ResMut<SceneStates>,
-                  vertex:  Query<(Entity, &Transform, &Vertex, Option<&PickedVertex>)>){
+fn ends_action(history: Res<History>, mouse: Res<Input<MouseButton>>, keys: Res<Input<KeyCode>>) -> bool {
+    history.open && !is_gesture(&mouse, &keys)
+}
+
+fn input_undo(keys: Res<Input<KeyCode>>, mut actions: EventWriter<HistoryAction>){
+    if !keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        actions.send(HistoryAction::Undo);
+    } else {
+        actions.send(HistoryAction::Redo);
+    }
+}
+
+fn input_redo(mut actions: EventWriter<HistoryAction>){
+    actions.send(HistoryAction::Redo);
+}
 
-    for _ev in apply_mod.iter(){   
-        info!("Saving state");     
-        let mut ss = SceneState{ vertex_entities: HashMap::new()};
-        for (entity, transform, vertex, picked_vertex) in vertex.iter(){
-            ss.vertex_entities.insert(entity, (*transform, *vertex, picked_vertex.is_some()));
+// current state of given planes, planes without vertex entities yet are read from their mesh
+fn world_state(planes:  &PlaneStates<'_, '_>,
+               vertex:  &Query<(&Vertex, &PickedVertex)>,
+               meshes:  &Assets<Mesh>,
+               ids:     &BTreeSet<PlaneId>) -> BTreeMap<PlaneId, PlaneState> {
+
+    let mut states: BTreeMap<PlaneId, PlaneState> = BTreeMap::new();
+    for (id, pd, handle_mesh, picked_plane, children) in planes.iter(){
+        if !ids.contains(id) {
+            continue;
+        }
+        let mut state = PlaneState{plane: pd.clone(), vertex: Vec::new(), picked: Vec::new(), selected: picked_plane.is_some_and(|p| p.0)};
+
+        if let Some(mesh) = meshes.get(handle_mesh) {
+            if let Some(VertexAttributeValues::Float32x3(v_pos)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+                let white: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+                let v_clr = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+                    Some(VertexAttributeValues::Float32x4(v_clr)) => {v_clr.clone()}
+                    _ => {vec![white; v_pos.len()]}
+                };
+                state.vertex = v_pos.iter().enumerate().map(|(index, pos)| Vertex::new(index, pos, v_clr.get(index).unwrap_or(&white))).collect();
+                state.picked = vec![false; v_pos.len()];
+            }
         }
-        scene_states.data.push_back(ss);
-        
-        while scene_states.data.len() > 20 {
-            scene_states.data.pop_front();
+        for child in children.into_iter().flat_map(|c| c.iter()){
+            if let Ok((v, picked)) = vertex.get(*child){
+                if v.index < state.vertex.len() {
+                    state.vertex[v.index] = *v;
+                    state.picked[v.index] = picked.0;
+                }
+            }
         }
-        scene_states.index = scene_states.data.len() as u32 -1;
+        states.insert(*id, state);
     }
+    return states;
+}
+
+fn same_vertex(a: &Vertex, b: &Vertex) -> bool {
+    a.loc == b.loc && a.clr == b.clr
 }
 
-// it doesnt undo actions, it just says undo
-pub fn undo(mut scene_states:   ResMut<SceneStates>,
-            mut vertex:         Query<(Entity, &mut Transform, &mut Vertex, &mut PickedVertex)>){
+fn diff(before: &BTreeMap<PlaneId, PlaneState>, after: &BTreeMap<PlaneId, PlaneState>) -> Vec<Command> {
+    let mut commands: Vec<Command> = Vec::new();
+    for (id, b) in before.iter(){
+        let Some(a) = after.get(id) else {
+            commands.push(Command::Despawn{id: *id, state: b.clone()});
+            continue;
+        };
+        if b.vertex.len() != a.vertex.len() {
+            commands.push(Command::Despawn{id: *id, state: b.clone()});
+            commands.push(Command::Spawn{id: *id, state: a.clone()});
+            continue;
+        }
+        if b.plane != a.plane {
+            commands.push(Command::Plane{id: *id, before: b.plane.clone(), after: a.plane.clone()});
+        }
+        let changes: Vec<(Vertex, Vertex)> = b.vertex.iter().zip(a.vertex.iter())
+                                              .filter(|(bv, av)| !same_vertex(bv, av))
+                                              .map(|(bv, av)| (*bv, *av)).collect();
+        if !changes.is_empty() {
+            commands.push(Command::Vertices{id: *id, changes});
+        }
+        let vertices: Vec<(usize, bool)> = (0..a.picked.len()).filter(|i| a.picked[*i] != b.picked[*i]).map(|i| (i, a.picked[i])).collect();
+        let plane = match a.selected != b.selected {
+            true  => {Some(a.selected)}
+            false => {None}
+        };
+        if !vertices.is_empty() || plane.is_some() {
+            commands.push(Command::Selection{id: *id, vertices, plane});
+        }
+    }
+    for (id, a) in after.iter(){
+        if !before.contains_key(id) {
+            commands.push(Command::Spawn{id: *id, state: a.clone()});
+        }
+    }
+    return commands;
+}
 
-    info!("scene_states index: {}", scene_states.index);
+// marks planes changed in this frame: plane data, selection, vertices or mesh
+#[allow(clippy::type_complexity)]
+fn track_changes(mut history:     ResMut<History>,
+                 planes:          Query<(&PlaneId, &Handle<Mesh>), With<TerrainPlane>>,
+                 changed:         Query<&PlaneId, (With<TerrainPlane>, Or<(Changed<PlaneData>, Changed<PickedPlane>)>)>,
+                 vertex:          Query<&Parent, Or<(Changed<Vertex>, Changed<PickedVertex>)>>,
+                 mut mesh_events: EventReader<AssetEvent<Mesh>>){
 
-    #[allow(unused_comparisons)]
-    if scene_states.index >= 0 && (scene_states.index as usize) < scene_states.data.len() {
+    let mut dirty: BTreeSet<PlaneId> = changed.iter().copied().collect();
+    for parent in vertex.iter(){
+        if let Ok((id, _handle_mesh)) = planes.get(parent.get()) {
+            dirty.insert(*id);
+        }
+    }
+    for event in mesh_events.iter(){
+        if let AssetEvent::Modified{handle} = event {
+            dirty.extend(planes.iter().filter(|(_id, handle_mesh)| *handle_mesh == handle).map(|(id, _handle_mesh)| *id));
+        }
+    }
+    if !dirty.is_empty() {
+        history.dirty.extend(dirty);
+    }
+}
 
-        info!("Undoing");
+// takes state of dirty, added and removed planes, returns their previous and current state
+fn refresh_states(history:  &mut History,
+                  planes:   &PlaneStates<'_, '_>,
+                  vertex:   &Query<(&Vertex, &PickedVertex)>,
+                  meshes:   &Assets<Mesh>) -> (BTreeMap<PlaneId, PlaneState>, BTreeMap<PlaneId, PlaneState>) {
 
-        let ss: &SceneState = &scene_states.data[scene_states.index as usize];    
+    let current: BTreeSet<PlaneId> = planes.iter().map(|p| *p.0).collect();
+    let mut ids: BTreeSet<PlaneId> = std::mem::take(&mut history.dirty);
+    ids.extend(current.iter().filter(|id| !history.states.contains_key(id)));
+    ids.extend(history.states.keys().filter(|id| !current.contains(id)));
 
-        for (entity, mut transform, mut vertex, mut picked) in vertex.iter_mut(){
-            if let Some((old_transform, old_vertex, old_picked_vertex)) = ss.vertex_entities.get(&entity){
+    let before: BTreeMap<PlaneId, PlaneState> = ids.iter().filter_map(|id| history.states.remove(id).map(|state| (*id, state))).collect();
+    let after = world_state(planes, vertex, meshes, &ids);
+    history.states.extend(after.iter().map(|(id, state)| (*id, state.clone())));
+    return (before, after);
+}
 
-                *transform = *old_transform;
-                *vertex = *old_vertex;
-                picked.0 = *old_picked_vertex;
-            }
+pub fn begin_action(mut history:  ResMut<History>,
+                    planes:       PlaneStates<'_, '_>,
+                    vertex:       Query<(&Vertex, &PickedVertex)>,
+                    meshes:       Res<Assets<Mesh>>){
+    if history.open {
+        return;
+    }
+    // changes made outside of actions (undo, layer evaluation in later frames) are not recorded
+    refresh_states(&mut history, &planes, &vertex, &meshes);
+    history.open = true;
+    history.label = None;
+}
+
+pub fn end_action(mut history:  ResMut<History>,
+                  settings:     Res<GlobalSettings>,
+                  planes:       PlaneStates<'_, '_>,
+                  vertex:       Query<(&Vertex, &PickedVertex)>,
+                  meshes:       Res<Assets<Mesh>>){
+
+    if !history.open {
+        return;
+    }
+    history.open = false;
+    let label = history.label.take();
+    let (before, after) = refresh_states(&mut history, &planes, &vertex, &meshes);
+    let commands = diff(&before, &after);
+    if commands.is_empty() {
+        return;
+    }
+    // selection only changes are named as such, otherwise by the main change
+    let name = label.unwrap_or_else(|| {
+        commands.iter().find(|c| !matches!(c, Command::Selection{..})).unwrap_or(&commands[0]).name().to_string()
+    });
+    let size = commands.iter().map(|c| c.size()).sum();
+    info!("Recorded {} ({} changes)", name, commands.len());
+    history.push(HistoryEntry{name, commands, size}, (settings.history_mb.max(0.0)*1_048_576.0) as usize);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn undo_redo(mut commands:     Commands,
+                 mut actions:      EventReader<HistoryAction>,
+                 mut history:      ResMut<History>,
+                 mouse:            Res<Input<MouseButton>>,
+                 mut meshes:       ResMut<Assets<Mesh>>,
+                 mut materials:    ResMut<Assets<StandardMaterial>>,
+                 mut planes:       PlaneStatesMut<'_, '_>,
+                 mut vertex:       Query<(&mut Vertex, &mut Transform, &mut PickedVertex), Without<TerrainPlane>>){
+
+    for action in actions.iter(){
+        if mouse.any_pressed([MouseButton::Left, MouseButton::Right]) {
+            continue; // in the middle of an edit
         }
+        history.open = false; // undo button click is not an edit
+        history.label = None;
 
-        if scene_states.index > 0 {
-            scene_states.index -= 1;
+        let steps: Vec<Command> = match action {
+            HistoryAction::Undo => {
+                let Some(entry) = history.undo.pop_back() else {continue;};
+                let steps = entry.commands.iter().rev().map(|c| c.inverse()).collect();
+                info!("Undo {}", entry.name);
+                history.redo.push(entry);
+                steps
+            }
+            HistoryAction::Redo => {
+                let Some(entry) = history.redo.pop() else {continue;};
+                let steps = entry.commands.clone();
+                info!("Redo {}", entry.name);
+                history.undo.push_back(entry);
+                steps
+            }
+        };
+        history.revision += 1;
+        for step in steps.iter(){
+            apply_command(step, &mut commands, &mut meshes, &mut materials, &mut planes, &mut vertex);
         }
+    }
+}
 
+fn apply_command(command:    &Command,
+                 commands:   &mut Commands,
+                 meshes:     &mut ResMut<Assets<Mesh>>,
+                 materials:  &mut ResMut<Assets<StandardMaterial>>,
+                 planes:     &mut PlaneStatesMut<'_, '_>,
+                 vertex:     &mut Query<(&mut Vertex, &mut Transform, &mut PickedVertex), Without<TerrainPlane>>){
+
+    if let Command::Spawn{id, state} = command {
+        let spd = SavePlaneData{plane: state.plane.clone(), vertex: state.vertex.clone(), flow: None};
+        let entity = spd.spawn(commands, meshes, materials);
+        commands.entity(entity).insert((*id, PickedPlane(state.selected)));
+        return;
+    }
+
+    let id = match command {
+        Command::Spawn{id, ..} | Command::Despawn{id, ..} | Command::Plane{id, ..} | Command::Vertices{id, ..} | Command::Selection{id, ..} => {*id}
+    };
+    let Some((entity, _id, mut pd, mut tr, mut aabb, picked_plane, handle_mesh, children)) = planes.iter_mut().find(|p| *p.1 == id) else {
+        warn!("Plane of history step not found");
+        return;
+    };
+
+    match command {
+        Command::Spawn{..} => {}
+        Command::Despawn{..} => {
+            commands.entity(entity).despawn_recursive();
+        }
+        Command::Plane{after, ..} => {
+            *pd = after.clone();
+            tr.translation = after.loc.into();
+            *aabb = after.get_aabb();
+        }
+        Command::Vertices{changes, ..} => {
+            let after: HashMap<usize, Vertex> = changes.iter().map(|(_b, a)| (a.index, *a)).collect();
+            for child in children.into_iter().flat_map(|c| c.iter()){
+                if let Ok((mut v, mut v_tr, _picked)) = vertex.get_mut(*child){
+                    if let Some(a) = after.get(&v.index) {
+                        *v = *a;
+                        v_tr.translation = a.loc.into();
+                    }
+                }
+            }
+            // mesh is updated here too, vertex systems only run in edit mode
+            if let Some(mesh) = meshes.get_mut(handle_mesh) {
+                if let Some(VertexAttributeValues::Float32x3(v_pos)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+                    let mut v_pos = v_pos.clone();
+                    let mut v_clr = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+                        Some(VertexAttributeValues::Float32x4(v_clr)) => {v_clr.clone()}
+                        _ => {vec![[1.0, 1.0, 1.0, 1.0]; v_pos.len()]}
+                    };
+                    for a in after.values(){
+                        if a.index < v_pos.len() {
+                            v_pos[a.index] = a.loc;
+                            v_clr[a.index] = a.clr;
+                        }
+                    }
+                    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
+                    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, v_clr);
+                }
+            }
+        }
+        Command::Selection{vertices, plane, ..} => {
+            let picks: HashMap<usize, bool> = vertices.iter().copied().collect();
+            for child in children.into_iter().flat_map(|c| c.iter()){
+                if let Ok((v, _v_tr, mut picked)) = vertex.get_mut(*child){
+                    if let Some(p) = picks.get(&v.index) {
+                        picked.0 = *p;
+                    }
+                }
+            }
+            if let (Some(p), Some(mut picked_plane)) = (plane, picked_plane) {
+                picked_plane.0 = *p;
+            }
+        }
     }
 }
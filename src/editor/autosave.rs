@@ -0,0 +1,227 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::core::planes::{PlaneData, TerrainPlane};
+use crate::core::save::{SaveFormat, load_planes};
+use crate::core::modifier::ModifierRegistry;
+use crate::core::vertex::Vertex;
+use crate::core::version::SaveError;
+use super::GlobalSettings;
+use super::actions::History;
+use super::colors::Colors;
+use super::io::{IOName, IOStatus, SaveProjectData, collect_planes, write_save};
+use super::mtb_ui::ModResources;
+
+// Timed autosave into a recovery slot, in the same format as regular saves.
+// While the editor runs it holds a lock file, which is removed on clean exit. A lock
+// left over at startup means the editor crashed and the autosave can be restored.
+// The autosave itself is removed on clean exit and after each manual save. While the recovery
+// is offered, the lock of the crashed session and its autosave are kept, so an unanswered offer
+// is made again on the next start.
+
+const RECOVERY_DIR: &str = "./assets/saves/recovery";
+
+fn recovery_base() -> String {
+    format!("{}/autosave", RECOVERY_DIR)
+}
+
+fn lock_path() -> PathBuf {
+    PathBuf::from(format!("{}/editor.lock", RECOVERY_DIR))
+}
+
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(Autosave::new())
+        .add_systems(Startup, check_recovery)
+        .add_systems(Update, autosave)
+        .add_systems(Update, recovery_ui.run_if(has_offer))
+        .add_systems(Last, release_lock.run_if(on_event::<AppExit>()))
+      ;
+    }
+  }
+
+#[derive(Resource)]
+pub struct Autosave {
+    pub elapsed:  f32,
+    pub revision: u64,               // history revision of the last autosave
+    pub offer:    Option<String>     // save name of the crashed session, waiting for restore or discard
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Autosave::new()
+    }
+}
+
+impl Autosave {
+    pub fn new() -> Self {
+        Autosave{elapsed: 0.0, revision: 0, offer: None}
+    }
+
+    // called after a manual save of given history revision, the autosave is not needed anymore
+    pub fn saved(&mut self, revision: u64) {
+        if self.offer.is_some() {
+            return; // recovery file is kept until user decides what to do with it
+        }
+        self.revision = revision;
+        self.elapsed = 0.0;
+        remove_recovery();
+    }
+}
+
+fn remove_recovery() {
+    let base = recovery_base();
+    let _res = fs::remove_file(format!("{}.mtb.json", base));
+    let _res = fs::remove_file(format!("{}.proj.json", base));
+}
+
+fn has_offer(autosave: Res<Autosave>) -> bool {
+    autosave.offer.is_some()
+}
+
+// lock holds the save name of the running session
+fn write_lock(name: &str) -> Result<(), SaveError> {
+    fs::create_dir_all(RECOVERY_DIR).map_err(|e| SaveError::Io(format!("{}: {}", RECOVERY_DIR, e)))?;
+    fs::write(lock_path(), name).map_err(|e| SaveError::Io(format!("{}: {}", lock_path().display(), e)))?;
+    return Ok(());
+}
+
+fn check_recovery(mut autosave: ResMut<Autosave>,
+                  mut status:   ResMut<IOStatus>) {
+
+    let lock = lock_path();
+    if lock.exists() && PathBuf::from(format!("{}.mtb.json", recovery_base())).exists() {
+        let name = fs::read_to_string(&lock).unwrap_or_default();
+        warn!("Editor was not closed cleanly, autosave of '{}' found", name.trim());
+        autosave.offer = Some(name.trim().to_string());
+        return; // lock of this session is written once the offer is resolved
+    }
+    take_lock("", &mut status);
+}
+
+fn take_lock(name: &str, status: &mut IOStatus) {
+    if let Err(e) = write_lock(name) {
+        status.error(format!("Autosave disabled: {}", e));
+    }
+}
+
+// on clean exit nothing is left to recover, unless the offer of the crashed session is still open
+fn release_lock(autosave: Res<Autosave>) {
+    if autosave.offer.is_some() {
+        return;
+    }
+    remove_recovery();
+    let _res = fs::remove_file(lock_path());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn autosave(time:       Res<Time>,
+            vertex:     Query<&Vertex>,
+            planes:     Query<(&PlaneData, &Children)>,
+            colors:     Res<Colors>,
+            mod_res:    Res<ModResources>,
+            registry:   Res<ModifierRegistry>,
+            settings:   Res<GlobalSettings>,
+            history:    Res<History>,
+            ioname:     Res<IOName>,
+            mut autosave: ResMut<Autosave>,
+            mut status:   ResMut<IOStatus>) {
+
+    // recovery file is kept until user decides what to do with it
+    if autosave.offer.is_some() || settings.autosave_secs <= 0.0 {
+        return;
+    }
+    autosave.elapsed += time.delta_seconds();
+    if autosave.elapsed < settings.autosave_secs {
+        return;
+    }
+    autosave.elapsed = 0.0;
+    if history.revision == autosave.revision {
+        return; // nothing changed since last autosave
+    }
+
+    let v_planes = collect_planes(&planes, &vertex);
+    let project_data = SaveProjectData::new(&colors, &mod_res, &registry, &settings);
+    let base = recovery_base();
+    let path = PathBuf::from(format!("{}.mtb.json", base));
+    let path_proj = PathBuf::from(format!("{}.proj.json", base));
+    match write_save(&path, &path_proj, SaveFormat::Json, &v_planes, &project_data, 0).and_then(|_| write_lock(&ioname.data)) {
+        Ok(())  => {
+            info!("Autosaved to {}", base);
+            autosave.revision = history.revision;
+        }
+        Err(e)  => {status.error(format!("Autosave failed: {}", e));}
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn recovery_ui(mut contexts:  EguiContexts,
+               mut commands:  Commands,
+               planes:        Query<Entity, With<TerrainPlane>>,
+               mut meshes:    ResMut<Assets<Mesh>>,
+               mut materials: ResMut<Assets<StandardMaterial>>,
+               mut colors:    ResMut<Colors>,
+               mut mod_res:   ResMut<ModResources>,
+               mut registry:  ResMut<ModifierRegistry>,
+               mut settings:  ResMut<GlobalSettings>,
+               mut ioname:    ResMut<IOName>,
+               mut autosave:  ResMut<Autosave>,
+               mut status:    ResMut<IOStatus>) {
+
+    let Some(name) = autosave.offer.clone() else {return;};
+    let mut restore = false;
+    let mut discard = false;
+
+    egui::Window::new("Recover").collapsible(false).resizable(false).show(contexts.ctx_mut(), |ui| {
+        if name.is_empty() {
+            ui.label("Editor was not closed cleanly. Restore the unsaved session?");
+        } else {
+            ui.label(format!("Editor was not closed cleanly. Restore the autosave of '{}'?", name));
+        }
+        ui.horizontal(|ui| {
+            restore = ui.button("Restore").clicked();
+            discard = ui.button("Discard").clicked();
+        });
+    });
+
+    if restore {
+        // spawned directly instead of through the asset server, so later autosaves don't reload the scene
+        let base = recovery_base();
+        let planes_file = fs::read(format!("{}.mtb.json", base)).map_err(|e| SaveError::Io(e.to_string()));
+        match planes_file.and_then(|bytes| load_planes(&bytes)) {
+            Ok(v_planes) => {
+                for entity in planes.iter(){
+                    commands.entity(entity).despawn_recursive();
+                }
+                for spd in v_planes.iter(){
+                    spd.spawn(&mut commands, &mut meshes, &mut materials);
+                }
+                let project = fs::read_to_string(format!("{}.proj.json", base)).map_err(|e| SaveError::Io(e.to_string()));
+                match project.and_then(|data| SaveProjectData::load(&data)) {
+                    Ok(projdata) => {
+                        projdata.apply(&mut colors, &mut mod_res, &mut registry, &mut settings);
+                        status.ok(format!("Restored autosave of '{}'", name));
+                    }
+                    Err(e) => {status.error(format!("Restored planes, failed to load project data: {}", e));}
+                }
+                ioname.data = name;
+            }
+            Err(e) => {
+                status.error(format!("Failed to restore autosave: {}", e));
+            }
+        }
+        autosave.offer = None;
+        take_lock(&ioname.data, &mut status);
+    } else if discard {
+        remove_recovery();
+        status.ok("Discarded autosave".to_string());
+        autosave.offer = None;
+        take_lock(&ioname.data, &mut status);
+    }
+}
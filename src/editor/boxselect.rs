@@ -3,7 +3,8 @@ use bevy::input::common_conditions::{input_just_pressed, input_pressed, input_ju
 use bevy::prelude::*;
 use libm::fabsf;
 
-use crate::core::vertex::{Vertex, PickedVertex};
+use crate::core::vertex::Vertex;
+use super::vertex::PickedVertex;
 use super::mtb_ui::PickerState;
 use super::mtb_grid::{HoverData, Hoverables};
 pub struct BoxSelectPlugin;
@@ -11,9 +11,11 @@ use bevy::render::mesh::{Indices, PrimitiveTopology};
 use triangulate::{ListFormat, Vertex as TRIVertex, TriangulationError};
 use triangulate::formats::IndexedListFormat;
 
-use crate::core::vertex::{Vertex, PickedVertex};
+use crate::core::vertex::Vertex;
+use super::vertex::PickedVertex;
 use super::mtb_ui::PickerState;
 use super::mtb_grid::HoverData;
+use super::paint::is_painting;
 
 pub struct BrushPlugin;
 
@@ -24,7 +26,7 @@ impl Plugin for BrushPlugin {
         .add_systems(OnExit(PickerState::Brush), despawn_brush)
         .add_systems(OnEnter(PickerState::Brush), spawn_brush)
         .add_systems(Update, update_brush.run_if(in_state(PickerState::Brush)))
-        .add_systems(Update, select.after(update_brush).run_if(input_pressed(MouseButton::Left).and_then(in_state(PickerState::Brush))))
+        .add_systems(Update, select.after(update_brush).run_if(input_pressed(MouseButton::Left).and_then(in_state(PickerState::Brush)).and_then(not(is_painting))))
         ;
     }
 }
@@ -221,14 +223,13 @@ impl TryFrom<Polygon> for Mesh {
         let polygons = polygon
             .points
             .into_iter()
-            .map(|v| Vec2f(v))
+            .map(Vec2f)
             .collect::<Vec<Vec2f>>();
         let mut output = Vec::<[usize; 3]>::new();
         let format = IndexedListFormat::new(&mut output).into_fan_format();
         triangulate::Polygon::triangulate(&polygons, format)?;
         let indices = output.into_iter()
-            .map(|[a, b, c]| [c, b, a])
-            .flatten()
+            .flat_map(|[a, b, c]| [c, b, a])
             .map(|v| v as u32)
             .collect();
 
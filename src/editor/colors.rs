@@ -13,10 +13,10 @@ impl Plugin for ColorsPlugin {
   }
 
 pub fn to_clr32(aclr: &[u8; 4]) -> Color32 {
-    Color32::from_rgba_unmultiplied(aclr[0] as u8, 
-                                    aclr[1] as u8, 
-                                    aclr[2] as u8, 
-                                    aclr[3] as u8)
+    Color32::from_rgba_unmultiplied(aclr[0], 
+                                    aclr[1], 
+                                    aclr[2], 
+                                    aclr[3])
 // cant really tell why those colors look this way, something with linear rgba blah blah blah
 }
 
@@ -43,6 +43,12 @@ pub struct Colors {
     pub input:         [f32; 4], 
     pub clicked:       bool
 }
+impl Default for Colors {
+    fn default() -> Self {
+        Colors::new()
+    }
+}
+
 impl Colors {
     pub fn addf32(&mut self, clr: &[f32; 4]) {
         let clr_conv = [(clr[0]*255.0) as u8, 
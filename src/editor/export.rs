@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Ui};
+use bevy::utils::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::core::export::{ExportFormat, ExportMesh};
+use crate::core::files::write_file;
+use crate::core::import::import_mesh;
+use crate::core::planes::{PlaneData, TerrainPlane, unique_label};
+use super::actions::History;
+use super::io::IOStatus;
+use super::planes::PickedPlane;
+
+// Exports current plane meshes into ./assets/exports/ and imports obj and ply grid meshes
+// from there as new planes, see core::export and core::import
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_event::<ExportData>()
+        .add_event::<ImportData>()
+        .insert_resource(ExportSettings::new())
+        .add_systems(PostUpdate, export_data.run_if(on_event::<ExportData>()))
+        .add_systems(PostUpdate, import_data.run_if(on_event::<ImportData>()))
+      ;
+    }
+  }
+
+#[derive(Event)]
+pub struct ExportData;
+
+#[derive(Event)]
+pub struct ImportData;
+
+#[derive(Resource)]
+pub struct ExportSettings {
+    pub name:      String,
+    pub format:    ExportFormat,
+    pub selected:  bool,         // only picked planes
+    pub import:    String        // file name with extension
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        ExportSettings::new()
+    }
+}
+
+impl ExportSettings {
+    pub fn new() -> Self {
+        ExportSettings{name: "".to_string(), format: ExportFormat::Glb, selected: false, import: "".to_string()}
+    }
+
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(format!("./assets/exports/{}.{}", self.name, self.format.extension()))
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, export: &mut EventWriter<ExportData>, import: &mut EventWriter<ImportData>) {
+        ui.add(egui::TextEdit::singleline(&mut self.name));
+        ui.horizontal_wrapped(|ui| {
+          for &p in ExportFormat::iterator(){
+            ui.radio_value(&mut self.format, p, p.extension());
+          }
+        });
+        ui.checkbox(&mut self.selected, "Selected planes only");
+        if ui.button("Export").clicked(){
+          export.send(ExportData);
+        }
+        ui.separator();
+        ui.label("Import .obj or .ply grid mesh");
+        ui.add(egui::TextEdit::singleline(&mut self.import));
+        if ui.button("Import").clicked(){
+          import.send(ImportData);
+        }
+    }
+}
+
+pub fn export_data(planes:     Query<(&PlaneData, &Handle<Mesh>, &PickedPlane), With<TerrainPlane>>,
+                   meshes:     Res<Assets<Mesh>>,
+                   settings:   Res<ExportSettings>,
+                   mut status: ResMut<IOStatus>) {
+
+    if settings.name.trim().is_empty() {
+        status.error("Failed to export: enter a file name".to_string());
+        return;
+    }
+
+    let mut v_planes: Vec<(&PlaneData, &Mesh)> = Vec::new();
+    for (pd, handle_mesh, picked) in planes.iter(){
+        if settings.selected && !picked.0 {
+            continue;
+        }
+        if let Some(mesh) = meshes.get(handle_mesh){
+            v_planes.push((pd, mesh));
+        }
+    }
+
+    let export_meshes = ExportMesh::from_planes(&v_planes);
+    if export_meshes.is_empty() {
+        status.error("Failed to export: no planes to export".to_string());
+        return;
+    }
+
+    let path = settings.path();
+    match write_file(&path, 0, |writer| settings.format.write(writer, &export_meshes)) {
+        Ok(())  => {status.ok(format!("Exported {} planes to {}", export_meshes.len(), path.display()));}
+        Err(e)  => {status.error(format!("Failed to export {}: {}", path.display(), e));}
+    }
+}
+
+pub fn import_data(mut commands:  Commands,
+                   planes:        Query<&PlaneData, With<TerrainPlane>>,
+                   mut meshes:    ResMut<Assets<Mesh>>,
+                   mut materials: ResMut<Assets<StandardMaterial>>,
+                   settings:      Res<ExportSettings>,
+                   mut history:   ResMut<History>,
+                   mut status:    ResMut<IOStatus>) {
+
+    let path = PathBuf::from(format!("./assets/exports/{}", settings.import));
+    let imported = fs::read(&path).map_err(|e| e.to_string())
+                                  .and_then(|bytes| import_mesh(&path, &bytes).map_err(|e| e.to_string()));
+    let v_planes = match imported {
+        Ok(v_planes) => {v_planes}
+        Err(e) => {
+            status.error(format!("Failed to import {}: {}", path.display(), e));
+            return;
+        }
+    };
+
+    // labels have to stay unique, planes are matched by label on reload
+    let mut labels: HashSet<String> = planes.iter().map(|pd| pd.label.clone()).collect();
+    history.set_label("Import");
+    for mut spd in v_planes.into_iter(){
+        spd.plane.label = unique_label(&spd.plane.label, &labels);
+        labels.insert(spd.plane.label.clone());
+        spd.spawn(&mut commands, &mut meshes, &mut materials);
+    }
+    status.ok(format!("Imported {}", path.display()));
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use bevy_egui::egui::Ui;
+
+use crate::core::flow::{FlowData, FlowRouting, FlowView};
+use crate::core::planes::PlaneData;
+use crate::core::vertex::Vertex;
+
+// Flow analysis overlay. Colors are written only into plane meshes, vertex colors stay untouched
+pub struct FlowPlugin;
+
+impl Plugin for FlowPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<FlowSettings>()
+        .add_systems(Last, flow_overlay)
+        ;
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct FlowSettings {
+    pub view:     Option<FlowView>,
+    pub routing:  FlowRouting,
+    pub export:   bool              // write flow analysis into saves
+}
+
+impl Default for FlowSettings {
+    fn default() -> Self {
+        FlowSettings{view: None, routing: FlowRouting::D8, export: false}
+    }
+}
+
+impl FlowSettings {
+    // returns true if any setting was changed
+    pub fn ui(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+          ui.label("Overlay:");
+          changed |= ui.radio_value(&mut self.view, None, "None").changed();
+          for &p in FlowView::iterator(){
+            changed |= ui.radio_value(&mut self.view, Some(p), format!("{p:?}")).changed();
+          }
+        });
+        ui.horizontal(|ui| {
+          ui.label("Routing:");
+          for &p in FlowRouting::iterator(){
+            changed |= ui.radio_value(&mut self.routing, p, format!("{p:?}")).changed();
+          }
+        });
+        changed |= ui.checkbox(&mut self.export, "Export flow in saves").changed();
+        return changed;
+    }
+}
+
+fn flow_overlay(settings:      Res<FlowSettings>,
+                planes:        Query<(Entity, &PlaneData, &Children, &Handle<Mesh>)>,
+                changed:       Query<&Parent, Changed<Vertex>>,
+                vertex:        Query<&Vertex>,
+                mut meshes:    ResMut<Assets<Mesh>>){
+
+    let all: bool = settings.is_changed();
+    if !all && (changed.is_empty() || settings.view.is_none()) {
+        return;
+    }
+    let changed_planes: Vec<Entity> = changed.iter().map(|parent| parent.get()).collect();
+
+    for (entity, pd, children, handle_mesh) in planes.iter(){
+        if !all && !changed_planes.contains(&entity) {
+            continue;
+        }
+        let Some(mesh) = meshes.get_mut(handle_mesh) else {continue;};
+        let mut vertices: Vec<Vertex> = children.iter().filter_map(|child| vertex.get(*child).ok()).copied().collect();
+        vertices.sort_by_key(|v| v.index);
+
+        let v_clr: Vec<[f32; 4]> = match settings.view {
+            Some(view) => {FlowData::new(pd, &vertices, settings.routing).colors(view)}
+            None       => {vertices.iter().map(|v| v.clr).collect()}
+        };
+        if v_clr.len() == mesh.count_vertices() {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, v_clr);
+        }
+    }
+}
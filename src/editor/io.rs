@@ -1,16 +1,26 @@
 use bevy::input::common_conditions::{input_pressed, input_just_pressed};
 use bevy::prelude::*;
 use bevy::utils::HashSet;
-use bevy::render::mesh::VertexAttributeValues;
+use bevy_egui::egui::{Color32, Ui};
 use bevy_mod_picking::prelude::*;
 use serde::{Serialize, Deserialize};
-use std::io::{BufWriter, Write};
-use std::fs::{self, File};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use super::GlobalSettings;
-use crate::core::planes::{PlaneData, TerrainPlane, PickPlane, PlaneEdit, plane_mesh};
+use crate::core::planes::{PlaneData, Planes, PlanesAsset, TerrainPlane, match_planes};
+use crate::core::files::{WriteFn, write_files};
+use crate::core::save::{SaveFormat, SavePlaneData, PlanesLoader};
+use crate::core::layers::Modifier;
+use crate::core::version::{Migration, SaveError, as_object, field, fields, items, migrate};
+use crate::core::modifier::ModifierRegistry;
+use super::planes::{PickPlane, PickedPlane, PlaneEdit, PlaneId};
 use crate::core::vertex::Vertex;
+use super::actions::History;
+use super::autosave::Autosave;
 use super::colors::Colors;
+use super::flow::FlowSettings;
 use super::mtb_ui::ModResources;
 
 pub struct IOPlugin;
@@ -20,11 +30,15 @@ impl Plugin for IOPlugin {
         app
         .add_event::<WriteData>()
         .add_event::<LoadData>()
+        .add_asset::<Planes>()
+        .init_asset_loader::<PlanesLoader>()
         .insert_resource(IOName::new())
+        .insert_resource(IOStatus::new())
         .add_systems(PreUpdate, input_write_data.run_if(input_pressed(KeyCode::ControlLeft)
                                                 .and_then(input_just_pressed(KeyCode::S))))
         .add_systems(PostUpdate, write_data.run_if(on_event::<WriteData>()))
         .add_systems(PostUpdate, load_data.run_if(on_event::<LoadData>()))
+        .add_systems(Update, sync_planes.run_if(resource_exists::<PlanesAsset>()))
       ;                      
     }
   }
@@ -39,6 +53,12 @@ fn input_write_data(mut write_data: EventWriter<WriteData>){
 pub struct IOName {
     pub data: String
 }
+impl Default for IOName {
+    fn default() -> Self {
+        IOName::new()
+    }
+}
+
 impl IOName {
     pub fn new() -> IOName {
         IOName { data: "".to_string() }
@@ -46,51 +66,57 @@ impl IOName {
 }
 
 
+// result of the last save or load, shown in object mode
+#[derive(Resource)]
+pub struct IOStatus {
+    pub message: String,
+    pub error:   bool
+}
+impl Default for IOStatus {
+    fn default() -> Self {
+        IOStatus::new()
+    }
+}
+
+impl IOStatus {
+    pub fn new() -> IOStatus {
+        IOStatus { message: "".to_string(), error: false }
+    }
+    pub fn ok(&mut self, message: String) {
+        info!("{}", message);
+        self.message = message;
+        self.error = false;
+    }
+    pub fn error(&mut self, message: String) {
+        warn!("{}", message);
+        self.message = message;
+        self.error = true;
+    }
+    pub fn ui(&self, ui: &mut Ui) {
+        if self.message.is_empty() {
+            return;
+        }
+        match self.error {
+            true  => {ui.colored_label(Color32::LIGHT_RED, &self.message);}
+            false => {ui.label(&self.message);}
+        }
+    }
+}
+
 #[derive(Event)]
 pub struct WriteData;
 
 #[derive(Event)]
 pub struct LoadData;
 
-#[derive(Serialize, Deserialize)]
-pub struct SavePlaneData {
-    pub plane:        PlaneData,
-    pub vertex:       Vec<Vertex>
-}
 impl SavePlaneData {
-    pub fn from_pd(pd: &PlaneData) -> Self {
-        SavePlaneData{plane: pd.clone(), vertex: Vec::new()}
-    }
-
+    #[allow(clippy::default_constructed_unit_structs)]
     pub fn spawn(&self,
                  commands:           &mut Commands, 
                  meshes:             &mut ResMut<Assets<Mesh>>,
                  materials:          &mut ResMut<Assets<StandardMaterial>>) -> Entity {
 
-        
-        let mut mesh = plane_mesh(&self.plane.subdivisions, &self.plane.dims);
-
-        // Adjust vertices:
-        let mut v_clr: Option<Vec<[f32;4]>> = None;
-        let mut v_pos: Option<Vec<[f32; 3]>>;
-
-        v_pos = Some(mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap().to_vec());
-        if let Some(attr_vcolor) = mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
-            if let VertexAttributeValues::Float32x4(vcolors) = attr_vcolor {
-                v_clr = Some(vcolors.to_vec());
-            }
-        } else {
-            v_clr = Some(vec![[1.0, 1.0, 1.0, 1.0]; v_pos.as_ref().unwrap().len()]);
-        }
-
-        if v_pos.is_some() && v_clr.is_some() {
-            for vertex in self.vertex.iter(){
-                v_pos.as_mut().unwrap()[vertex.index] = vertex.loc;
-                v_clr.as_mut().unwrap()[vertex.index] = vertex.clr;
-            }
-            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, v_pos.unwrap());
-            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, v_clr.unwrap());
-        } 
+        let mesh = self.mesh();
 
         let entity = commands.spawn((PbrBundle {
             material: materials.add(StandardMaterial{..default()}),
@@ -98,6 +124,8 @@ impl SavePlaneData {
             transform: Transform::from_translation(self.plane.loc.into()),
             ..default()
             },
+            PickedPlane(false),
+            PlaneId::new(),
             TerrainPlane,
             PlaneEdit(false),
             PickableBundle::default(),
@@ -112,23 +140,110 @@ impl SavePlaneData {
     }
 }
 
+// migrations of the project file, see core::version
+pub const PROJECT_MIGRATIONS: [Migration; 1] = [project_v1];
+
+// version 0 is the same, just without version
+fn project_v1(value: Value) -> Result<Value, SaveError> {
+    as_object(value, "project").map(Value::Object)
+}
+
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct SaveProjectData {
     // planes:     Vec<SavePlaneData>,
+    version:    u32,
     colors:     HashSet<[u8; 4]>,
     mod_res:    ModResources,
+    modifiers:  Vec<Modifier>,
     settings:   GlobalSettings
 }
 
+impl Default for SaveProjectData {
+    fn default() -> Self {
+        SaveProjectData{version:   PROJECT_MIGRATIONS.len() as u32,
+                        colors:    HashSet::new(),
+                        mod_res:   ModResources::default(),
+                        modifiers: Vec::new(),
+                        settings:  GlobalSettings::new()}
+    }
+}
+
+impl SaveProjectData {
+    pub fn new(colors: &Colors, mod_res: &ModResources, registry: &ModifierRegistry, settings: &GlobalSettings) -> Self {
+        SaveProjectData{version:   PROJECT_MIGRATIONS.len() as u32,
+                        colors:    colors.selects.clone(),
+                        mod_res:   mod_res.clone(),
+                        modifiers: registry.params(),
+                        settings:  settings.clone()}
+    }
+
+    // sets editor resources from the project
+    pub fn apply(self, colors: &mut Colors, mod_res: &mut ModResources, registry: &mut ModifierRegistry, settings: &mut GlobalSettings) {
+        colors.selects = self.colors;
+        *mod_res = self.mod_res;
+        registry.set_params(&self.modifiers);
+        *settings = self.settings;
+    }
+
+    // reads project file of any version
+    pub fn load(data: &str) -> Result<SaveProjectData, SaveError> {
+        let value: Value = serde_json::from_str(data).map_err(|e| SaveError::Json(e.to_string()))?;
+        let value = migrate(value, &PROJECT_MIGRATIONS)?;
+        if let Ok(projdata) = SaveProjectData::deserialize(&value) {
+            return Ok(projdata);
+        }
+        // narrow the error down to the broken part
+        fields(&value, "project", &[("colors",    |v, p| field::<HashSet<[u8; 4]>>(v, p).map(|_| ())),
+                                    ("mod_res",   |v, p| field::<ModResources>(v, p).map(|_| ())),
+                                    ("modifiers", |v, p| items::<Modifier>(Some(v), p)),
+                                    ("settings",  |v, p| field::<GlobalSettings>(v, p).map(|_| ()))])?;
+        return field::<SaveProjectData>(&value, "project");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn write_data(vertex:   Query<&Vertex>,
                   planes:   Query<(&PlaneData, &Children)>,
                   colors:   Res<Colors>,
                   mod_res:  Res<ModResources>,
+                  registry: Res<ModifierRegistry>,
                   settings: Res<GlobalSettings>,
-                  ioname:   Res<IOName>) {
+                  flow:     Res<FlowSettings>,
+                  ioname:   Res<IOName>,
+                  history:  Res<History>,
+                  mut autosave: ResMut<Autosave>,
+                  mut status: ResMut<IOStatus>) {
 
     info!("Writing data to {}", ioname.data);
+    if ioname.data.trim().is_empty() {
+        status.error("Failed to save: enter a save name".to_string());
+        return;
+    }
+
+    let mut v_planes = collect_planes(&planes, &vertex);
+    if flow.export {
+        for spd in v_planes.iter_mut(){
+            spd.analyse_flow(flow.routing);
+        }
+    }
+    let project_data = SaveProjectData::new(&colors, &mod_res, &registry, &settings);
+
+    let (path, path_proj) = save_paths(&ioname.data);
+    let path = PathBuf::from(format!("./assets/{}", path));
+    let format = SaveFormat::from_path(&path, settings.binary);
+    match write_save(&path, &PathBuf::from(format!("./assets/{}", path_proj)), format, &v_planes, &project_data, settings.backups) {
+        Ok(())  => {
+            status.ok(format!("Saved {}", ioname.data));
+            autosave.saved(history.revision);
+        }
+        Err(e)  => {status.error(format!("Failed to save {}: {}", ioname.data, e));}
+    }
+}
 
+// current planes with their vertices
+pub fn collect_planes(planes:  &Query<(&PlaneData, &Children)>,
+                      vertex:  &Query<&Vertex>) -> Vec<SavePlaneData> {
     let mut v_planes: Vec<SavePlaneData> = Vec::new();
     for (pd, children) in planes.iter(){
         let mut spd = SavePlaneData::from_pd(pd);
@@ -139,62 +254,185 @@ pub fn write_data(vertex:   Query<&Vertex>,
         }
         v_planes.push(spd);
     }
-    let f = File::create(format!("./assets/saves/{}.json", ioname.data)).ok().unwrap();
-    let mut writer = BufWriter::new(f);
-    let _res = serde_json::to_writer(&mut writer, &v_planes);
-    let _res = writer.flush();
-
+    return v_planes;
+}
 
-    let project_data = SaveProjectData{colors: colors.selects.clone(), 
-        mod_res: mod_res.to_owned(), 
-        settings: settings.to_owned()};
+// planes and project file of save, relative to assets. Names ending with .mtb are saved in binary format,
+// others as .mtb.json
+pub fn save_paths(name: &str) -> (String, String) {
+    if let Some(base) = name.strip_suffix(".mtb") {
+        return (format!("saves/{}.mtb", base), format!("saves/{}.proj.json", base));
+    }
+    let base = name.strip_suffix(".json").unwrap_or(name);
+    let base = base.strip_suffix(".mtb").unwrap_or(base);
+    return (format!("saves/{}.mtb.json", base), format!("saves/{}.proj.json", base));
+}
 
-    let f_proj = File::create(format!("./assets/saves/{}.proj.json", ioname.data)).ok().unwrap();
-    let mut writer_proj = BufWriter::new(f_proj);
-    let _res_proj = serde_json::to_writer(&mut writer_proj, &project_data);
-    let _res_proj = writer_proj.flush();
+// both files are replaced together, previous versions are kept as backups
+pub fn write_save(path:       &Path,
+                  path_proj:  &Path,
+                  format:     SaveFormat,
+                  v_planes:   &Vec<SavePlaneData>,
+                  project:    &SaveProjectData,
+                  backups:    usize) -> Result<(), SaveError> {
+    let files: Vec<(PathBuf, WriteFn)> = vec![
+        (path.to_path_buf(), Box::new(move |writer| format.write(writer, v_planes))),
+        (path_proj.to_path_buf(), Box::new(|writer| serde_json::to_writer(writer, project).map_err(|e| SaveError::Io(e.to_string()))))
+    ];
+    return write_files(files, backups);
+}
 
+// json saves of older versions were named .json, which the planes loader no longer claims
+fn rename_legacy(path: &str, status: &mut IOStatus) {
+    let Some(base) = path.strip_suffix(".mtb.json") else {return;};
+    let new_path = format!("./assets/{}", path);
+    let old_path = format!("./assets/{}.json", base);
+    if Path::new(&new_path).exists() || !Path::new(&old_path).exists() {
+        return;
+    }
+    match fs::rename(&old_path, &new_path) {
+        Ok(()) => {info!("Renamed {} to {}", old_path, new_path);}
+        Err(e) => {status.error(format!("Failed to rename {} to {}: {}", old_path, new_path, e));}
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn load_data(mut commands:      Commands,
-                 mut meshes:        ResMut<Assets<Mesh>>,
-                 mut materials:     ResMut<Assets<StandardMaterial>>,
+                 ass:               Res<AssetServer>,
+                 planes_assets:     Res<Assets<Planes>>,
                  mut colors:        ResMut<Colors>,
                  mut mod_res:       ResMut<ModResources>,
+                 mut registry:      ResMut<ModifierRegistry>,
                  mut settings:      ResMut<GlobalSettings>,
-                 planes:            Query<Entity, With<PlaneData>>,
+                 mut status:        ResMut<IOStatus>,
                  ioname:            Res<IOName>) {
 
     info!("Loading data from {}", ioname.data);
 
-    let path: &str = &format!("./assets/saves/{}.json", ioname.data);
-    if let Ok(data) = fs::read_to_string(path){
-        if let Ok(vspds) = serde_json::from_str::<Vec<SavePlaneData>>(&data) {
-            for entity in planes.iter(){
-                commands.entity(entity).despawn_recursive();
+    // checked here to report errors, planes are spawned by sync_planes once the asset is loaded
+    let (path, path_proj) = save_paths(&ioname.data);
+    let path: &str = &path;
+    rename_legacy(path, &mut status);
+    let format = SaveFormat::from_path(Path::new(path), settings.binary);
+    let planes_file = fs::read(format!("./assets/{}", path)).map_err(|e| SaveError::Io(e.to_string()));
+    if let Err(e) = planes_file.and_then(|bytes| format.load(&bytes)) {
+        status.error(format!("Failed to load {}: {}", path, e));
+        return;
+    }
+
+    let path_proj: &str = &format!("./assets/{}", path_proj);
+    match fs::read_to_string(path_proj) {
+        Ok(data) => {
+            match SaveProjectData::load(&data) {
+                Ok(projdata) => {
+                    projdata.apply(&mut colors, &mut mod_res, &mut registry, &mut settings);
+                    status.ok(format!("Loaded {}", ioname.data));
+                }
+                Err(e) => {
+                    status.error(format!("Failed to load project data {}: {}", path_proj, e));
+                }
             }
-            for spd in vspds.iter(){
+        }
+        Err(e) => {
+            status.error(format!("Loaded planes, no project data {}: {}", path_proj, e));
+        }
+    }
+
+    let handle: Handle<Planes> = ass.load(path);
+    if planes_assets.contains(&handle) {
+        ass.reload_asset(path); // already loaded, read the file again
+    }
+    commands.insert_resource(PlanesAsset(handle));
+}
+
+// Spawns planes from loaded save and keeps them in sync with the file.
+// Planes with matching label keep their entity, only data, mesh and vertices are replaced.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn sync_planes(mut commands:      Commands,
+                   mut asset_events:  EventReader<AssetEvent<Planes>>,
+                   planes_asset:      Res<PlanesAsset>,
+                   planes_assets:     Res<Assets<Planes>>,
+                   mut planes:        Query<(Entity, &PlaneData, &PlaneId, &Handle<Mesh>, &mut Transform, &Children), With<TerrainPlane>>,
+                   mut vertex:        Query<(&mut Vertex, &mut Transform), Without<TerrainPlane>>,
+                   mut meshes:        ResMut<Assets<Mesh>>,
+                   mut materials:     ResMut<Assets<StandardMaterial>>,
+                   mut history:       ResMut<History>) {
+
+    for ev in asset_events.iter(){
+        let handle = match ev {
+            AssetEvent::Created{handle}  => {handle}
+            AssetEvent::Modified{handle} => {handle}
+            AssetEvent::Removed{..}      => {continue;}
+        };
+        if *handle != planes_asset.0 {
+            continue;
+        }
+        let Some(planes_data) = planes_assets.get(handle) else {continue;};
+        history.set_label("Load");
+
+        // planes sharing a label are matched in the order they were spawned
+        let mut existing: Vec<(PlaneId, String, Entity)> = planes.iter().map(|(entity, pd, id, _handle_mesh, _transform, _children)| (*id, pd.label.clone(), entity)).collect();
+        existing.sort_by_key(|(id, _label, _entity)| *id);
+        let existing: Vec<(String, Entity)> = existing.into_iter().map(|(_id, label, entity)| (label, entity)).collect();
+        let (matched, left) = match_planes(&existing, &planes_data.planes);
+
+        for (spd, entity) in planes_data.planes.iter().zip(matched){
+            let Some(entity) = entity else {
                 spd.spawn(&mut commands, &mut meshes, &mut materials);
+                continue;
+            };
+            let Ok((_entity, pd, _id, handle_mesh, mut transform, children)) = planes.get_mut(entity) else {continue;};
+
+            if pd.subdivisions != spd.plane.subdivisions {
+                // vertex count changed, respawn the plane with its vertices
+                commands.entity(entity).despawn_recursive();
+                spd.spawn(&mut commands, &mut meshes, &mut materials);
+                continue;
+            }
+
+            if let Some(mesh) = meshes.get_mut(handle_mesh){
+                *mesh = spd.mesh();
+            }
+            transform.translation = spd.plane.loc.into();
+            commands.entity(entity).insert((spd.plane.clone(), spd.plane.get_aabb()));
+
+            // saved vertices by index
+            let mut saved: Vec<Option<&Vertex>> = Vec::new();
+            for saved_v in spd.vertex.iter(){
+                if saved_v.index >= saved.len() {
+                    saved.resize(saved_v.index + 1, None);
+                }
+                saved[saved_v.index] = Some(saved_v);
+            }
+
+            for child in children.iter(){
+                if let Ok((mut v, mut v_transform)) = vertex.get_mut(*child){
+                    if let Some(Some(saved_v)) = saved.get(v.index) {
+                        *v = **saved_v;
+                    }
+                    v_transform.translation = v.loc.into();
+                }
             }
-            info!("Success! Loaded data from {}", ioname.data);
-        } else {
-            info!("Failed to parse Vec<SavePlaneData> from {}", ioname.data);
         }
-    } else {
-        info!("Failed to read data from save file: {}", ioname.data);
-    }                    
-
-    let path_proj: &str = &format!("./assets/saves/{}.proj.json", ioname.data);
-    if let Ok(data) = fs::read_to_string(path_proj){
-        if let Ok(projdata) = serde_json::from_str::<SaveProjectData>(&data) {
-            colors.selects = projdata.colors.clone();
-            *mod_res = projdata.mod_res;
-            *settings = projdata.settings;
-            info!("Success! Loaded project data from {}", ioname.data);
-        } else {
-            info!("Failed to parse SaveProjectData from {}", ioname.data);
+
+        for entity in left.iter(){
+            commands.entity(*entity).despawn_recursive();
         }
-    } else {
-        info!("Failed to read data from save file: {}", ioname.data);
-    }                    
+
+        info!("Success! Loaded {} planes", planes_data.planes.len());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_0_project_is_migrated() {
+        let projdata = SaveProjectData::load(include_str!("../../assets/saves/test.proj.json")).unwrap();
+        assert_eq!(projdata.version, PROJECT_MIGRATIONS.len() as u32);
+        assert!(matches!(SaveProjectData::load(r#"{"version": 99}"#), Err(SaveError::Version{found: 99, ..})));
+        assert!(matches!(SaveProjectData::load(r#"{"settings": 5}"#), Err(SaveError::Field{..})));
+    }
 }
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{Context, Ui};
+
+use crate::core::layers::ModifierLayer;
+use crate::core::modifier::ModifierRegistry;
+use crate::core::planes::PlaneData;
+use crate::core::vertex::Vertex;
+use super::colors::Colors;
+
+
+impl ModifierLayer {
+    // returns true if layers need to be evaluated again
+    pub fn ui(ctx:      &Context,
+              ui:       &mut Ui,
+              layers:   &mut Vec<ModifierLayer>,
+              registry: &ModifierRegistry,
+              colors:   &mut Colors) -> bool {
+
+        let mut changed: bool = false;
+        let mut swap: Option<(usize, usize)> = None;
+        let mut remove: Option<usize> = None;
+        let count = layers.len();
+
+        ui.label("Modifier Layers");
+        if layers.is_empty() {
+            ui.label("No modifiers applied");
+        }
+
+        for (index, layer) in layers.iter_mut().enumerate(){
+            ui.horizontal(|ui| {
+                let label = format!("{}. {} ({} vertex)", index+1, layer.modifier.name, layer.selection.len());
+                if ui.checkbox(&mut layer.active, label).changed() {
+                    changed = true;
+                }
+                if ui.small_button("Up").clicked() && index > 0 {
+                    swap = Some((index-1, index));
+                }
+                if ui.small_button("Down").clicked() && index + 1 < count {
+                    swap = Some((index, index+1));
+                }
+                ui.toggle_value(&mut layer.open, "Edit");
+                if ui.small_button("Remove").clicked() {
+                    remove = Some(index);
+                }
+            });
+
+            if layer.open {
+                // edit a copy of the modifier built from layer parameters
+                match registry.build(&layer.modifier) {
+                    Ok(mut modifier) => {
+                        modifier.ui(ctx, ui, colors);
+                        let params = modifier.to_value();
+                        if params != layer.modifier.params {
+                            layer.modifier.params = params;
+                            changed = true;
+                        }
+                    }
+                    Err(e) => {
+                        ui.label(e);
+                    }
+                }
+                ui.separator();
+            }
+        }
+
+        if let Some((a, b)) = swap {
+            layers.swap(a, b);
+            changed = true;
+        }
+        if let Some(index) = remove {
+            layers.remove(index);
+            changed = true;
+        }
+        return changed;
+    }
+}
+
+
+#[derive(Event)]
+pub struct EvaluateLayers {
+    pub entity:   Entity,
+    pub previous: Vec<ModifierLayer>    // layers before the change
+}
+
+// Rebuilds plane vertices from flat plane and all its active layers, manual edits are kept on top
+pub fn evaluate_layers(mut evaluate:  EventReader<EvaluateLayers>,
+                       registry:      Res<ModifierRegistry>,
+                       planes:        Query<(&PlaneData, &Children, &Handle<Mesh>)>,
+                       mut vertex:    Query<(&mut Transform, &mut Vertex)>,
+                       mut meshes:    ResMut<Assets<Mesh>>){
+
+    for ev in evaluate.iter(){
+        let Ok((pd, children, handle_mesh)) = planes.get(ev.entity) else {continue;};
+        info!("Evaluating {} layers of {}", pd.layers.len(), pd.label);
+
+        let mut current = pd.base_vertices();
+        for child in children.iter(){
+            if let Ok((_tr, v)) = vertex.get(*child){
+                if let Some(cur) = current.get_mut(v.index) {
+                    *cur = *v;
+                }
+            }
+        }
+
+        let vertices = pd.reevaluate_layers(&registry, &ev.previous, &current);
+        for child in children.iter(){
+            if let Ok((mut tr, mut v)) = vertex.get_mut(*child){
+                if let Some(new_v) = vertices.get(v.index) {
+                    *v = *new_v;
+                    tr.translation = v.loc.into();
+                }
+            }
+        }
+
+        if let Some(plane_mesh) = meshes.get_mut(handle_mesh) {
+            let v_pos: Vec<[f32; 3]> = vertices.iter().map(|v| v.loc).collect();
+            let v_clr: Vec<[f32; 4]> = vertices.iter().map(|v| v.clr).collect();
+            plane_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
+            plane_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, v_clr);
+        }
+    }
+}
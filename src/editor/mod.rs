@@ -10,6 +10,7 @@ use serde::{Serialize, Deserialize};
 
 
 pub mod actions;
+pub mod autosave;
 pub mod boxselect;
 pub mod brush;
 pub mod mtb_camera;
@@ -17,9 +18,22 @@ pub mod mtb_grid;
 pub mod mtb_ui;
 pub mod io;
 pub mod colors;
-
-use super::core::planes::{PlanesPlugin, TerrainPlane};
-use super::core::vertex::{spawn_vertex, Vertex, VertexRefs, VertexPlugin};
+pub mod export;
+pub mod flow;
+pub mod layers;
+pub mod modifiers;
+pub mod planes;
+pub mod paint;
+pub mod sculpt;
+pub mod vertex;
+pub mod water;
+
+use crate::core::binary::BinaryOptions;
+use crate::core::planes::TerrainPlane;
+use crate::core::vertex::Vertex;
+use planes::PlanesPlugin;
+use vertex::{spawn_vertex, VertexRefs, VertexPlugin};
+use water::WaterPlugin;
 
 use mtb_camera::MTBCameraPlugin;
 use mtb_grid::MTBGridPlugin;
@@ -42,6 +56,7 @@ impl Plugin for MTBEditorPlugin {
         .add_plugins(MTBUIPlugin)
         .add_plugins(PlanesPlugin)
         .add_plugins(VertexPlugin)
+        .add_plugins(WaterPlugin)
         .add_systems(Startup, spawn_lights)
         .add_systems(Update,  update_lights.run_if(is_settings_changed))
 
@@ -65,58 +80,113 @@ impl Plugin for MTBEditorPlugin {
 
 
 #[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GlobalSettings {
     pub dir_light:      DirLightData,
     pub ambient_light:  AmbientLightData,
-    pub vertex_radius:  f32
+    pub vertex_radius:  f32,
+    pub sea:            SeaData,
+    pub history_mb:     f32,       // memory for undo history
+    pub backups:        usize,     // previous versions kept on save
+    pub autosave_secs:  f32,       // autosave interval, 0 disables it
+    pub binary:         BinaryOptions  // options for .mtb saves
+}
+impl Default for GlobalSettings {
+    fn default() -> Self {
+        GlobalSettings::new()
+    }
 }
 impl GlobalSettings {
     pub fn new() -> Self {
         GlobalSettings {
             vertex_radius: 3.0,
             ambient_light: AmbientLightData::new(),
-            dir_light:     DirLightData::new()
+            dir_light:     DirLightData::new(),
+            sea:           SeaData::new(),
+            history_mb:    256.0,
+            backups:       3,
+            autosave_secs: 120.0,
+            binary:        BinaryOptions::default()
         }
     }
-    pub fn ui(&mut self, ui: &mut Ui) {
+    // returns true if any setting was changed
+    pub fn ui(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
         ui.label("Settings");
         ui.separator();
 
         ui.label("Vertex Radius");
-        ui.add(egui::DragValue::new(&mut self.vertex_radius).speed(1.0));
+        changed |= ui.add(egui::DragValue::new(&mut self.vertex_radius).speed(1.0)).changed();
+        ui.label("Undo history (MB)");
+        changed |= ui.add(egui::DragValue::new(&mut self.history_mb).speed(1.0).clamp_range(1.0..=f32::MAX)).changed();
+        ui.label("Save backups");
+        changed |= ui.add(egui::DragValue::new(&mut self.backups).speed(0.1).clamp_range(0..=20)).changed();
+        ui.label("Autosave every (s), 0 is off");
+        changed |= ui.add(egui::DragValue::new(&mut self.autosave_secs).speed(1.0).clamp_range(0.0..=f32::MAX)).changed();
+        changed |= ui.checkbox(&mut self.binary.compress, "Compress .mtb saves").changed();
+        changed |= ui.checkbox(&mut self.binary.u8_colors, "8 bit colors in .mtb saves").changed();
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
         ui.label("Ambient light");
-        ui.checkbox(&mut self.ambient_light.active, "Active");
-        ui.add(egui::DragValue::new(&mut self.ambient_light.brightness).speed(1.0));
+        changed |= ui.checkbox(&mut self.ambient_light.active, "Active").changed();
+        changed |= ui.add(egui::DragValue::new(&mut self.ambient_light.brightness).speed(1.0)).changed();
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
         ui.label("Directional light");
-        ui.checkbox(&mut self.dir_light.active, "Active");
+        changed |= ui.checkbox(&mut self.dir_light.active, "Active").changed();
 
         ui.columns(2, |columns| {
             columns[0].label("Illuminance");
-            columns[1].add(egui::DragValue::new(&mut self.dir_light.illuminance).speed(1.0));
+            changed |= columns[1].add(egui::DragValue::new(&mut self.dir_light.illuminance).speed(1.0)).changed();
             columns[0].label("Max Distance");
-            columns[1].add(egui::DragValue::new(&mut self.dir_light.max_dist).speed(1.0));
+            changed |= columns[1].add(egui::DragValue::new(&mut self.dir_light.max_dist).speed(1.0)).changed();
             columns[0].label("Loc X");
-            columns[1].add(egui::DragValue::new(&mut self.dir_light.loc[0]).speed(1.0));
+            changed |= columns[1].add(egui::DragValue::new(&mut self.dir_light.loc[0]).speed(1.0)).changed();
             columns[0].label("Loc Y");
-            columns[1].add(egui::DragValue::new(&mut self.dir_light.loc[1]).speed(1.0));
+            changed |= columns[1].add(egui::DragValue::new(&mut self.dir_light.loc[1]).speed(1.0)).changed();
             columns[0].label("Loc Z");
-            columns[1].add(egui::DragValue::new(&mut self.dir_light.loc[2]).speed(1.0));
+            changed |= columns[1].add(egui::DragValue::new(&mut self.dir_light.loc[2]).speed(1.0)).changed();
             columns[0].label("LookAt X");
-            columns[1].add(egui::DragValue::new(&mut self.dir_light.look_at[0]).speed(1.0));
+            changed |= columns[1].add(egui::DragValue::new(&mut self.dir_light.look_at[0]).speed(1.0)).changed();
             columns[0].label("LookAt Y");
-            columns[1].add(egui::DragValue::new(&mut self.dir_light.look_at[1]).speed(1.0));
+            changed |= columns[1].add(egui::DragValue::new(&mut self.dir_light.look_at[1]).speed(1.0)).changed();
             columns[0].label("LookAt Z");
-            columns[1].add(egui::DragValue::new(&mut self.dir_light.look_at[2]).speed(1.0));
+            changed |= columns[1].add(egui::DragValue::new(&mut self.dir_light.look_at[2]).speed(1.0)).changed();
           });
-          ui.checkbox(&mut self.dir_light.shadows_enabled, "Shadows Enabled");
+          changed |= ui.checkbox(&mut self.dir_light.shadows_enabled, "Shadows Enabled").changed();
 
+        ui.allocate_space(egui::Vec2::new(1.0, 20.0));
+        ui.label("Sea");
+        changed |= ui.checkbox(&mut self.sea.active, "Show water").changed();
+        ui.columns(2, |columns| {
+            columns[0].label("Sea level");
+            changed |= columns[1].add(egui::DragValue::new(&mut self.sea.level).speed(0.5)).changed();
+          });
+        changed |= ui.color_edit_button_rgba_unmultiplied(&mut self.sea.color).changed();
+        return changed;
     }
 }
 
 
+// Project sea level, shown as water surface and used by Coastline modifier
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeaData {
+    pub active:     bool,
+    pub level:      f32,
+    pub color:      [f32; 4]
+}
+impl SeaData {
+    pub fn new() -> Self {
+        SeaData { active: false, level: 0.0, color: [0.1, 0.3, 0.5, 0.6] }
+    }
+}
+impl Default for SeaData {
+    fn default() -> Self {
+        SeaData::new()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AmbientLightData {
     pub active:     bool,
     pub brightness: f32
@@ -126,8 +196,14 @@ impl AmbientLightData {
         AmbientLightData { active: true, brightness: 5.0 }
     }
 }
+impl Default for AmbientLightData {
+    fn default() -> Self {
+        AmbientLightData::new()
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DirLightData {
     pub loc:                [f32; 3],
     pub look_at:            [f32; 3],
@@ -147,6 +223,11 @@ impl DirLightData {
                     }
     }
 }
+impl Default for DirLightData {
+    fn default() -> Self {
+        DirLightData::new()
+    }
+}
 
 pub fn spawn_lights(mut commands:     Commands,
                     settings:         Res<GlobalSettings>) {
@@ -218,6 +299,12 @@ pub fn is_settings_changed(settings: Res<GlobalSettings>) -> bool {
     pub clicked: bool,
     pub timer:   Stopwatch
  }
+ impl Default for DoubleClickTimer {
+     fn default() -> Self {
+         DoubleClickTimer::new()
+     }
+ }
+
  impl DoubleClickTimer {
     pub fn new() -> Self {
         DoubleClickTimer { clicked: false, timer: Stopwatch::new() }
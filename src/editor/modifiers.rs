@@ -0,0 +1,569 @@
+use bevy_egui::egui::{self, Context, DragValue, RichText, Ui};
+
+use crate::core::coastline::Coastline;
+use crate::core::color::{Color, ColorGradient};
+use crate::core::easings::Easings;
+use crate::core::hydraulic::HydraulicErosion;
+use crate::core::lakes::{LakeMode, Lakes};
+use crate::core::noises::{Noise, Noises};
+use crate::core::offset::Offset;
+use crate::core::rivers::Rivers;
+use crate::core::terrace::Terrace;
+use crate::core::thermal::ThermalErosion;
+use crate::core::utils::Axis;
+use crate::core::value::{Value, ValueScaling, ValueType};
+use crate::core::wanders::{Edge, NoiseSeed, TargetWanderNoise, TargetWanders, WanderCarve, WanderLoc};
+use crate::core::wave::Wave;
+use super::colors::{Colors, f32_to_clr32};
+
+// Editor ui of the core modifiers
+
+impl Color {
+    pub fn settings_ui(&mut self, ctx: &Context, ui: &mut Ui, colors: &mut Colors){
+        let color = f32_to_clr32(&self.color);
+        ui.label("Color:");
+        ui.toggle_value(&mut self.open, 
+                        RichText::new("_____________")
+                        .background_color(color)
+                        .color(color));
+        colors.show(ctx, &mut self.open, "Color");
+        if self.open {
+            self.color = colors.input;
+        }
+    }
+}
+
+impl ColorGradient {
+    pub fn settings_ui(&mut self, ctx: &Context, ui: &mut Ui, colors: &mut Colors) {
+        ui.vertical(|ui| {
+            ui.label("Color Gradient");
+            ui.separator();
+
+            ui.columns(2, |columns| {
+                columns[0].label("Min Height:");
+                columns[0].add(DragValue::new(&mut self.min_height).speed(1.0));
+                columns[1].label("Max Height:");
+                columns[1].add(DragValue::new(&mut self.max_height).speed(1.0));
+            });
+            
+            ui.separator();
+
+            ui.columns(2, |columns| {
+                columns[0].label("Min Color:");
+                columns[1].label("Max Color:");
+
+                let min_color = f32_to_clr32(&self.min_color);
+                columns[0].toggle_value(&mut self.min_open,  
+                                        RichText::new("_____________")
+                                                 .background_color(min_color)
+                                                 .color(min_color));
+
+                colors.show(ctx, &mut self.min_open, "Gradient Min Color");
+                if self.min_open {
+                    self.max_open = false;
+                    if colors.clicked {
+                        self.min_color = colors.input;
+                        colors.clicked = false;
+                    }
+                }
+
+                let max_color = f32_to_clr32(&self.max_color);
+                columns[1].toggle_value(&mut self.max_open,  
+                                        RichText::new("_____________")
+                                                 .background_color(max_color)
+                                                 .color(max_color));         
+                colors.show(ctx, &mut self.max_open, "Gradient Max Color");
+                if self.max_open {
+                    self.min_open = false;
+                    if colors.clicked {
+                        self.max_color = colors.input;
+                        colors.clicked = false;
+                    }
+                }
+            });
+          });
+    }
+}
+
+impl Noise {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+
+        egui::ComboBox::from_label("Noise")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.noise))
+        .show_ui(ui, |ui| {
+          for &p in Noises::iterator(){
+            ui.selectable_value(&mut self.noise, p, format!("{p:?}"));
+          }
+        });
+
+        ui.separator();
+
+        ui.columns(2, |columns| {
+          columns[1].label("Seed");
+          columns[0].add(egui::DragValue::new(&mut self.seed).speed(1.0));
+          columns[1].label("Scale");
+          columns[0].add(egui::DragValue::new(&mut self.scale).speed(0.0001));
+          columns[1].label("Frequency");
+          columns[0].add(egui::DragValue::new(&mut self.freq).speed(0.1));
+          columns[1].label("Octaves");
+          columns[0].add(egui::DragValue::new(&mut self.octaves).speed(1.0));
+        });
+
+        egui::ComboBox::from_label("Easing")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.easing))
+        .show_ui(ui, |ui| {
+          for &p in Easings::iterator(){
+            ui.selectable_value(&mut self.easing, p, format!("{p:?}"));
+          }
+        });
+        ui.checkbox(&mut self.global, "Use global position?");
+
+        ui.checkbox(&mut self.reset, "Reset everytime?");
+        if self.reset {
+            ui.add(egui::DragValue::new(&mut self.reset_value).speed(1.0));
+        }
+    }
+}
+
+impl Offset {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+
+        ui.separator();
+        ui.vertical(|ui| {
+            ui.label("Offsets");
+            ui.columns(2, |columns| {
+              columns[1].label("X");
+              columns[0].add(egui::DragValue::new(&mut self.x).speed(0.1));
+              columns[1].label("Y");
+              columns[0].add(egui::DragValue::new(&mut self.y).speed(0.1));
+              columns[1].label("Z");
+              columns[0].add(egui::DragValue::new(&mut self.z).speed(0.1));
+            });
+        });
+    }
+}
+
+impl Terrace {
+  pub fn settings_ui(&mut self, ui: &mut Ui) {
+    ui.label("Terrace");
+    ui.separator();
+
+    ui.columns(2, |columns| {
+        columns[1].label("From");
+        columns[0].add(DragValue::new(&mut self.from).speed(1.0));
+        columns[1].label("To");
+        columns[0].add(DragValue::new(&mut self.to).speed(1.0));
+        columns[1].label("Value");
+        columns[0].add(DragValue::new(&mut self.value).speed(1.0));
+    });
+  }
+}
+
+impl Value {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+        
+        ui.label("Value");
+        ui.add(egui::DragValue::new(&mut self._value).speed(0.1));
+        ui.vertical(|ui| {
+          ui.label("Type:");
+          for &p in ValueType::iterator(){
+              if ui.radio_value(&mut self.value_type, p, format!("{p:?}")).clicked() {
+                self.value_type = p;
+              };
+          }
+        });
+        ui.separator();
+        ui.vertical(|ui| {
+          ui.label("Scaling:");
+          for &p in ValueScaling::iterator(){
+              if ui.radio_value(&mut self.scaling, p, format!("{p:?}")).clicked() {
+                self.scaling = p;
+              };
+          }
+        });
+
+        match self.scaling {
+          ValueScaling::DistanceAxis | ValueScaling::DistanceAxisRev => {
+            ui.vertical(|ui| {
+              ui.label("Axis:");
+              for &p in Axis::iterator(){
+                  if ui.radio_value(&mut self._axis, p, format!("{p:?}")).clicked() {
+                    self._axis = p;
+                  };
+              }
+              ui.label("Axis Value:");
+              ui.add(egui::DragValue::new(&mut self._axis_v).speed(1.0));
+              ui.label("Scale:");
+              ui.add(egui::DragValue::new(&mut self._scale).speed(0.1));
+            });
+          }
+          ValueScaling::DistancePoint | ValueScaling::DistancePointRev => {
+            ui.vertical(|ui| {
+              ui.label("Point:");
+              ui.columns(2, |columns| {
+                columns[1].label("X");
+                columns[0].add(egui::DragValue::new(&mut self._point.0).speed(1.0));
+                columns[1].label("Y");
+                columns[0].add(egui::DragValue::new(&mut self._point.1).speed(1.0));
+              });
+              ui.label("Scale:");
+              ui.add(egui::DragValue::new(&mut self._scale).speed(0.1));
+            });
+          }
+          _ => {}
+        }
+
+        ui.separator();
+
+    }
+}
+
+impl Wave {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+        
+        
+        ui.label("Wave");
+        ui.separator();
+
+        ui.columns(2, |columns| {
+            columns[1].label("Scale X");
+            columns[0].add(egui::DragValue::new(&mut self.scale_x).speed(0.1));
+            columns[1].label("Scale Z");
+            columns[0].add(egui::DragValue::new(&mut self.scale_z).speed(0.1));
+        });
+
+        ui.separator();
+        egui::ComboBox::from_label("Noise")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.noise.noise))
+        .show_ui(ui, |ui| {
+          for &p in Noises::iterator(){
+            ui.selectable_value(&mut self.noise.noise, p, format!("{p:?}"));
+          }
+        });
+
+        ui.separator();
+
+        ui.columns(2, |columns| {
+          columns[1].label("Seed");
+          columns[0].add(egui::DragValue::new(&mut self.noise.seed).speed(1.0));
+          columns[1].label("Scale");
+          columns[0].add(egui::DragValue::new(&mut self.noise.scale).speed(1.0));
+          columns[1].label("Frequency");
+          columns[0].add(egui::DragValue::new(&mut self.noise.freq).speed(0.1));
+          columns[1].label("Octaves");
+          columns[0].add(egui::DragValue::new(&mut self.noise.octaves).speed(1.0));
+        });
+
+        egui::ComboBox::from_label("Easing")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.noise.easing))
+        .show_ui(ui, |ui| {
+          for &p in Easings::iterator(){
+            ui.selectable_value(&mut self.noise.easing, p, format!("{p:?}"));
+          }
+        });
+        ui.checkbox(&mut self.noise.global, "Use global position?");
+    
+    }
+}
+
+impl HydraulicErosion {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+
+        ui.label("Hydraulic Erosion");
+        ui.separator();
+
+        ui.columns(2, |columns| {
+          columns[1].label("Seed");
+          columns[0].add(DragValue::new(&mut self.seed).speed(1.0));
+          columns[1].label("Droplets");
+          columns[0].add(DragValue::new(&mut self.iterations).speed(100.0));
+          columns[1].label("Lifetime");
+          columns[0].add(DragValue::new(&mut self.lifetime).speed(1.0));
+          columns[1].label("Inertia");
+          columns[0].add(DragValue::new(&mut self.inertia).speed(0.01).clamp_range(0.0..=1.0));
+          columns[1].label("Capacity");
+          columns[0].add(DragValue::new(&mut self.capacity).speed(0.1));
+          columns[1].label("Erosion");
+          columns[0].add(DragValue::new(&mut self.erosion).speed(0.01).clamp_range(0.0..=1.0));
+          columns[1].label("Deposition");
+          columns[0].add(DragValue::new(&mut self.deposition).speed(0.01).clamp_range(0.0..=1.0));
+          columns[1].label("Evaporation");
+          columns[0].add(DragValue::new(&mut self.evaporation).speed(0.001).clamp_range(0.0..=1.0));
+          columns[1].label("Gravity");
+          columns[0].add(DragValue::new(&mut self.gravity).speed(0.1));
+        });
+
+        ui.checkbox(&mut self.paint, "Paint erosion into colors?");
+        if self.paint {
+            ui.horizontal(|ui| {
+                ui.label("Eroded");
+                ui.color_edit_button_rgba_unmultiplied(&mut self.erosion_color);
+                ui.label("Deposited");
+                ui.color_edit_button_rgba_unmultiplied(&mut self.deposition_color);
+            });
+        }
+    }
+}
+
+impl ThermalErosion {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+
+        ui.label("Thermal Erosion");
+        ui.separator();
+
+        ui.columns(2, |columns| {
+          columns[1].label("Talus angle");
+          columns[0].add(DragValue::new(&mut self.talus).speed(0.5).clamp_range(0.0..=89.0));
+          columns[1].label("Iterations");
+          columns[0].add(DragValue::new(&mut self.iterations).speed(1.0));
+          columns[1].label("Strength");
+          columns[0].add(DragValue::new(&mut self.strength).speed(0.01).clamp_range(0.0..=1.0));
+        });
+    }
+}
+
+impl Coastline {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+
+        ui.label("Coastline");
+        ui.separator();
+
+        ui.columns(2, |columns| {
+          columns[1].label("Sea level");
+          columns[0].label(format!("{:.2}", self.sea_level)); // set from project sea level
+          columns[1].label("Beach height");
+          columns[0].add(DragValue::new(&mut self.beach_height).speed(0.1).clamp_range(0.0..=f32::MAX));
+          columns[1].label("Shelf depth");
+          columns[0].add(DragValue::new(&mut self.shelf_depth).speed(0.1).clamp_range(0.0..=f32::MAX));
+          columns[1].label("Strength");
+          columns[0].add(DragValue::new(&mut self.strength).speed(0.01).clamp_range(0.0..=1.0));
+        });
+        ui.label("Sea level follows the project sea level, change it under Sea in settings");
+
+        egui::ComboBox::from_label("Beach")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.beach))
+        .show_ui(ui, |ui| {
+          for &p in Easings::iterator(){
+            ui.selectable_value(&mut self.beach, p, format!("{p:?}"));
+          }
+        });
+        egui::ComboBox::from_label("Shelf")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.shelf))
+        .show_ui(ui, |ui| {
+          for &p in Easings::iterator(){
+            ui.selectable_value(&mut self.shelf, p, format!("{p:?}"));
+          }
+        });
+
+        ui.separator();
+        ui.checkbox(&mut self.island, "Island mask");
+        if self.island {
+          ui.columns(2, |columns| {
+            columns[1].label("Margin");
+            columns[0].add(DragValue::new(&mut self.island_margin).speed(1.0).clamp_range(0.0..=f32::MAX));
+            columns[1].label("Depth");
+            columns[0].add(DragValue::new(&mut self.island_depth).speed(0.5).clamp_range(0.0..=f32::MAX));
+          });
+        }
+    }
+}
+
+impl Lakes {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+
+        ui.label("Lakes");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+          ui.label("Mode:");
+          for &p in LakeMode::iterator(){
+              if ui.radio_value(&mut self.mode, p, format!("{p:?}")).clicked() {
+                self.mode = p;
+              };
+          }
+        });
+
+        ui.columns(2, |columns| {
+          match self.mode {
+            LakeMode::Fill => {
+              columns[1].label("Epsilon");
+              columns[0].add(DragValue::new(&mut self.epsilon).speed(0.001).clamp_range(0.0..=1.0));
+            }
+            LakeMode::Lake => {
+              columns[1].label("Min depth");
+              columns[0].add(DragValue::new(&mut self.min_depth).speed(0.1).clamp_range(0.0..=f32::MAX));
+              columns[1].label("Min area");
+              columns[0].add(DragValue::new(&mut self.min_area).speed(10.0).clamp_range(0.0..=f32::MAX));
+            }
+          }
+        });
+
+        ui.horizontal(|ui| {
+          ui.checkbox(&mut self.paint, "Paint water");
+          ui.color_edit_button_rgba_unmultiplied(&mut self.color);
+        });
+    }
+}
+
+impl Rivers {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+
+        ui.columns(2, |columns| {
+          columns[1].label("Seed");
+          columns[0].add(DragValue::new(&mut self.seed).speed(1.0));
+          columns[1].label("Sources");
+          columns[0].add(DragValue::new(&mut self.sources).speed(1.0));
+          columns[1].label("Source height");
+          columns[0].add(DragValue::new(&mut self.source_height).speed(0.01).clamp_range(0.0..=1.0));
+          columns[1].label("Spacing");
+          columns[0].add(DragValue::new(&mut self.spacing).speed(1.0).clamp_range(0.0..=f32::MAX));
+          columns[1].label("Min width");
+          columns[0].add(DragValue::new(&mut self.min_width).speed(0.5).clamp_range(0.0..=f32::MAX));
+          columns[1].label("Max width");
+          columns[0].add(DragValue::new(&mut self.max_width).speed(0.5).clamp_range(0.0..=f32::MAX));
+          columns[1].label("Min depth");
+          columns[0].add(DragValue::new(&mut self.min_depth).speed(0.1));
+          columns[1].label("Max depth");
+          columns[0].add(DragValue::new(&mut self.max_depth).speed(0.1));
+        });
+
+        egui::ComboBox::from_label("Banks")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.easing))
+        .show_ui(ui, |ui| {
+          for &p in Easings::iterator(){
+            ui.selectable_value(&mut self.easing, p, format!("{p:?}"));
+          }
+        });
+
+        ui.horizontal(|ui| {
+          ui.checkbox(&mut self.paint, "Paint rivers");
+          ui.color_edit_button_rgba_unmultiplied(&mut self.color);
+        });
+    }
+}
+
+impl TargetWanderNoise {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+
+        ui.label("Wander");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+          ui.label("Wander:");
+          for &p in TargetWanders::iterator(){
+              let selected = std::mem::discriminant(&self.wander) == std::mem::discriminant(&p);
+              if ui.radio(selected, wander_name(&p)).clicked() && !selected {
+                self.wander = p;
+              };
+          }
+        });
+        match &mut self.wander {
+            TargetWanders::Emu(degrees) | TargetWanders::EmuPerlin(degrees) => {
+                ui.horizontal(|ui| {
+                  ui.label("Angle range:");
+                  ui.add(DragValue::new(degrees).speed(1.0).clamp_range(0.0..=360.0));
+                });
+            }
+            TargetWanders::Wanderer => {}
+        }
+
+        ui.horizontal(|ui| {
+          ui.label("Carve:");
+          for &p in WanderCarve::iterator(){
+              if ui.radio_value(&mut self.carve, p, format!("{p:?}")).clicked() {
+                self.carve = p;
+              };
+          }
+        });
+
+        ui.columns(2, |columns| {
+          columns[1].label("Width");
+          columns[0].add(DragValue::new(&mut self.width).speed(0.5).clamp_range(0.0..=f32::MAX));
+          columns[1].label("Depth");
+          columns[0].add(DragValue::new(&mut self.height).speed(0.5));
+          columns[1].label("Step");
+          columns[0].add(DragValue::new(&mut self.step).speed(0.5).clamp_range(0.1..=f32::MAX));
+          columns[1].label("Max steps");
+          columns[0].add(DragValue::new(&mut self.max_steps).speed(1.0));
+        });
+
+        ui.separator();
+        wander_loc_ui(ui, "Source", &mut self.source);
+        wander_loc_ui(ui, "Target", &mut self.target);
+        ui.separator();
+
+        let mut random = self.seed == NoiseSeed::NoSeed;
+        if ui.checkbox(&mut random, "Random seed").changed() {
+            self.seed = match random {
+                true  => {NoiseSeed::NoSeed}
+                false => {NoiseSeed::SetSeed(0)}
+            };
+        }
+        if let NoiseSeed::SetSeed(seed) = &mut self.seed {
+            ui.horizontal(|ui| {
+              ui.label("Seed:");
+              ui.add(DragValue::new(seed).speed(1.0));
+            });
+        }
+
+        egui::ComboBox::from_label("Easing")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.easing))
+        .show_ui(ui, |ui| {
+          for &p in Easings::iterator(){
+            ui.selectable_value(&mut self.easing, p, format!("{p:?}"));
+          }
+        });
+    }
+}
+
+fn wander_name(wander: &TargetWanders) -> &'static str {
+    match wander {
+        TargetWanders::Emu(_)       => {"Emu"}
+        TargetWanders::EmuPerlin(_) => {"Emu Perlin"}
+        TargetWanders::Wanderer     => {"Wanderer"}
+    }
+}
+
+fn wander_loc_ui(ui: &mut Ui, label: &str, loc: &mut WanderLoc) {
+    ui.horizontal(|ui| {
+      ui.label(format!("{}:", label));
+      if ui.radio(matches!(loc, WanderLoc::Point(_)), "Point").clicked() {
+        if let WanderLoc::Edge(_) = loc {
+          *loc = WanderLoc::Point((0.0, 0.0));
+        }
+      }
+      if ui.radio(matches!(loc, WanderLoc::Edge(_)), "Edge").clicked() {
+        if let WanderLoc::Point(_) = loc {
+          *loc = WanderLoc::Edge(Edge::NX);
+        }
+      }
+    });
+    match loc {
+        WanderLoc::Point((x, z)) => {
+            ui.horizontal(|ui| {
+              ui.label("X");
+              ui.add(DragValue::new(x).speed(1.0));
+              ui.label("Z");
+              ui.add(DragValue::new(z).speed(1.0));
+            });
+        }
+        WanderLoc::Edge(edge) => {
+            egui::ComboBox::from_id_source(label)
+            .width(140.0)
+            .selected_text(format!("{:?}", edge))
+            .show_ui(ui, |ui| {
+              for &p in Edge::iterator(){
+                ui.selectable_value(edge, p, format!("{p:?}"));
+              }
+            });
+        }
+    }
+}
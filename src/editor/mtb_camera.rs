@@ -49,6 +49,7 @@ struct InputState {
 pub struct MTBCamera;
 
 
+#[allow(clippy::default_constructed_unit_structs)]
 fn setup(mut commands: Commands, 
          mut state: ResMut<InputState>,){
 
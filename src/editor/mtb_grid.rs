@@ -37,7 +37,7 @@ fn _update(mut grid: ResMut<GridData>,
     let v_pos: Vec<[f32; 3]> = md.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap().to_vec();
     for pos in v_pos.iter(){
       let tile_xz = grid.get_tile(pos[0], pos[2]);
-      tiles.entry(tile_xz).or_insert(Vec::new()).push(pos[1]);
+      tiles.entry(tile_xz).or_default().push(pos[1]);
     }
   }
 
@@ -120,6 +120,12 @@ pub struct HoverData {
 
 }
 
+impl Default for HoverData {
+    fn default() -> Self {
+        HoverData::new()
+    }
+}
+
 impl HoverData {
   pub fn new() -> HoverData {
         return HoverData{cursor_position: None,
@@ -147,6 +153,12 @@ pub struct GridData {
     pub y:              f32
 }
 
+impl Default for GridData {
+    fn default() -> Self {
+        GridData::new()
+    }
+}
+
 impl GridData {
     pub fn new() -> Self {
         let gp = GridData {
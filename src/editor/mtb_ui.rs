@@ -6,21 +6,25 @@ use bevy_egui::{egui, EguiContexts, EguiPlugin};
 use bevy_infinite_grid::{GridShadowCamera, InfiniteGrid, InfiniteGridBundle, InfiniteGridPlugin};
 use bevy::input::common_conditions::{input_pressed, input_just_pressed};
 
-use crate::core::offset::Offset;
-use crate::core::color::{Color, ColorGradient};
-use crate::core::noises::Noise;
-use crate::core::planes::{PlaneData, SpawnNewPlaneEvent};
-use crate::core::value::Value;
-use crate::core::vertex::{Vertex, HoveredVertex};
-use crate::core::wave::Wave;
-use crate::core::terrace::Terrace;
+use crate::core::easings::Easings;
+use crate::core::modifier::ModifierRegistry;
+use crate::core::rivers::Rivers;
+use crate::core::planes::PlaneData;
+use crate::core::vertex::Vertex;
 
 use super::colors::{ColorsPlugin, Colors};
-use super::io::{WriteData, LoadData, IOPlugin, IOName};
-use super::actions::ActionsPlugin;
+use super::flow::{FlowPlugin, FlowSettings};
+use super::planes::{GenerateRivers, SpawnNewPlaneEvent, StitchPlanes};
+use super::vertex::HoveredVertex;
+use super::io::{WriteData, LoadData, IOPlugin, IOName, IOStatus};
+use super::actions::{ActionsPlugin, History, HistoryAction};
+use super::autosave::AutosavePlugin;
+use super::export::{ExportData, ExportPlugin, ExportSettings, ImportData};
 use super::mtb_grid::{HoverData, Hoverables};
 use super::{AppState, GlobalSettings};
 use super::brush::{BrushPlugin, BrushSettings};
+use super::paint::{PaintPlugin, PaintSettings};
+use super::sculpt::{SculptPlugin, SculptSettings};
 use super::boxselect::BoxSelectPlugin;
 use super::spawn_text_node;
 
@@ -31,16 +35,22 @@ impl Plugin for MTBUIPlugin {
         app
         .add_event::<ApplyModifierEvent>()
         .add_state::<PickerState>()
-        .add_state::<ModifierState>()
         .add_plugins(InfiniteGridPlugin)
         .add_plugins(BoxSelectPlugin)
         .add_plugins(BrushPlugin)
+        .add_plugins(PaintPlugin)
+        .add_plugins(SculptPlugin)
         .add_plugins(EguiPlugin)
         .add_plugins(IOPlugin)
+        .add_plugins(AutosavePlugin)
+        .add_plugins(ExportPlugin)
         .add_plugins(ActionsPlugin)
         .add_plugins(ColorsPlugin)
+        .add_plugins(FlowPlugin)
         .init_resource::<OccupiedScreenSpace>()
         .insert_resource(ModResources::default())
+        .init_resource::<ModifierRegistry>()
+        .init_resource::<ModifierReport>()
         .insert_resource(PlaneData::new())
         .add_systems(Startup, setup)
         .add_systems(PreUpdate, input_apply_modifier.run_if(input_just_pressed(KeyCode::Return)
@@ -77,7 +87,7 @@ struct OccupiedScreenSpace {
 
 #[derive(Event)]
 pub struct ApplyModifierEvent{
-  pub mod_type: ModifierState
+  pub modifier: String
 }
 
 
@@ -86,71 +96,49 @@ pub enum PickerState {
     #[default]
     Point,
     Brush,
-    Box
+    Box,
+    Sculpt
 }
 
-impl<'a> PickerState {
+impl PickerState {
   pub fn iterator() -> Iter<'static, PickerState> {
-    static PICKER_OPTIONS: [PickerState; 3] = [PickerState::Box, PickerState::Point, PickerState::Brush];
+    static PICKER_OPTIONS: [PickerState; 4] = [PickerState::Box, PickerState::Point, PickerState::Brush, PickerState::Sculpt];
     PICKER_OPTIONS.iter()
   }
 }
 
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States, Component)]
-pub enum ModifierState {
-  #[default]
-    Color,
-    ColorGradient,
-    Noise,
-    Offset,
-    Value,
-    Wave,
-    Terrace,
-}
-
-impl<'a> ModifierState { 
-  pub fn iterator() -> Iter<'static, ModifierState> {
-    static MOD_OPTIONS: [ModifierState; 7] = [ModifierState::Color, 
-                                              ModifierState::ColorGradient,
-                                              ModifierState::Noise, 
-                                              ModifierState::Offset,
-                                              ModifierState::Value,
-                                              ModifierState::Wave, 
-                                              ModifierState::Terrace];
-    MOD_OPTIONS.iter()
-  }
-  
-}
-
+// Editor settings of modifiers, parameters of each modifier are kept in ModifierRegistry
 #[derive(Debug, Clone, Resource, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ModResources{
-  pub color:          Color,
-  pub color_gradient: ColorGradient,
-  pub value:          Value,
-  pub noise:          Noise,
-  pub wave:           Wave,
-  pub terrace:        Terrace,
-  pub offset:         Offset,
+  pub modifier:       String, // name of selected modifier
   pub show_csw:       bool,
   pub allow_dragging: bool,
   pub apply_gradient: bool, // to apply last gradient automatically on each height modifier
+  pub soft_selection: bool, // picked vertices spread weights to vertices around them
+  pub falloff_radius: f32,
+  pub falloff:        Easings
 }
 impl Default for ModResources {
     fn default() -> Self {
-      ModResources{show_csw:        false,
+      ModResources{modifier:        "Color".to_string(),
+                   show_csw:        false,
                    allow_dragging:  false,
                    apply_gradient:  false,
-                   color:           Color::new(), 
-                   color_gradient:  ColorGradient::new(), 
-                   value:           Value::new(),
-                   noise:           Noise::new(),
-                   wave:            Wave::new(),
-                   terrace:         Terrace::new(),
-                   offset:          Offset::new()
+                   soft_selection:  false,
+                   falloff_radius:  50.0,
+                   falloff:         Easings::SmoothStep
                   }
     }
 }
 
+// Summary of the last applied modifier, see TerrainModifier::report
+#[derive(Debug, Clone, Resource, Default)]
+pub struct ModifierReport {
+  pub modifier: String,
+  pub lines:    Vec<String>
+}
+
 #[derive(Component)]
 pub struct TopLeftInfoPanel;
 
@@ -169,22 +157,27 @@ fn setup(mut commands:  Commands){
 
 }
 
-fn input_apply_modifier(modifier_state: Res<State<ModifierState>>,
+fn input_apply_modifier(mod_res:        Res<ModResources>,
                         mut apply_mod:  EventWriter<ApplyModifierEvent>,){
-  apply_mod.send(ApplyModifierEvent{mod_type: *modifier_state.get()});
+  apply_mod.send(ApplyModifierEvent{modifier: mod_res.modifier.clone()});
 }
 
 
 
+#[allow(clippy::too_many_arguments)]
 fn update_egui_editor(mut contexts:              EguiContexts,
                       mut occupied_screen_space: ResMut<OccupiedScreenSpace>,
                       picker_state:              Res<State<PickerState>>,
                       mut next_picker_state:     ResMut<NextState<PickerState>>,
                       mut brush_settings:        ResMut<BrushSettings>,
-                      modifier_state:            Res<State<ModifierState>>,
-                      mut next_modifier_state:   ResMut<NextState<ModifierState>>,
+                      mut paint_settings:        ResMut<PaintSettings>,
+                      mut sculpt_settings:       ResMut<SculptSettings>,
+                      mut registry:              ResMut<ModifierRegistry>,
                       mut mod_res:               ResMut<ModResources>,
                       mut apply_mod:             EventWriter<ApplyModifierEvent>,
+                      report:                    Res<ModifierReport>,
+                      history:                   Res<History>,
+                      mut history_actions:       EventWriter<HistoryAction>,
                       mut colors:                ResMut<Colors>) {
 
   let ctx = contexts.ctx_mut();
@@ -192,6 +185,7 @@ fn update_egui_editor(mut contexts:              EguiContexts,
     .resizable(true)
     .show(ctx, |ui| {
         ui.label("Edit mode");
+        history.ui(ui, &mut history_actions);
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
         ui.vertical(|ui| {
           ui.label("Picker:");
@@ -205,19 +199,40 @@ fn update_egui_editor(mut contexts:              EguiContexts,
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
         if let PickerState::Brush = picker_state.get() {
           ui.add(egui::Slider::new(&mut brush_settings.radius, 1.0..=100.0).max_decimals(1));
+          paint_settings.ui(ctx, ui, &mut colors);
+        }
+        if let PickerState::Sculpt = picker_state.get() {
+          ui.add(egui::Slider::new(&mut brush_settings.radius, 1.0..=100.0).max_decimals(1));
+          sculpt_settings.ui(ui);
         }
 
         ui.allocate_space(egui::Vec2::new(1.0, 10.0));
         ui.checkbox(&mut mod_res.allow_dragging, "Allow Dragging vertices?");
         ui.allocate_space(egui::Vec2::new(1.0, 10.0));
         ui.checkbox(&mut mod_res.apply_gradient, "Apply gradient?");
+        ui.allocate_space(egui::Vec2::new(1.0, 10.0));
+        ui.checkbox(&mut mod_res.soft_selection, "Soft selection");
+        if mod_res.soft_selection {
+          ui.horizontal(|ui| {
+            ui.label("Falloff radius");
+            ui.add(egui::DragValue::new(&mut mod_res.falloff_radius).speed(1.0).clamp_range(0.0..=f32::MAX));
+          });
+          egui::ComboBox::from_label("Falloff")
+          .width(140.0)
+          .selected_text(format!("{:?}", mod_res.falloff))
+          .show_ui(ui, |ui| {
+            for &p in Easings::iterator(){
+              ui.selectable_value(&mut mod_res.falloff, p, format!("{p:?}"));
+            }
+          });
+        }
         
         ui.allocate_space(egui::Vec2::new(1.0, 10.0));
         ui.vertical(|ui| {
           ui.label("Modifier:");
-          for &p in ModifierState::iterator(){
-              if ui.radio_value(&mut modifier_state.get(), &p, format!("{p:?}")).clicked() {
-                next_modifier_state.set(p);
+          for name in registry.names(){
+              if ui.radio(mod_res.modifier == name, name).clicked() {
+                mod_res.modifier = name.to_string();
                 colors.reset();
               };
           }
@@ -226,46 +241,24 @@ fn update_egui_editor(mut contexts:              EguiContexts,
 
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
 
-        match modifier_state.get() {
-          ModifierState::Color => {
-            Color::ui(ctx, ui, &mut mod_res, &mut colors);
-            ui.separator();        
-          }
-          ModifierState::ColorGradient => {
-            ColorGradient::ui(ctx, ui, &mut mod_res, &mut colors);
-            ui.separator();
-          }
-          ModifierState::Value => {
-            Value::ui(ui, &mut mod_res);
-          }
-          ModifierState::Noise => {
-            Noise::ui(ui, &mut mod_res);
-          }
-          ModifierState::Wave => {
-            Wave::ui(ui, &mut mod_res);
-          }
-          ModifierState::Terrace => {
-            Terrace::ui(ui, &mut mod_res);
-          }
-          ModifierState::Offset => {
-            Offset::ui(ui, &mut mod_res);
-          }
-        }
+        modifier_ui(ctx, ui, &mod_res.modifier, &mut registry, &mut colors);
       
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
 
         if ui.button("Apply").clicked() {
-          apply_mod.send(ApplyModifierEvent{mod_type: *modifier_state.get()});
+          apply_mod.send(ApplyModifierEvent{modifier: mod_res.modifier.clone()});
 
-          match modifier_state.get(){
-            ModifierState::Color => {
-              colors.addf32(&mod_res.color.color);
-            }
-            ModifierState::ColorGradient => {
-              colors.addf32(&mod_res.color_gradient.min_color);
-              colors.addf32(&mod_res.color_gradient.max_color);
+          if let Some(modifier) = registry.get(&mod_res.modifier) {
+            for clr in modifier.used_colors().iter(){
+              colors.addf32(clr);
             }
-            _ => {}
+          }
+        }
+
+        if report.modifier == mod_res.modifier && !report.lines.is_empty() {
+          ui.allocate_space(egui::Vec2::new(1.0, 10.0));
+          for line in report.lines.iter(){
+            ui.label(line);
           }
         }
         
@@ -276,6 +269,17 @@ fn update_egui_editor(mut contexts:              EguiContexts,
     .width();
 }
 
+pub fn modifier_ui(ctx:       &egui::Context,
+                   ui:        &mut egui::Ui,
+                   name:      &str,
+                   registry:  &mut ModifierRegistry,
+                   colors:    &mut Colors){
+  if let Some(modifier) = registry.get_mut(name) {
+    modifier.ui(ctx, ui, colors);
+    ui.separator();
+  }
+}
+
 fn _input_spawn_plane(plane_data:       Res<PlaneData>,
                      mut spawn_plane:  EventWriter<SpawnNewPlaneEvent>,){
 
@@ -283,20 +287,30 @@ fn _input_spawn_plane(plane_data:       Res<PlaneData>,
 }
 
 
+#[allow(clippy::too_many_arguments)]
 fn update_egui_object(mut contexts:              EguiContexts,
                       mut occupied_screen_space: ResMut<OccupiedScreenSpace>,
                       mut plane_data:            ResMut<PlaneData>,
                       mut spawn_plane:           EventWriter<SpawnNewPlaneEvent>,
+                      mut stitch_planes:         EventWriter<StitchPlanes>,
+                      mut generate_rivers:       EventWriter<GenerateRivers>,
+                      mut rivers:                ResMut<Rivers>,
+                      mut flow:                  ResMut<FlowSettings>,
                       mut write_data:            EventWriter<WriteData>,
                       mut load_data:             EventWriter<LoadData>,
                       mut ioname:                ResMut<IOName>,
-                      mut settings:              ResMut<GlobalSettings>
+                      io_status:                 Res<IOStatus>,
+                      mut export:                (ResMut<ExportSettings>, EventWriter<ExportData>, EventWriter<ImportData>),
+                      mut settings:              ResMut<GlobalSettings>,
+                      history:                   Res<History>,
+                      mut history_actions:       EventWriter<HistoryAction>
                     ) {
   let ctx = contexts.ctx_mut();
   occupied_screen_space.right = egui::SidePanel::right("right_panel")
     .resizable(true)
     .show(ctx, |ui| {
         ui.label("Object mode");
+        history.ui(ui, &mut history_actions);
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
         ui.vertical(|ui| {
           ui.label("Plane Data");
@@ -334,6 +348,23 @@ fn update_egui_object(mut contexts:              EguiContexts,
         if ui.button("New Plane").clicked(){
           spawn_plane.send(SpawnNewPlaneEvent{pd: plane_data.clone()});
         }
+        if ui.button("Stitch Planes").clicked(){
+          stitch_planes.send(StitchPlanes);
+        }
+
+        ui.allocate_space(egui::Vec2::new(1.0, 20.0));
+        ui.collapsing("Rivers", |ui| {
+          rivers.settings_ui(ui);
+          if ui.button("Generate Rivers").clicked(){
+            generate_rivers.send(GenerateRivers);
+          }
+        });
+        ui.collapsing("Flow", |ui| {
+          // drawing the ui would mark settings as changed every frame and recolor all planes
+          if flow.bypass_change_detection().ui(ui) {
+            flow.set_changed();
+          }
+        });
 
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
         ui.separator();
@@ -350,13 +381,20 @@ fn update_egui_object(mut contexts:              EguiContexts,
           if ui.button("Load").clicked(){
             load_data.send(LoadData);
           }
+          ui.collapsing("Export/Import", |ui| {
+            export.0.ui(ui, &mut export.1, &mut export.2);
+          });
+          io_status.ui(ui);
 
         });
 
         ui.allocate_space(egui::Vec2::new(1.0, 20.0));
         ui.separator();
 
-        settings.ui(ui);
+        // drawing the ui would mark settings as changed every frame, see is_settings_changed
+        if settings.bypass_change_detection().ui(ui) {
+          settings.set_changed();
+        }
 
         ui.allocate_rect(ui.available_rect_before_wrap(), egui::Sense::hover());
     })
@@ -369,11 +407,12 @@ fn update_egui_object(mut contexts:              EguiContexts,
 
 
 
+#[allow(clippy::too_many_arguments)]
 fn update_left_into_panel(mut commands:  Commands,
                           hover_data:    Res<HoverData>,
                           ass:           Res<AssetServer>,
                           app_state:     Res<State<AppState>>,
-                          mod_state:     Res<State<ModifierState>>,
+                          mod_res:       Res<ModResources>,
                           planes:        Query<&PlaneData>,
                           hover_vertex:  Query<&Vertex, With<HoveredVertex>>,
                           top_left:      Query<Entity, With<TopLeftInfoPanel>>){
@@ -383,7 +422,7 @@ fn update_left_into_panel(mut commands:  Commands,
 
   let mut v: Vec<Entity> = Vec::new();
   v.push(spawn_text_node(&format!("    App State: {:?}", app_state.get()), &mut commands, &ass));  
-  v.push(spawn_text_node(&format!("    Modifier: {:?}", mod_state.get()), &mut commands, &ass));  
+  v.push(spawn_text_node(&format!("    Modifier: {}", mod_res.modifier), &mut commands, &ass));  
   v.push(spawn_text_node(&format!("    Planes Count: {:?}", planes.iter().len()), &mut commands, &ass));  
   v.push(spawn_text_node(&format!("    Tile: {:?}", hover_data.hovered_tile_xz), &mut commands, &ass));  
   v.push(spawn_text_node(&format!("    Pos: ({:.0}, {:.0})",  hover_data.hovered_xz.0, hover_data.hovered_xz.1), &mut commands, &ass)); 
@@ -445,5 +484,8 @@ fn change_picker(keys:                Res<Input<KeyCode>>,
   if keys.just_pressed(KeyCode::Key3){
       picker_state.set(PickerState::Brush);
   }
+  if keys.just_pressed(KeyCode::Key4){
+      picker_state.set(PickerState::Sculpt);
+  }
 
 }
\ No newline at end of file
@@ -0,0 +1,247 @@
+use bevy::input::common_conditions::{input_just_pressed, input_just_released, input_pressed};
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Context, DragValue, RichText, Ui};
+use std::slice::Iter;
+
+use crate::core::easings::Easings;
+use crate::core::utils::get_distance_euclidean;
+use crate::core::vertex::Vertex;
+use super::AppState;
+use super::actions::History;
+use super::brush::BrushSettings;
+use super::colors::{Colors, f32_to_clr32};
+use super::mtb_grid::{HoverData, Hoverables, hover_check};
+use super::mtb_ui::PickerState;
+
+// Vertex color painting in brush picker, each stroke is one undo step
+pub struct PaintPlugin;
+
+impl Plugin for PaintPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(PaintSettings::new())
+        .insert_resource(PaintStroke::new())
+        .add_systems(Update, start_stroke.after(hover_check)
+                                         .run_if(input_just_pressed(MouseButton::Left)
+                                         .and_then(is_painting)
+                                         .and_then(in_state(AppState::Edit))))
+        .add_systems(Update, paint.after(start_stroke)
+                                  .run_if(input_pressed(MouseButton::Left)
+                                  .and_then(is_painting)
+                                  .and_then(in_state(AppState::Edit))))
+        .add_systems(Update, end_stroke.run_if(input_just_released(MouseButton::Left)))
+        ;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrushMode {
+    Select,
+    Paint
+}
+
+impl BrushMode {
+    pub fn iterator() -> Iter<'static, BrushMode> {
+        static BRUSH_OPTIONS: [BrushMode; 2] = [BrushMode::Select, BrushMode::Paint];
+        BRUSH_OPTIONS.iter()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Add,
+    Erase     // back to white
+}
+
+impl BlendMode {
+    pub fn iterator() -> Iter<'static, BlendMode> {
+        static BLEND_OPTIONS: [BlendMode; 4] = [BlendMode::Normal, BlendMode::Multiply, BlendMode::Add, BlendMode::Erase];
+        BLEND_OPTIONS.iter()
+    }
+
+    pub fn blend(&self, clr: &[f32; 4], paint: &[f32; 4], w: f32) -> [f32; 4] {
+        let mut out = *clr;
+        for i in 0..4 {
+            let target = match self {
+                BlendMode::Normal   => {paint[i]}
+                BlendMode::Multiply => {clr[i]*paint[i]}
+                BlendMode::Add      => {(clr[i] + paint[i]).min(1.0)}
+                BlendMode::Erase    => {1.0}
+            };
+            out[i] = clr[i] + (target - clr[i])*w;
+        }
+        return out;
+    }
+}
+
+// Brush radius is shared with selection brush
+#[derive(Resource, Debug, Clone)]
+pub struct PaintSettings {
+    pub mode:       BrushMode,
+    pub blend:      BlendMode,
+    pub color:      [f32; 4],
+    pub strength:   f32,        // 0..1, blend factor of a full dab
+    pub falloff:    Easings,
+    pub spacing:    f32,        // distance the cursor moves between dabs
+    pub eyedropper: bool,       // next click picks color from the terrain
+    pub open:       bool
+}
+
+impl PaintSettings {
+    fn new() -> Self {
+        PaintSettings{mode:       BrushMode::Select,
+                      blend:      BlendMode::Normal,
+                      color:      [1.0, 1.0, 1.0, 1.0],
+                      strength:   0.5,
+                      falloff:    Easings::SmoothStep,
+                      spacing:    2.0,
+                      eyedropper: false,
+                      open:       false}
+    }
+
+    pub fn ui(&mut self, ctx: &Context, ui: &mut Ui, colors: &mut Colors) {
+        ui.horizontal(|ui| {
+          ui.label("Brush:");
+          for &p in BrushMode::iterator(){
+            ui.radio_value(&mut self.mode, p, format!("{p:?}"));
+          }
+        });
+        if self.mode == BrushMode::Select {
+          return;
+        }
+
+        let color = f32_to_clr32(&self.color);
+        ui.horizontal(|ui| {
+          ui.label("Color:");
+          ui.toggle_value(&mut self.open,
+                          RichText::new("_____________")
+                          .background_color(color)
+                          .color(color));
+          ui.toggle_value(&mut self.eyedropper, "Eyedropper");
+        });
+        colors.show(ctx, &mut self.open, "Paint");
+        if self.open {
+          self.color = colors.input;
+        }
+
+        ui.horizontal_wrapped(|ui| {
+          ui.label("Blend:");
+          for &p in BlendMode::iterator(){
+            ui.radio_value(&mut self.blend, p, format!("{p:?}"));
+          }
+        });
+        ui.columns(2, |columns| {
+          columns[1].label("Strength");
+          columns[0].add(DragValue::new(&mut self.strength).speed(0.01).clamp_range(0.0..=1.0));
+          columns[1].label("Spacing");
+          columns[0].add(DragValue::new(&mut self.spacing).speed(0.1).clamp_range(0.0..=f32::MAX));
+        });
+        egui::ComboBox::from_label("Paint falloff")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.falloff))
+        .show_ui(ui, |ui| {
+          for &p in Easings::iterator(){
+            ui.selectable_value(&mut self.falloff, p, format!("{p:?}"));
+          }
+        });
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct PaintStroke {
+    pub active:  bool,
+    pub last:    Option<(f32, f32)>   // position of the last dab
+}
+
+impl PaintStroke {
+    fn new() -> Self {
+        PaintStroke{active: false, last: None}
+    }
+}
+
+pub fn is_painting(settings:      Res<PaintSettings>,
+                   picker_state:  Res<State<PickerState>>) -> bool {
+    *picker_state.get() == PickerState::Brush && settings.mode == BrushMode::Paint
+}
+
+// picks the color of the closest vertex under the cursor
+fn eyedropper(center:        (f32, f32),
+              settings:      &mut PaintSettings,
+              colors:        &mut Colors,
+              vertex:        &Query<(&GlobalTransform, &Vertex)>){
+
+    let closest = vertex.iter().min_by(|(a, _), (b, _)| {
+        let (a, b) = (a.translation(), b.translation());
+        get_distance_euclidean(&center, &(a.x, a.z)).total_cmp(&get_distance_euclidean(&center, &(b.x, b.z)))
+    });
+    if let Some((_gtr, v)) = closest {
+        settings.color = v.clr;
+        colors.input = v.clr;
+        colors.addf32(&v.clr);
+    }
+    settings.eyedropper = false;
+}
+
+fn start_stroke(hover_data:        Res<HoverData>,
+                mut settings:      ResMut<PaintSettings>,
+                mut colors:        ResMut<Colors>,
+                mut stroke:        ResMut<PaintStroke>,
+                mut history:       ResMut<History>,
+                colored:           Query<(&GlobalTransform, &Vertex)>){
+
+    if let Hoverables::Gui = hover_data.hoverable {
+        return;
+    }
+    // eyedropper click is not a stroke
+    if settings.eyedropper {
+        eyedropper(hover_data.hovered_xz, &mut settings, &mut colors, &colored);
+        return;
+    }
+    history.set_label("Paint");
+    stroke.active = true;
+    stroke.last = None;
+}
+
+fn end_stroke(mut stroke:    ResMut<PaintStroke>,
+              settings:      Res<PaintSettings>,
+              mut colors:    ResMut<Colors>){
+    if stroke.active {
+        stroke.active = false;
+        stroke.last = None;
+        colors.addf32(&settings.color);
+    }
+}
+
+fn paint(hover_data:        Res<HoverData>,
+         brush_settings:    Res<BrushSettings>,
+         settings:          Res<PaintSettings>,
+         mut stroke:        ResMut<PaintStroke>,
+         mut vertex:        Query<(&GlobalTransform, &mut Vertex)>){
+
+    if !stroke.active {
+        return;
+    }
+    let center = hover_data.hovered_xz;
+    if let Some(last) = stroke.last {
+        if get_distance_euclidean(&last, &center) < settings.spacing.max(0.01) {
+            return;
+        }
+    }
+    stroke.last = Some(center);
+
+    let radius = brush_settings.radius;
+    let strength = settings.strength.clamp(0.0, 1.0);
+    for (gtr, mut v) in vertex.iter_mut(){
+        let pos = gtr.translation();
+        let dist = get_distance_euclidean(&center, &(pos.x, pos.z));
+        if dist >= radius {
+            continue;
+        }
+        let w = settings.falloff.apply(1.0 - dist/radius)*strength;
+        if w > 0.0 {
+            v.clr = settings.blend.blend(&v.clr, &settings.color, w);
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use std::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
+use bevy::prelude::*;
+use bevy::input::common_conditions::{input_pressed, input_just_pressed};
+use bevy_egui::EguiContexts;
+use bevy_egui::egui::{DragValue, Window};
+use bevy_mod_picking::prelude::*;
+use bevy::utils::HashSet;
+use crate::core::planes::{PlaneData, TerrainPlane, plane_mesh, unique_label};
+use crate::core::save::SavePlaneData;
+use crate::core::stitch::stitch_planes;
+use crate::core::terrain::update_terrain_normals;
+use crate::core::layers::ModifierLayer;
+use crate::core::modifier::ModifierRegistry;
+use crate::core::rivers::Rivers;
+use crate::core::utils::{AABB, get_mesh_stats};
+use crate::core::vertex::Vertex;
+use super::{AppState, DoubleClick};
+use super::actions::{History, begin_action};
+use super::colors::Colors;
+use super::layers::{EvaluateLayers, evaluate_layers};
+use super::mtb_grid::{hover_check, HoverData, Hoverables};
+
+pub struct PlanesPlugin;
+
+impl Plugin for PlanesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_event::<SpawnNewPlaneEvent>()
+        .add_event::<PickPlane>()
+        .add_event::<EvaluateLayers>()
+        .add_event::<StitchPlanes>()
+        .add_event::<GenerateRivers>()
+        .init_resource::<Rivers>()
+        .add_systems(PreUpdate,  clear.after(begin_action).run_if(input_just_pressed(MouseButton::Right)).run_if(in_state(AppState::Object)))
+        .add_systems(Update,     spawn_new_plane.run_if(on_event::<SpawnNewPlaneEvent>()).run_if(in_state(AppState::Object)))
+        .add_systems(Update,     pick_plane.run_if(in_state(AppState::Object)))
+        .add_systems(Update,     drag.run_if(input_pressed(MouseButton::Left)
+                                 .and_then(in_state(AppState::Object)))
+                                 .after(hover_check))
+        .add_systems(PostUpdate, highlight_picked_plane
+                                        .after(pick_plane)
+                                        .run_if(in_state(AppState::Object))
+                                    )
+        .add_systems(Update,     drop_plane.run_if(input_just_pressed(KeyCode::Back)))
+        .add_systems(OnExit(AppState::Object), deselect_plane)
+
+        .add_systems(PostUpdate, spawn_edit_plane.run_if(in_state(AppState::Object).and_then(on_event::<DoubleClick>())))
+        .add_systems(PostUpdate, edit_plane.run_if(in_state(AppState::Object)))
+        .add_systems(PostUpdate, evaluate_layers.after(edit_plane).run_if(on_event::<EvaluateLayers>()))
+        .add_systems(PostUpdate, stitch.after(evaluate_layers).run_if(on_event::<StitchPlanes>()))
+        .add_systems(PostUpdate, rivers.after(stitch).run_if(on_event::<GenerateRivers>()))
+        .add_systems(Last,       update_terrain_normals)
+        ;
+    }
+  }
+
+  pub fn spawn_edit_plane(hover_data:        Res<HoverData>,
+                          mut planes:        Query<&mut PlaneEdit>) {
+    
+      if let Hoverables::Entity(entity) = hover_data.hoverable {
+          if let Ok(mut pd) = planes.get_mut(entity) {
+              pd.0 = true;
+          }
+      }
+      
+    }
+
+  pub fn edit_plane(mut contexts:      EguiContexts,
+                    registry:          Res<ModifierRegistry>,
+                    mut colors:        ResMut<Colors>,
+                    mut evaluate:      EventWriter<EvaluateLayers>,
+                    mut planes:        Query<(Entity, &mut PlaneData, &mut Transform, &mut AABB,  &mut PlaneEdit)>){
+
+    let ctx = contexts.ctx_mut();
+    for (entity, mut pd, mut tr, mut aabb, mut pe) in planes.iter_mut() {
+        if !pe.0 {
+            continue;
+        }
+
+        Window::new(format!("Plane Edit {}", pd.label))
+                .open(&mut pe.0)
+                .resizable(true)
+                .default_width(280.0)
+                .show(ctx, |ui| {
+                    
+                    ui.columns(2, |columns| {
+                        columns[0].label("Loc X ");
+                        columns[1].add(DragValue::new(&mut pd.loc[0]).speed(1.0));
+                        columns[0].label("Loc Y ");
+                        columns[1].add(DragValue::new(&mut pd.loc[1]).speed(1.0));
+                        columns[0].label("Loc Z ");
+                        columns[1].add(DragValue::new(&mut pd.loc[2]).speed(1.0));
+                    }
+                );
+
+                ui.separator();
+                let previous = pd.layers.clone();
+                if ModifierLayer::ui(ctx, ui, &mut pd.layers, &registry, &mut colors) {
+                    evaluate.send(EvaluateLayers{entity, previous});
+                }
+
+        });
+
+        tr.translation.x = pd.loc[0];  
+        tr.translation.y = pd.loc[1];  
+        tr.translation.z = pd.loc[2];   
+        *aabb = pd.get_aabb();
+    }
+  }
+
+
+  pub fn drop_plane(mut commands: Commands, 
+                    planes:       Query<(Entity, &PickedPlane)>) {
+    for (entity, picked) in planes.iter(){
+        if picked.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+  }
+
+  #[derive(Event)]
+  pub struct PickPlane {
+    pub entity: Entity
+  }
+
+  impl  From<ListenerInput<Pointer<Down>>> for PickPlane {
+    fn from(event: ListenerInput<Pointer<Down>>) -> Self {
+        PickPlane{entity: event.target}
+    }
+}
+
+  #[derive(Event)]
+  pub struct SpawnNewPlaneEvent {
+    pub pd: PlaneData
+  }
+  impl Default for SpawnNewPlaneEvent {
+      fn default() -> Self {
+          SpawnNewPlaneEvent::new()
+      }
+  }
+
+  impl SpawnNewPlaneEvent {
+    pub fn new() -> Self {
+        SpawnNewPlaneEvent{pd: PlaneData::new()}
+    }
+  }
+
+#[derive(Component)]
+pub struct PickedPlane(pub bool);
+
+// Click on grid in object mode
+fn clear(mut planes: Query<&mut PickedPlane>){
+    for mut picked in planes.iter_mut(){
+        picked.0 = false;
+    }
+}
+
+
+pub fn pick_plane(mut pick_plane_event:    EventReader<PickPlane>,
+                  mut planes:              Query<&mut PickedPlane>){
+    for ev in pick_plane_event.iter(){
+        if let Ok(mut picked) = planes.get_mut(ev.entity){
+            picked.0 = true;
+        }
+    }
+}
+
+// it shouldnt work all the time
+pub fn highlight_picked_plane(
+    mut materials:         ResMut<Assets<StandardMaterial>>,
+    planes:                Query<(&mut Handle<StandardMaterial>, &PickedPlane), Changed<PickedPlane>>){
+    
+    for (handle_mat, picked) in planes.iter(){
+        if let Some(mat) = materials.get_mut(handle_mat){
+            if picked.0 {
+                mat.base_color.set_g(0.4);
+                mat.base_color.set_b(0.4);
+            } else {
+                mat.base_color.set_g(1.0);
+                mat.base_color.set_b(1.0);
+            }
+        }
+    }
+}
+  pub fn spawn_new_plane(mut commands:     Commands, 
+                         mut meshes:       ResMut<Assets<Mesh>>,
+                         mut materials:    ResMut<Assets<StandardMaterial>>,
+                         mut spawn_plane:  EventReader<SpawnNewPlaneEvent>,
+                         planes:           Query<&PlaneData, With<TerrainPlane>>
+                        ){
+    // labels have to stay unique, planes are matched by label on reload
+    let mut labels: HashSet<String> = planes.iter().map(|pd| pd.label.clone()).collect();
+    for ev in spawn_plane.iter(){
+        let mut pd = ev.pd.clone();
+        pd.label = unique_label(&pd.label, &labels);
+        labels.insert(pd.label.clone());
+        let _entity = pd.spawn(&mut commands, &mut meshes, &mut materials);
+    }
+  }
+
+#[derive(Component)]
+pub struct PlaneEdit(pub bool);
+
+static NEXT_PLANE_ID: AtomicU32 = AtomicU32::new(0);
+
+// Identifies plane over respawns (undo of delete), entities and labels can't be used for that
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlaneId(pub u32);
+
+impl Default for PlaneId {
+    fn default() -> Self {
+        PlaneId::new()
+    }
+}
+
+impl PlaneId {
+    pub fn new() -> Self {
+        PlaneId(NEXT_PLANE_ID.fetch_add(1, AtomicOrdering::Relaxed))
+    }
+}
+
+impl PlaneData {
+    #[allow(clippy::default_constructed_unit_structs)]
+    pub fn spawn(&self,
+                 commands:           &mut Commands, 
+                 meshes:             &mut ResMut<Assets<Mesh>>,
+                 materials:          &mut ResMut<Assets<StandardMaterial>>) -> Entity {
+
+        let mesh = plane_mesh(&self.subdivisions, &self.dims);
+        get_mesh_stats(&mesh);
+    
+        let entity = commands.spawn((PbrBundle {
+            material: materials.add(StandardMaterial{alpha_mode: AlphaMode::Mask(0.5),..default()}),
+            mesh: meshes.add(mesh),
+            transform: Transform::from_translation(self.loc.into()),
+            ..default()
+            },
+            PickedPlane(false),
+            PlaneId::new(),
+            TerrainPlane,
+            PlaneEdit(false),
+            PickableBundle::default(),
+            RaycastPickTarget::default(),
+            On::<Pointer<Down>>::send_event::<PickPlane>(),
+            self.clone(),
+            self.get_aabb()
+        )).id();
+    
+        return entity;
+    }
+}
+
+pub fn deselect_plane(mut materials:    ResMut<Assets<StandardMaterial>>,
+                      mut planes:       Query<(&mut Handle<StandardMaterial>, &mut PickedPlane)>){
+    for (handle_mat, mut picked) in planes.iter_mut(){
+        if picked.0 {
+            picked.0 = false;
+            if let Some(mat) = materials.get_mut(&handle_mat){
+                mat.base_color.set_g(1.0);
+                mat.base_color.set_b(1.0);
+            }
+
+        }
+    }
+}
+
+pub fn drag(mut picked_plane:  Query<(&mut Transform, &mut AABB, &mut PlaneData, &PickedPlane)>, 
+            hover_data:        Res<HoverData>){
+
+    let delta_x = hover_data.hovered_xz.0 - hover_data.old_hovered_xz.0;
+    let delta_y = hover_data.hovered_xz.1 - hover_data.old_hovered_xz.1;
+
+    for (mut tr, mut aabb, mut pd, picked)  in picked_plane.iter_mut(){
+        if picked.0 {
+            tr.translation.x += delta_x;
+            tr.translation.z += delta_y;
+    
+            pd.loc[0] = tr.translation.x;
+            pd.loc[2] = tr.translation.z;
+    
+            *aabb = pd.get_aabb();
+        }
+    }
+
+}
+
+
+#[derive(Event)]
+pub struct StitchPlanes;
+
+#[derive(Event)]
+pub struct GenerateRivers;
+
+// Welds borders of adjacent planes and writes the result back into vertices and meshes
+pub fn stitch(mut stitch_events:  EventReader<StitchPlanes>,
+              mut history:        ResMut<History>,
+              planes:             Query<(&PlaneData, &Children, &Handle<Mesh>)>,
+              mut vertex:         Query<(&mut Transform, &mut Vertex)>,
+              mut meshes:         ResMut<Assets<Mesh>>){
+
+    stitch_events.clear();
+    history.set_label("Stitch planes");
+    edit_world(&planes, &mut vertex, &mut meshes, |v_planes| {
+        let count = stitch_planes(v_planes);
+        info!("Stitched {} edges", count);
+    });
+}
+
+// Carves river network over all planes
+pub fn rivers(mut river_events:  EventReader<GenerateRivers>,
+              rivers:            Res<Rivers>,
+              mut history:       ResMut<History>,
+              planes:            Query<(&PlaneData, &Children, &Handle<Mesh>)>,
+              mut vertex:        Query<(&mut Transform, &mut Vertex)>,
+              mut meshes:        ResMut<Assets<Mesh>>){
+
+    river_events.clear();
+    history.set_label("Generate rivers");
+    edit_world(&planes, &mut vertex, &mut meshes, |v_planes| {
+        let count = rivers.apply(v_planes);
+        info!("Generated rivers from {} sources", count);
+    });
+}
+
+// Runs edit over current vertices of all planes, then writes the result back into vertices and meshes
+fn edit_world<F: FnOnce(&mut Vec<SavePlaneData>)>(planes:  &Query<(&PlaneData, &Children, &Handle<Mesh>)>,
+                                                  vertex:  &mut Query<(&mut Transform, &mut Vertex)>,
+                                                  meshes:  &mut Assets<Mesh>,
+                                                  edit:    F){
+
+    let mut v_planes: Vec<SavePlaneData> = Vec::new();
+    let mut v_entities: Vec<(&Children, &Handle<Mesh>)> = Vec::new();
+    for (pd, children, handle_mesh) in planes.iter(){
+        let mut spd = SavePlaneData::from_pd(pd);
+        spd.vertex = pd.base_vertices();
+        for child in children.iter(){
+            if let Ok((_tr, v)) = vertex.get(*child){
+                if let Some(sv) = spd.vertex.get_mut(v.index) {
+                    *sv = *v;
+                }
+            }
+        }
+        v_planes.push(spd);
+        v_entities.push((children, handle_mesh));
+    }
+
+    edit(&mut v_planes);
+
+    for (spd, (children, handle_mesh)) in v_planes.iter().zip(v_entities.iter()){
+        for child in children.iter(){
+            if let Ok((mut tr, mut v)) = vertex.get_mut(*child){
+                if let Some(sv) = spd.vertex.get(v.index) {
+                    *v = *sv;
+                    tr.translation = v.loc.into();
+                }
+            }
+        }
+        if let Some(plane_mesh) = meshes.get_mut(handle_mesh) {
+            let v_pos: Vec<[f32; 3]> = spd.vertex.iter().map(|v| v.loc).collect();
+            let v_clr: Vec<[f32; 4]> = spd.vertex.iter().map(|v| v.clr).collect();
+            plane_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
+            plane_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, v_clr);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::input::common_conditions::{input_just_pressed, input_just_released, input_pressed};
+use bevy::prelude::*;
+use bevy_egui::egui::{self, DragValue, Ui};
+use noise::{NoiseFn, Perlin};
+use std::slice::Iter;
+
+use crate::core::easings::Easings;
+use crate::core::utils::get_distance_euclidean;
+use crate::core::vertex::Vertex;
+use super::AppState;
+use super::actions::History;
+use super::brush::{BrushSettings, despawn_brush, spawn_brush, update_brush};
+use super::mtb_grid::{HoverData, Hoverables, hover_check};
+use super::mtb_ui::PickerState;
+
+// Sculpt brushes change vertex heights while left mouse is held, each stroke is one undo step (see actions)
+pub struct SculptPlugin;
+
+impl Plugin for SculptPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(SculptSettings::new())
+        .insert_resource(SculptStroke::new())
+        .add_systems(OnEnter(PickerState::Sculpt), spawn_brush)
+        .add_systems(OnExit(PickerState::Sculpt), despawn_brush)
+        .add_systems(Update, update_brush.run_if(in_state(PickerState::Sculpt)))
+        .add_systems(Update, start_stroke.after(hover_check)
+                                         .run_if(input_just_pressed(MouseButton::Left)
+                                         .and_then(in_state(PickerState::Sculpt))
+                                         .and_then(in_state(AppState::Edit))))
+        .add_systems(Update, sculpt.after(start_stroke)
+                                   .run_if(input_pressed(MouseButton::Left)
+                                   .and_then(in_state(PickerState::Sculpt))
+                                   .and_then(in_state(AppState::Edit))))
+        .add_systems(Update, end_stroke.run_if(input_just_released(MouseButton::Left)))
+        ;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SculptMode {
+    Raise,
+    Lower,
+    Smooth,
+    Flatten,  // to the height sampled at the start of the stroke
+    Noise
+}
+
+impl SculptMode {
+    pub fn iterator() -> Iter<'static, SculptMode> {
+        static SCULPT_OPTIONS: [SculptMode; 5] = [SculptMode::Raise, SculptMode::Lower, SculptMode::Smooth, SculptMode::Flatten, SculptMode::Noise];
+        SCULPT_OPTIONS.iter()
+    }
+}
+
+// Brush radius is shared with selection brush
+#[derive(Resource, Debug, Clone)]
+pub struct SculptSettings {
+    pub mode:        SculptMode,
+    pub strength:    f32,      // 0..1, fraction of the full effect per dab
+    pub height:      f32,      // height change of full dab for raise, lower and noise
+    pub falloff:     Easings,
+    pub spacing:     f32,      // distance the cursor moves between dabs
+    pub noise_scale: f32,
+    pub seed:        u32
+}
+
+impl SculptSettings {
+    fn new() -> Self {
+        SculptSettings{mode:        SculptMode::Raise,
+                       strength:    0.5,
+                       height:      2.0,
+                       falloff:     Easings::SmoothStep,
+                       spacing:     2.0,
+                       noise_scale: 0.05,
+                       seed:        0}
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+          ui.label("Sculpt:");
+          for &p in SculptMode::iterator(){
+            ui.radio_value(&mut self.mode, p, format!("{p:?}"));
+          }
+        });
+        ui.columns(2, |columns| {
+          columns[1].label("Strength");
+          columns[0].add(DragValue::new(&mut self.strength).speed(0.01).clamp_range(0.0..=1.0));
+          if let SculptMode::Raise | SculptMode::Lower | SculptMode::Noise = self.mode {
+            columns[1].label("Height");
+            columns[0].add(DragValue::new(&mut self.height).speed(0.1).clamp_range(0.0..=f32::MAX));
+          }
+          columns[1].label("Spacing");
+          columns[0].add(DragValue::new(&mut self.spacing).speed(0.1).clamp_range(0.0..=f32::MAX));
+          if let SculptMode::Noise = self.mode {
+            columns[1].label("Noise scale");
+            columns[0].add(DragValue::new(&mut self.noise_scale).speed(0.001).clamp_range(0.0..=f32::MAX));
+            columns[1].label("Seed");
+            columns[0].add(DragValue::new(&mut self.seed).speed(1.0));
+          }
+        });
+        egui::ComboBox::from_label("Falloff")
+        .width(140.0)
+        .selected_text(format!("{:?}", self.falloff))
+        .show_ui(ui, |ui| {
+          for &p in Easings::iterator(){
+            ui.selectable_value(&mut self.falloff, p, format!("{p:?}"));
+          }
+        });
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct SculptStroke {
+    pub active:   bool,
+    pub last:     Option<(f32, f32)>,   // position of the last dab
+    pub height:   f32                   // sampled height for flatten
+}
+
+impl SculptStroke {
+    fn new() -> Self {
+        SculptStroke{active: false, last: None, height: 0.0}
+    }
+}
+
+fn start_stroke(hover_data:        Res<HoverData>,
+                brush_settings:    Res<BrushSettings>,
+                settings:          Res<SculptSettings>,
+                mut stroke:        ResMut<SculptStroke>,
+                mut history:       ResMut<History>,
+                gtrs:              Query<&GlobalTransform, With<Vertex>>){
+
+    if let Hoverables::Gui = hover_data.hoverable {
+        return;
+    }
+    history.set_label(&format!("Sculpt {:?}", settings.mode));
+
+    // weighted average height under the brush
+    let center = hover_data.hovered_xz;
+    let mut sum: f32 = 0.0;
+    let mut weights: f32 = 0.0;
+    for gtr in gtrs.iter(){
+        let tr = gtr.translation();
+        let dist = get_distance_euclidean(&center, &(tr.x, tr.z));
+        if dist < brush_settings.radius {
+            let w = 1.0 - dist/brush_settings.radius;
+            sum += tr.y*w;
+            weights += w;
+        }
+    }
+    stroke.active = true;
+    stroke.last = None;
+    stroke.height = match weights > 0.0 {
+        true  => {sum/weights}
+        false => {0.0}
+    };
+}
+
+fn end_stroke(mut stroke: ResMut<SculptStroke>){
+    if stroke.active {
+        stroke.active = false;
+        stroke.last = None;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn sculpt(hover_data:        Res<HoverData>,
+          brush_settings:    Res<BrushSettings>,
+          settings:          Res<SculptSettings>,
+          mut stroke:        ResMut<SculptStroke>,
+          mut vertex:        Query<(&GlobalTransform, &mut Transform), With<Vertex>>){
+
+    if !stroke.active {
+        return;
+    }
+    let center = hover_data.hovered_xz;
+    if let Some(last) = stroke.last {
+        if get_distance_euclidean(&last, &center) < settings.spacing.max(0.01) {
+            return;
+        }
+    }
+    stroke.last = Some(center);
+
+    let radius = brush_settings.radius;
+    let mut dab: Vec<(Mut<Transform>, f32, f32, (f32, f32))> = Vec::new(); // transform, world height, weight, world xz
+    for (gtr, tr) in vertex.iter_mut(){
+        let pos = gtr.translation();
+        let dist = get_distance_euclidean(&center, &(pos.x, pos.z));
+        if dist < radius {
+            dab.push((tr, pos.y, settings.falloff.apply(1.0 - dist/radius), (pos.x, pos.z)));
+        }
+    }
+    if dab.is_empty() {
+        return;
+    }
+
+    let strength = settings.strength.clamp(0.0, 1.0);
+    let average: f32 = dab.iter().map(|(_, h, w, _)| h*w).sum::<f32>()/dab.iter().map(|(_, _, w, _)| w).sum::<f32>().max(1e-6);
+    let perlin = Perlin::new(settings.seed);
+
+    for (tr, h, w, xz) in dab.iter_mut(){
+        let delta = match settings.mode {
+            SculptMode::Raise   => {settings.height*strength*(*w)}
+            SculptMode::Lower   => {-settings.height*strength*(*w)}
+            SculptMode::Smooth  => {(average - *h)*strength*(*w)}
+            SculptMode::Flatten => {(stroke.height - *h)*strength*(*w)}
+            SculptMode::Noise   => {
+                let n = perlin.get([(xz.0*settings.noise_scale) as f64, (xz.1*settings.noise_scale) as f64]) as f32;
+                settings.height*strength*(*w)*n
+            }
+        };
+        if delta != 0.0 {
+            tr.translation.y += delta;
+        }
+    }
+}
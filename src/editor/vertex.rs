@@ -0,0 +1,506 @@
+use bevy::{prelude::*, input::common_conditions::{input_pressed, input_just_pressed}, render::mesh::VertexAttributeValues, pbr::NotShadowReceiver};
+use bevy::pbr::NotShadowCaster;
+use bevy::utils::HashMap;
+use bevy_mod_picking::prelude::*;
+use crate::core::planes::{PlaneData, TerrainPlane};
+use crate::core::color::ColorGradient;
+use crate::core::easings::Easings;
+use crate::core::layers::ModifierLayer;
+use crate::core::modifier::ModifierRegistry;
+use crate::core::utils::get_distance_euclidean;
+use crate::core::vertex::Vertex;
+use super::{mtb_grid::{HoverData, hover_check, Hoverables}, 
+            mtb_ui::{PickerState, ApplyModifierEvent, ModResources, ModifierReport}, AppState, DoubleClick, GlobalSettings, is_settings_changed};
+use super::actions::{History, begin_action};
+use super::paint::is_painting;
+
+const WEIGHT_STEPS: usize = 8;
+
+
+pub struct VertexPlugin;
+
+impl Plugin for VertexPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_event::<PickVertex>()
+        .add_event::<HoverVertex>()
+        .add_event::<DehoverVertex>()
+        .add_systems(Startup, setup)
+        .add_systems(Update, hover_vertex.run_if(on_event::<HoverVertex>()))
+        .add_systems(Update, dehover_vertex.run_if(on_event::<DehoverVertex>()))
+        .add_systems(Update, pick_vertex.run_if(on_event::<PickVertex>().and_then(in_state(AppState::Edit)).and_then(not(in_state(PickerState::Sculpt))).and_then(not(is_painting))).after(hover_vertex))
+
+        .add_systems(PreUpdate, clear.after(begin_action).run_if(input_just_pressed(MouseButton::Right)).run_if(in_state(AppState::Edit)))
+
+        .add_systems(PostUpdate, update_weights.after(pick_vertex).run_if(in_state(AppState::Edit)))
+        .add_systems(PostUpdate, highlight_picked.after(update_weights).run_if(in_state(AppState::Edit)))
+
+        .add_systems(Update, drag.run_if(input_pressed(MouseButton::Left)
+                                 .and_then(in_state(PickerState::Point))
+                                 .and_then(in_state(AppState::Edit))
+                                ).after(hover_check))
+        .add_systems(Update, apply_modifiers.run_if(in_state(AppState::Edit)))
+        .add_systems(PostUpdate, vertex_update_transform.after(drag).after(apply_modifiers).run_if(in_state(AppState::Edit)))
+
+        .add_systems(PostUpdate, vertex_update_vertex.after(apply_modifiers).run_if(in_state(AppState::Edit)))
+
+        .add_systems(OnExit(AppState::Edit), deselect_vertex)
+        .add_systems(PostUpdate, select_all.run_if(in_state(AppState::Edit).and_then(on_event::<DoubleClick>())))
+        .add_systems(Update, update_scale.run_if(is_settings_changed))
+
+        ;
+    }
+}
+
+pub fn hover_vertex(mut commands:       Commands,
+                    mut hover_vertex:   EventReader<HoverVertex>, 
+                    vertex:             Query<Entity, With<Vertex>>){
+    for ev in hover_vertex.iter(){
+        if let Ok(entity) = vertex.get(ev.entity){
+            commands.entity(entity).insert(HoveredVertex);
+        }
+    }
+}
+
+pub fn dehover_vertex(mut commands:       Commands,
+                      mut dehover_vertex: EventReader<DehoverVertex>,
+                      vertex:             Query<Entity, With<Vertex>>
+                    ){
+    for ev in dehover_vertex.iter(){
+        if let Ok(entity) = vertex.get(ev.entity){
+            commands.entity(entity).remove::<HoveredVertex>();
+        }
+    }
+}
+
+
+pub fn update_scale(settings:    Res<GlobalSettings>,
+                    mut vertex:  Query<&mut Transform, With<Vertex>>){
+
+    for mut tr in vertex.iter_mut(){
+        tr.scale = Vec3::splat(settings.vertex_radius);
+    }
+    
+}
+
+
+pub fn apply_modifiers(
+    mut apply_mod:      EventReader<ApplyModifierEvent>,
+    mod_res:            Res<ModResources>,
+    registry:           Res<ModifierRegistry>,
+    mut report:         ResMut<ModifierReport>,
+    mut history:        ResMut<History>,
+    mut planes:         Query<(&mut PlaneData, &Children)>,
+    mut picked_vertex:  Query<(&mut Transform, &mut Vertex, &SelectionWeight)>
+) {
+
+    for ev in apply_mod.iter(){
+        info!(" Applied modifier {}", ev.modifier);
+        history.set_label(&format!("Apply {}", ev.modifier));
+        let Some(modifier) = registry.modifier(&ev.modifier) else {
+            warn!("Unknown modifier: {}", ev.modifier);
+            continue;
+        };
+        let instance = registry.build(&modifier).ok();
+        report.modifier = ev.modifier.clone();
+        report.lines.clear();
+        let gradient = match mod_res.apply_gradient {
+            true  => {registry.get("ColorGradient").and_then(|m| serde_json::from_value::<ColorGradient>(m.to_value()).ok())}
+            false => {None}
+        };
+
+        for (mut pd, children) in planes.iter_mut(){
+
+            // current state of plane vertices ordered by index
+            let mut vertices: Vec<Vertex> = pd.base_vertices();
+            let mut selection: Vec<usize> = Vec::new();
+            let mut weights: Vec<f32> = Vec::new();
+            for child in children.iter(){
+                if let Ok((_tr, v, weight)) = picked_vertex.get(*child){
+                    if let Some(pv) = vertices.get_mut(v.index) {
+                        *pv = *v;
+                    }
+                    if weight.0 > 0.0 {
+                        selection.push(v.index);
+                        weights.push(weight.0);
+                    }
+                }
+            }
+
+            if selection.is_empty() {
+                continue; // nothing picked on this plane
+            }
+
+            if let Some(instance) = &instance {
+                report.lines.extend(instance.report(&pd, &vertices, &selection));
+            }
+            let mut layer = ModifierLayer::new(modifier.clone(), selection, gradient);
+            if weights.iter().any(|w| *w < 1.0) {
+                layer.weights = weights;
+            }
+            layer.apply(&registry, &pd, &mut vertices);
+
+            for child in children.iter(){
+                if let Ok((mut tr, mut v, weight)) = picked_vertex.get_mut(*child){
+                    if weight.0 <= 0.0 {
+                        continue; // not picked
+                    } 
+                    *v = vertices[v.index];
+                    if tr.translation != Vec3::from(v.loc) {
+                        tr.translation = v.loc.into();
+                    }
+                }
+            }
+            pd.layers.push(layer);
+        }
+    }
+}
+
+
+fn select_all(hover_data:        Res<HoverData>,
+              planes:            Query<&Children, With<TerrainPlane>>,
+              mut vertex:        Query<&mut PickedVertex, With<Vertex>>
+){
+    if let Hoverables::Entity(entity) = hover_data.hoverable {
+        if let Ok(plane_children) = planes.get(entity) {
+            for child in plane_children.iter(){
+                if let Ok(mut picked_vertex) = vertex.get_mut(*child){
+                    picked_vertex.0 = true;
+                } 
+            }
+        }
+    }
+
+}
+
+
+// Click on grid in edit mode
+fn clear(mut vertex: Query<&mut PickedVertex>){
+    for mut v in vertex.iter_mut(){
+      v.0 = false;
+    }
+}
+
+
+pub fn drag(mut picked_vertex: Query<(&mut Transform, &SelectionWeight)>, 
+            mod_res:           Res<ModResources>,
+            hover_data:        Res<HoverData>){
+
+    if !mod_res.allow_dragging {
+        return; // dragging not allowed;
+    }
+
+    let delta_x = hover_data.hovered_xz.0 - hover_data.old_hovered_xz.0;
+    let delta_y = hover_data.hovered_xz.1 - hover_data.old_hovered_xz.1;
+
+    for (mut tr, weight) in picked_vertex.iter_mut(){
+        if weight.0 > 0.0 {
+            tr.translation.x += delta_x*weight.0;
+            tr.translation.z += delta_y*weight.0;
+        }
+    }
+
+}
+
+
+pub fn vertex_update_transform(mut vertex: Query<(&Transform, &mut Vertex, &Parent), Changed<Transform>>,
+                               planes: Query<&Handle<Mesh>, With<TerrainPlane>>,
+                               mut meshes: ResMut<Assets<Mesh>>
+){
+    for (transform, mut vertex, parent) in vertex.iter_mut(){
+
+        if let Ok(plane_mesh_handle) = planes.get(**parent) {
+            if let Some(plane_mesh) = meshes.get_mut(plane_mesh_handle) {
+                let mut v_pos: Vec<[f32; 3]> = plane_mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap().to_vec();
+                v_pos[vertex.index] = transform.translation.into();
+                plane_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
+            }
+        }
+        vertex.loc = transform.translation.into();
+    }
+}
+
+pub fn vertex_update_vertex(changed_vertex: Query<(&Parent, &mut Vertex), Changed<Vertex>>,
+                            planes:         Query<(Entity, &Handle<Mesh>), With<TerrainPlane>>,
+                            mut meshes:     ResMut<Assets<Mesh>>
+){
+
+    if changed_vertex.is_empty(){
+        return; // no changes
+    }
+
+    for (plane_entity, handle_plane_mesh) in planes.iter(){
+        let mut v_clr: Option<Vec<[f32;4]>> = None;
+        #[allow(unused_assignments)]
+        let mut v_pos: Option<Vec<[f32; 3]>> = None;
+
+        if let Some(plane_mesh) = meshes.get_mut(handle_plane_mesh) {
+
+            v_pos = Some(plane_mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap().to_vec());
+            if let Some(attr_vcolor) = plane_mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+                if let VertexAttributeValues::Float32x4(vcolors) = attr_vcolor {
+                    v_clr = Some(vcolors.to_vec());
+                }
+            } else {
+                v_clr = Some(vec![[1.0, 1.0, 1.0, 1.0]; v_pos.as_ref().unwrap().len()]);
+            }
+
+            for (parent, vertex) in changed_vertex.iter() {
+                if plane_entity == parent.get() {
+                    v_pos.as_mut().unwrap()[vertex.index] = vertex.loc;
+                    v_clr.as_mut().unwrap()[vertex.index] = vertex.clr;
+                }
+            }
+
+            plane_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, v_pos.unwrap());
+            plane_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, v_clr.unwrap());
+        }
+    }
+}
+
+
+#[derive(Event)]
+pub struct PickVertex {
+    pub entity: Entity
+}
+impl  From<ListenerInput<Pointer<Down>>> for PickVertex {
+    fn from(event: ListenerInput<Pointer<Down>>) -> Self {
+        PickVertex{entity: event.target}
+    }
+}
+
+#[derive(Event)]
+pub struct HoverVertex {
+    pub entity: Entity
+}
+impl  From<ListenerInput<Pointer<Over>>> for HoverVertex {
+    fn from(event: ListenerInput<Pointer<Over>>) -> Self {
+        HoverVertex{entity: event.target}
+    }
+}
+
+
+#[derive(Event)]
+pub struct DehoverVertex {
+    pub entity: Entity
+}
+impl  From<ListenerInput<Pointer<Out>>> for DehoverVertex {
+    fn from(event: ListenerInput<Pointer<Out>>) -> Self {
+        DehoverVertex{entity: event.target}
+    }
+}
+
+
+#[derive(Resource)]
+pub struct VertexRefs {
+    pub mesh:           Handle<Mesh>,
+    pub mat:            Handle<StandardMaterial>,
+    pub picked_mat:     Handle<StandardMaterial>,
+    pub weight_mats:    Vec<Handle<StandardMaterial>>   // color ramp of soft selection weights
+}
+
+// spawn references
+pub fn setup(mut commands:     Commands,
+             mut materials:    ResMut<Assets<StandardMaterial>>,
+             mut meshes:       ResMut<Assets<Mesh>>,
+             settings:         Res<GlobalSettings>
+){
+    
+    let ref_loc: [f32;3] = [-5000.0, -5000.0, -5000.0]; // basically hell
+
+    // let ref_loc: [f32;3] = [0.0, 10.0, 0.0];
+    let default_vertex_material = materials.add(Color::BLACK.with_a(0.85).into());
+    let red_vertex_material = materials.add(Color::ORANGE_RED.with_a(0.85).into());
+    let default_vertex_mesh = meshes.add(shape::UVSphere{radius: 1.0, ..default()}.into());
+
+    commands.spawn((PbrBundle {
+        material: default_vertex_material.clone(),
+        mesh: default_vertex_mesh.clone(),
+        transform: Transform::from_translation(ref_loc.into())
+                             .with_scale(Vec3::splat(settings.vertex_radius)),
+        ..default()}, RefVertex));
+
+     commands.spawn((PbrBundle {
+            material: red_vertex_material.clone(),
+            mesh: default_vertex_mesh.clone(),
+            transform: Transform::from_translation(ref_loc.into())
+                                 .with_scale(Vec3::splat(settings.vertex_radius)),
+            ..default()}, RefVertex));
+
+    let weight_mats: Vec<Handle<StandardMaterial>> = (0..WEIGHT_STEPS).map(|step| {
+        let t = step as f32/(WEIGHT_STEPS - 1) as f32;
+        materials.add(Color::rgba(1.0, 1.0 - 0.73*t, 0.0, 0.4 + 0.45*t).into())
+    }).collect();
+
+    let refs = VertexRefs{mesh: default_vertex_mesh, mat: default_vertex_material, picked_mat: red_vertex_material, weight_mats};
+    commands.insert_resource(refs);
+
+}
+
+pub fn pick_vertex(mut pick_vertex_event: EventReader<PickVertex>,
+                   keys:                  Res<Input<KeyCode>>,
+                   mut vertex:            Query<(Entity, &mut PickedVertex), With<Vertex>>){
+    for ev in pick_vertex_event.iter(){
+
+        if let Ok((_entity, mut picked_vertex)) = vertex.get_mut(ev.entity){
+            picked_vertex.0 = true;
+            if !keys.pressed(KeyCode::ShiftLeft) {
+                for (entity, mut picked_vertex) in vertex.iter_mut(){
+                    if entity != ev.entity {
+                        picked_vertex.0 = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// it does it all the time in edit
+pub fn highlight_picked(
+    mut commands:          Commands,
+    vertex:                Query<(Entity, &SelectionWeight), Changed<SelectionWeight>>, 
+    refs:                  Res<VertexRefs>){
+
+    for (entity, weight) in vertex.iter(){
+        if weight.0 >= 1.0 {
+            commands.entity(entity).insert(refs.picked_mat.clone_weak());
+        } else if weight.0 > 0.0 {
+            let step = ((weight.0*(WEIGHT_STEPS - 1) as f32).round() as usize).min(WEIGHT_STEPS - 1);
+            commands.entity(entity).insert(refs.weight_mats[step].clone_weak());
+        } else {
+            commands.entity(entity).insert(refs.mat.clone_weak());
+        }
+    }
+}
+
+// Picked vertices get weight 1, with soft selection the weight falls off with distance to the closest picked vertex
+pub fn update_weights(mod_res:      Res<ModResources>,
+                      mut last:     Local<Option<(bool, f32, Easings)>>,   // falloff settings of the last update
+                      changed:      Query<(), Changed<PickedVertex>>,
+                      picked:       Query<(&GlobalTransform, &PickedVertex)>,
+                      mut vertex:   Query<(&GlobalTransform, &PickedVertex, &mut SelectionWeight)>){
+
+    // ModResources is changed by the editor ui every frame, only falloff settings matter here
+    let settings = Some((mod_res.soft_selection, mod_res.falloff_radius, mod_res.falloff));
+    if *last == settings && changed.is_empty() {
+        return;
+    }
+    *last = settings;
+
+    let radius = mod_res.falloff_radius;
+    let soft = mod_res.soft_selection && radius > 0.0;
+    let bucket = |x: f32, z: f32| -> (i64, i64) {((x/radius).floor() as i64, (z/radius).floor() as i64)};
+    let mut buckets: HashMap<(i64, i64), Vec<(f32, f32)>> = HashMap::new();
+    if soft {
+        for (gtr, p) in picked.iter(){
+            if p.0 {
+                let tr = gtr.translation();
+                buckets.entry(bucket(tr.x, tr.z)).or_default().push((tr.x, tr.z));
+            }
+        }
+    }
+
+    for (gtr, p, mut weight) in vertex.iter_mut(){
+        let mut w: f32 = 0.0;
+        if p.0 {
+            w = 1.0;
+        } else if soft {
+            let tr = gtr.translation();
+            let (bx, bz) = bucket(tr.x, tr.z);
+            let mut dist = f32::MAX;
+            for dx in -1..=1 {
+                for dz in -1..=1 {
+                    let Some(points) = buckets.get(&(bx+dx, bz+dz)) else {continue;};
+                    for point in points.iter(){
+                        dist = dist.min(get_distance_euclidean(point, &(tr.x, tr.z)));
+                    }
+                }
+            }
+            if dist < radius {
+                w = mod_res.falloff.apply(1.0 - dist/radius);
+            }
+        }
+        if weight.0 != w {
+            weight.0 = w;
+        }
+    }
+}
+
+
+
+
+
+#[derive(Component)]
+pub struct RefVertex;
+
+#[derive(Component)]
+pub struct PickedVertex(pub bool);
+
+// Soft selection weight, 1 for picked vertices, falls off around them
+#[derive(Component)]
+pub struct SelectionWeight(pub f32);
+
+#[derive(Component)]
+pub struct HoveredVertex;
+
+#[allow(clippy::default_constructed_unit_structs)]
+pub fn spawn_vertex(plane_entity: &Entity,
+                    commands:     &mut Commands, 
+                    handle_mesh:  &Handle<Mesh>, 
+                    meshes:       &mut ResMut<Assets<Mesh>>,
+                    refs:         &Res<VertexRefs>,
+                    settings:     &Res<GlobalSettings>
+                ){
+
+    info!("SPAWN VERTEX");
+    let plane_mesh = meshes.get_mut(handle_mesh).unwrap();
+
+    let v_pos: Vec<[f32; 3]> = plane_mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().as_float3().unwrap().to_vec();
+    let mut v_clr: Vec<[f32; 4]> = Vec::new();
+    if let Some(attr_vcolor) = plane_mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+        if let VertexAttributeValues::Float32x4(vcolors) = attr_vcolor {
+            v_clr = vcolors.to_vec();
+        }
+    } else {
+        v_clr = vec![[1.0, 1.0, 1.0, 1.0]; v_pos.len()];
+    }
+    
+    let mut vertices: Vec<Entity> = Vec::new();
+    for (index, pos) in v_pos.iter().enumerate(){
+
+        let entity = commands.spawn((PbrBundle {
+                                        material: refs.mat.clone_weak(),
+                                        mesh: refs.mesh.clone_weak(),
+                                    transform: Transform::from_translation((*pos).into())
+                                                         .with_scale(Vec3::splat(settings.vertex_radius)),
+                                    ..default()}, 
+                                    Vertex::new(index, pos, &v_clr[index]),
+                                    PickedVertex(false),
+                                    SelectionWeight(0.0),
+                                    NotShadowCaster,
+                                    NotShadowReceiver,
+                                    PickableBundle::default(),
+                                    RaycastPickTarget::default(),
+                                    On::<Pointer<Down>>::send_event::<PickVertex>(),
+                                    On::<Pointer<Over>>::send_event::<HoverVertex>(),
+                                    On::<Pointer<Out>>::send_event::<DehoverVertex>(),
+                                )).id();
+
+        commands.entity(entity).insert(Visibility::Hidden);
+        vertices.push(entity);
+
+    }
+
+    commands.entity(*plane_entity).push_children(&vertices);
+
+}
+
+pub fn deselect_vertex(mut commands:    Commands,
+                       mut vertex:      Query<(Entity, &mut PickedVertex)>,
+                       refs:            Res<VertexRefs>
+                    ){
+    for (entity, mut picked) in vertex.iter_mut(){
+        picked.0 = false;
+        commands.entity(entity).insert(refs.mat.clone_weak());
+    }
+}
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+
+use crate::core::layers::Modifier;
+use crate::core::modifier::ModifierRegistry;
+use crate::core::planes::{PlaneData, TerrainPlane};
+use crate::core::utils::AABB;
+use super::{GlobalSettings, is_settings_changed};
+
+const WATER_MARGIN: f32 = 500.0;
+
+// Water surface at project sea level, covering all planes
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Startup, spawn_water)
+        .add_systems(Update,  update_water)
+        .add_systems(Update,  sync_sea_level.run_if(is_settings_changed))
+        ;
+    }
+}
+
+#[derive(Component)]
+pub struct WaterPlane;
+
+fn spawn_water(mut commands:   Commands,
+               mut meshes:     ResMut<Assets<Mesh>>,
+               mut materials:  ResMut<Assets<StandardMaterial>>,
+               settings:       Res<GlobalSettings>){
+
+    let clr = settings.sea.color;
+    commands.spawn((PbrBundle {
+        mesh: meshes.add(shape::Plane::from_size(1.0).into()),
+        material: materials.add(StandardMaterial{base_color: Color::rgba(clr[0], clr[1], clr[2], clr[3]),
+                                                 alpha_mode: AlphaMode::Blend,
+                                                 perceptual_roughness: 0.1,
+                                                 ..default()}),
+        visibility: Visibility::Hidden,
+        ..default()
+        },
+        WaterPlane
+    ));
+}
+
+// follows sea settings and extent of the planes
+fn update_water(settings:       Res<GlobalSettings>,
+                planes:         Query<&PlaneData, With<TerrainPlane>>,
+                changed:        Query<(), Changed<PlaneData>>,
+                mut water:      Query<(&mut Transform, &mut Visibility, &Handle<StandardMaterial>), With<WaterPlane>>,
+                mut materials:  ResMut<Assets<StandardMaterial>>){
+
+    if !settings.is_changed() && changed.is_empty() {
+        return;
+    }
+    let Ok((mut tr, mut visibility, handle)) = water.get_single_mut() else {return;};
+
+    *visibility = match settings.sea.active {
+        true  => {Visibility::Visible}
+        false => {Visibility::Hidden}
+    };
+
+    let mut aabb: Option<AABB> = None;
+    for pd in planes.iter(){
+        let p = pd.get_aabb();
+        aabb = Some(match aabb {
+            Some(a) => {AABB{min_x: a.min_x.min(p.min_x), max_x: a.max_x.max(p.max_x), min_z: a.min_z.min(p.min_z), max_z: a.max_z.max(p.max_z)}}
+            None    => {p}
+        });
+    }
+    let aabb = aabb.unwrap_or(AABB{min_x: 0.0, max_x: 0.0, min_z: 0.0, max_z: 0.0});
+    tr.translation = Vec3::new((aabb.min_x + aabb.max_x)/2.0, settings.sea.level, (aabb.min_z + aabb.max_z)/2.0);
+    tr.scale = Vec3::new(aabb.max_x - aabb.min_x + 2.0*WATER_MARGIN, 1.0, aabb.max_z - aabb.min_z + 2.0*WATER_MARGIN);
+
+    // getting the material mutably marks it changed, so only when the color differs
+    let clr = settings.sea.color;
+    let color = Color::rgba(clr[0], clr[1], clr[2], clr[3]);
+    if materials.get(handle).is_some_and(|material| material.base_color != color) {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = color;
+        }
+    }
+}
+
+// Coastline modifier uses project sea level
+fn sync_sea_level(settings:      Res<GlobalSettings>,
+                  mut registry:  ResMut<ModifierRegistry>){
+
+    let Some(mut modifier) = registry.get("Coastline").map(Modifier::from_instance) else {return;};
+    if modifier.params.get("sea_level").and_then(|v| v.as_f64()) == Some(settings.sea.level as f64) {
+        return;
+    }
+    modifier.params["sea_level"] = serde_json::json!(settings.sea.level);
+    registry.set_params(&[modifier]);
+}
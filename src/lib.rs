@@ -0,0 +1,8 @@
+// Explicit returns are the style of this crate
+#![allow(clippy::needless_return)]
+
+// Terrain core (planes, vertices, modifiers, save format) is always available,
+// the editor with egui, picking and infinite grid requires the `editor` feature.
+pub mod core;
+#[cfg(feature = "editor")]
+pub mod editor;
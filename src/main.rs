@@ -4,10 +4,7 @@ use std::time::Duration;
 use bevy::prelude::*;
 use bevy::window::{WindowMode, PresentMode, WindowPlugin, WindowResolution};
 
-pub mod core;
-pub mod editor;
-
-use crate::editor::MTBEditorPlugin;
+use mtb_terrain_gen::editor::MTBEditorPlugin;
 
 pub const HEIGHT: f32 = 900.0;
 // pub const HEIGHT: f32 = 600.0;